@@ -1,21 +1,13 @@
 use clap::error::Result;
 use log::info;
 use serde::Serialize;
+use simlib::stats::GraphDiversity;
 use std::{
     error::Error,
     fs::{self, File},
     path::PathBuf,
 };
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
-#[serde(rename_all = "camelCase")]
-pub struct GraphDiversity {
-    pub(crate) lambda: f32,
-    /// The number of paths we searched for
-    pub(crate) k: usize,
-    pub(crate) diversity: f32,
-}
-
 #[derive(Debug, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Results {
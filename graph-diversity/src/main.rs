@@ -6,9 +6,8 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 mod io;
-mod total_diversity;
 use io::*;
-use total_diversity::*;
+use simlib::stats::diversity::total_graph_diversity;
 
 #[derive(clap::Parser)]
 #[command(name = "graph-diversity", version, about)]
@@ -39,6 +38,9 @@ struct Cli {
     _run: u64,
     #[arg(long = "graph-source", short = 'g')]
     graph_type: network_parser::GraphSource,
+    /// Mirror one-way channels instead of removing them, i.e. use the undirected topology
+    #[arg(long)]
+    undirected: bool,
     verbose: bool,
 }
 
@@ -58,6 +60,9 @@ fn main() {
         graph_source.clone(),
     );
     let graph = match g {
+        Ok(graph) if args.undirected => {
+            simlib::core_types::graph::Graph::to_undirected_sim_graph(&graph, graph_source)
+        }
         Ok(graph) => simlib::core_types::graph::Graph::to_sim_graph(&graph, graph_source),
         Err(e) => {
             error!("Error in graph file {}. Exiting.", e);
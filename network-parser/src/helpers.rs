@@ -1,4 +1,4 @@
-use log::error;
+use log::{error, warn};
 use serde::{Deserialize, Deserializer};
 use serde_aux::prelude::*;
 use std::hash::{Hash, Hasher};
@@ -32,6 +32,8 @@ pub struct RawLnresearchNode {
     #[serde(deserialize_with = "addr_lnr_deserialize")]
     #[serde(default)]
     pub(crate) addresses: Option<Vec<String>>,
+    pub(crate) out_degree: Option<usize>,
+    pub(crate) in_degree: Option<usize>,
 }
 
 #[derive(Deserialize, Debug, Clone, Default, Eq, PartialEq)]
@@ -48,8 +50,9 @@ pub struct LnresearchRawEdge {
     pub channel_id: Option<String>,
     pub source: Option<String>,
     pub destination: Option<String>,
-    pub fee_base_msat: Option<u64>,
-    pub fee_proportional_millionths: Option<u64>,
+    /// Signed so that negative fees of buggy gossip can be detected
+    pub fee_base_msat: Option<i64>,
+    pub fee_proportional_millionths: Option<i64>,
     pub htlc_minimim_msat: Option<u64>,
     pub htlc_maximum_msat: Option<u64>,
     pub cltv_expiry_delta: Option<u64>,
@@ -69,15 +72,28 @@ pub struct LndRawEdge {
     pub node2_policy: Option<NodePolicy>,
 }
 
+/// Why an edge was dropped while parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DropReason {
+    /// At least one of the channel's endpoints is not in the list of nodes
+    UnknownNode,
+    /// Source and destination are the same node
+    SelfLoop,
+    /// A policy or one of its necessary fields is missing
+    MissingPolicy,
+    /// A policy has a max HTLC of zero or negative fees
+    InvalidPolicy,
+}
+
 #[derive(Deserialize, Debug, Clone, Default)]
 pub struct NodePolicy {
-    /// Denominated in msat
+    /// Denominated in msat; signed so that negative fees of buggy gossip can be detected
     #[serde(deserialize_with = "deserialize_option_number_from_string")]
-    pub fee_base_msat: Option<u64>,
+    pub fee_base_msat: Option<i64>,
     /// Denominated in ppm msat
     #[serde(alias = "fee_rate_milli_msat")]
     #[serde(deserialize_with = "deserialize_option_number_from_string")]
-    pub fee_proportional_millionths: Option<u64>,
+    pub fee_proportional_millionths: Option<i64>,
     /// Denominated in msat
     #[serde(alias = "min_htlc")]
     #[serde(deserialize_with = "deserialize_option_number_from_string")]
@@ -118,6 +134,8 @@ impl Node {
             id: raw_node.id.expect("Error in node ID"),
             alias: raw_node.alias.unwrap_or_default(),
             addresses,
+            out_degree: raw_node.out_degree.unwrap_or_default(),
+            in_degree: raw_node.in_degree.unwrap_or_default(),
         }
     }
     pub(crate) fn from_raw_lnd(raw_node: RawLndNode) -> Node {
@@ -134,114 +152,145 @@ impl Node {
             id: raw_node.id.expect("Error in node ID"),
             alias: raw_node.alias.unwrap_or_default(),
             addresses,
+            ..Default::default()
         }
     }
 }
 
 impl Edge {
     /// We remove "orphaned" edges - edges where the source node is not in the list of nodes
+    /// Malformed edges, i.e. with a max HTLC of zero or fees that do not fit usize, are dropped.
     pub(crate) fn from_lnresearch_raw(raw_edge: &LnresearchRawEdge) -> Option<Edge> {
-        if raw_edge.fee_base_msat.is_none()
-            || raw_edge.fee_proportional_millionths.is_none()
-            || raw_edge.htlc_maximum_msat.is_none()
-        {
-            None
-        } else {
-            Some(Edge {
-                channel_id: raw_edge.channel_id.clone().expect("scid not found"),
-                source: raw_edge.source.clone().unwrap_or_default(),
-                destination: raw_edge.destination.clone().unwrap_or_default(),
-                fee_base_msat: raw_edge
-                    .fee_base_msat
-                    .expect("Error in fee_base_msat field")
-                    .try_into()
-                    .expect("Error in fee_base_msat field"),
-                fee_proportional_millionths: raw_edge
-                    .fee_proportional_millionths
-                    .expect("Error in fee_proportional_millionths field")
-                    .try_into()
-                    .expect("Error in fee_proportional_millionths field"),
-                htlc_minimim_msat: raw_edge
-                    .htlc_minimim_msat
-                    .unwrap_or_default()
-                    .try_into()
-                    .unwrap_or(usize::default()),
-                htlc_maximum_msat: raw_edge
-                    .htlc_maximum_msat
-                    .unwrap_or_default()
-                    .try_into()
-                    .unwrap_or(usize::default()),
-                cltv_expiry_delta: raw_edge
-                    .cltv_expiry_delta
-                    .unwrap_or_default()
-                    .try_into()
-                    .unwrap_or(usize::default()),
-                balance: 0,
-                liquidity: 0,
-                capacity: 0,
+        match (
+            raw_edge.fee_base_msat,
+            raw_edge.fee_proportional_millionths,
+            raw_edge.htlc_maximum_msat,
+        ) {
+            (Some(fee_base_msat), Some(fee_proportional_millionths), Some(htlc_maximum_msat)) => {
+                let channel_id = raw_edge.channel_id.clone().expect("scid not found");
+                let (fee_base_msat, fee_proportional_millionths) = Self::validate_policy(
+                    &channel_id,
+                    fee_base_msat,
+                    fee_proportional_millionths,
+                    htlc_maximum_msat,
+                )?;
+                Some(Edge {
+                    channel_id,
+                    source: raw_edge.source.clone().unwrap_or_default(),
+                    destination: raw_edge.destination.clone().unwrap_or_default(),
+                    fee_base_msat,
+                    fee_proportional_millionths,
+                    htlc_minimim_msat: raw_edge
+                        .htlc_minimim_msat
+                        .unwrap_or_default()
+                        .try_into()
+                        .unwrap_or(usize::default()),
+                    htlc_maximum_msat: raw_edge
+                        .htlc_maximum_msat
+                        .unwrap_or_default()
+                        .try_into()
+                        .unwrap_or(usize::default()),
+                    cltv_expiry_delta: raw_edge
+                        .cltv_expiry_delta
+                        .unwrap_or_default()
+                        .try_into()
+                        .unwrap_or(usize::default()),
+                    balance: 0,
+                    liquidity: 0,
+                    capacity: 0,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the fees (base, proportional) of a channel direction if its policy is usable.
+    /// Buggy gossip may announce a max HTLC of zero or negative fees; such edges are logged and
+    /// should be dropped.
+    fn validate_policy(
+        channel_id: &str,
+        fee_base_msat: i64,
+        fee_proportional_millionths: i64,
+        htlc_maximum_msat: u64,
+    ) -> Option<(usize, usize)> {
+        if htlc_maximum_msat == 0 {
+            warn!(
+                "Dropping edge of channel {} with htlc_maximum_msat of 0.",
+                channel_id
+            );
+            return None;
+        }
+        match (
+            usize::try_from(fee_base_msat),
+            usize::try_from(fee_proportional_millionths),
+        ) {
+            (Ok(fee_base_msat), Ok(fee_proportional_millionths)) => {
+                Some((fee_base_msat, fee_proportional_millionths))
+            }
+            _ => {
+                warn!(
+                    "Dropping edge of channel {} with invalid fees (base {}, proportional {}).",
+                    channel_id, fee_base_msat, fee_proportional_millionths
+                );
+                None
+            }
+        }
+    }
+    /// True if both policies carry the fees and the max HTLC.
+    fn has_complete_lnd_policies(raw_edge: &LndRawEdge) -> bool {
+        [&raw_edge.node1_policy, &raw_edge.node2_policy]
+            .iter()
+            .all(|policy| {
+                matches!(policy, Some(policy) if policy.fee_base_msat.is_some()
+                    && policy.fee_proportional_millionths.is_some()
+                    && policy.htlc_maximum_msat.is_some())
             })
+    }
+
+    /// Why an LND edge was dropped by [`Edge::from_lnd_raw`].
+    pub(crate) fn lnd_drop_reason(raw_edge: &LndRawEdge) -> DropReason {
+        if raw_edge.source == raw_edge.destination {
+            DropReason::SelfLoop
+        } else if !Self::has_complete_lnd_policies(raw_edge) {
+            DropReason::MissingPolicy
+        } else {
+            DropReason::InvalidPolicy
         }
     }
+
     /// We remove "orphaned" edges - edges where the source node is not in the list of nodes
+    /// Channels with a malformed policy in either direction are dropped, see
+    /// [`Edge::from_lnresearch_raw`], as are self-loops.
     pub(crate) fn from_lnd_raw(raw_edge: &LndRawEdge) -> Option<(Edge, Edge)> {
-        if raw_edge.node1_policy.is_none()
-            || raw_edge.node2_policy.is_none()
-            || raw_edge
-                .node1_policy
-                .as_ref()
-                .unwrap()
-                .fee_base_msat
-                .is_none()
-            || raw_edge
-                .node1_policy
-                .as_ref()
-                .unwrap()
-                .fee_proportional_millionths
-                .is_none()
-            || raw_edge
-                .node1_policy
-                .as_ref()
-                .unwrap()
-                .htlc_maximum_msat
-                .is_none()
-            || raw_edge
-                .node2_policy
-                .as_ref()
-                .unwrap()
-                .fee_base_msat
-                .is_none()
-            || raw_edge
-                .node2_policy
-                .as_ref()
-                .unwrap()
-                .fee_proportional_millionths
-                .is_none()
-            || raw_edge
-                .node2_policy
-                .as_ref()
-                .unwrap()
-                .htlc_maximum_msat
-                .is_none()
-        {
+        if raw_edge.source == raw_edge.destination {
+            warn!(
+                "Dropping self-loop channel {}.",
+                raw_edge.channel_id.as_deref().unwrap_or_default()
+            );
+            None
+        } else if !Self::has_complete_lnd_policies(raw_edge) {
             None
         } else {
             let node1_policy = raw_edge.node1_policy.clone().unwrap(); // safe because of the earlier check
             let node2_policy = raw_edge.node2_policy.clone().unwrap();
+            let channel_id = raw_edge.channel_id.clone().expect("scid not found");
+            let validate = |policy: &NodePolicy| {
+                Self::validate_policy(
+                    &channel_id,
+                    policy.fee_base_msat.unwrap_or_default(),
+                    policy.fee_proportional_millionths.unwrap_or_default(),
+                    policy.htlc_maximum_msat.unwrap_or_default(),
+                )
+            };
+            let (node1_fee_base_msat, node1_fee_proportional_millionths) = validate(&node1_policy)?;
+            let (node2_fee_base_msat, node2_fee_proportional_millionths) = validate(&node2_policy)?;
             Some((
                 Edge {
                     channel_id: raw_edge.channel_id.clone().expect("scid not found"),
                     source: raw_edge.source.clone().unwrap_or_default(),
                     destination: raw_edge.destination.clone().unwrap_or_default(),
-                    fee_base_msat: node1_policy
-                        .fee_base_msat
-                        .expect("Error in fee_base_msat field")
-                        .try_into()
-                        .expect("Error in fee_base_msat field"),
-                    fee_proportional_millionths: node1_policy
-                        .fee_proportional_millionths
-                        .expect("Error in fee_proportional_millionths field")
-                        .try_into()
-                        .expect("Error in fee_proportional_millionths field"),
+                    fee_base_msat: node1_fee_base_msat,
+                    fee_proportional_millionths: node1_fee_proportional_millionths,
                     htlc_minimim_msat: node1_policy
                         .htlc_minimim_msat
                         .unwrap_or_default()
@@ -270,16 +319,8 @@ impl Edge {
                     channel_id: raw_edge.channel_id.clone().expect("scid not found"),
                     destination: raw_edge.source.clone().unwrap_or_default(),
                     source: raw_edge.destination.clone().unwrap_or_default(),
-                    fee_base_msat: node2_policy
-                        .fee_base_msat
-                        .expect("Error in fee_base_msat field")
-                        .try_into()
-                        .expect("Error in fee_base_msat field"),
-                    fee_proportional_millionths: node2_policy
-                        .fee_proportional_millionths
-                        .expect("Error in fee_proportional_millionths field")
-                        .try_into()
-                        .expect("Error in fee_proportional_millionths field"),
+                    fee_base_msat: node2_fee_base_msat,
+                    fee_proportional_millionths: node2_fee_proportional_millionths,
                     htlc_minimim_msat: node2_policy
                         .htlc_minimim_msat
                         .unwrap_or_default()
@@ -502,7 +543,7 @@ mod tests {
             ],
             "edges": []
             }"##;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         assert_eq!(graph.nodes.len(), 3);
     }
 
@@ -550,7 +591,7 @@ mod tests {
                 }
               ]
             }"##;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         let actual = graph.edge_count();
         let expected = 0;
         assert_eq!(expected, actual);
@@ -601,7 +642,7 @@ mod tests {
             ]
             }"##;
         let expected = 1000 * 1000;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 2);
         for e in graph.get_edges_as_vec_vec().into_iter().flatten() {
@@ -643,7 +684,7 @@ mod tests {
             ],
             "edges": []
             }"##;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         assert_eq!(graph.nodes.len(), 3);
         let expected: HashMap<ID, Vec<Address>> = HashMap::from([
             (
@@ -712,4 +753,78 @@ mod tests {
             assert_eq!(*expected.get(&node.id).unwrap(), node.addresses);
         }
     }
+
+    fn lnresearch_edge(fee_base_msat: &str, htlc_maximum_msat: &str) -> LnresearchRawEdge {
+        let json_str = format!(
+            r##"{{
+                "scid": "714105x2146x0/0",
+                "source": "validnode",
+                "destination": "othervalidnode",
+                "fee_base_msat": {},
+                "fee_proportional_millionths": 270,
+                "htlc_minimim_msat": 1000,
+                "htlc_maximum_msat": {},
+                "cltv_expiry_delta": 34
+            }}"##,
+            fee_base_msat, htlc_maximum_msat
+        );
+        serde_json::from_str(&json_str).unwrap()
+    }
+
+    fn lnd_edge(fee_rate_milli_msat: &str, max_htlc_msat: &str) -> LndRawEdge {
+        let json_str = format!(
+            r##"{{
+                "channel_id": "659379322247708673",
+                "node1_pub": "node1",
+                "node2_pub": "node2",
+                "capacity": "1000000",
+                "node1_policy": {{
+                    "time_lock_delta": 14,
+                    "min_htlc": "1000",
+                    "fee_base_msat": "1000",
+                    "fee_rate_milli_msat": "1",
+                    "max_htlc_msat": "990000000"
+                }},
+                "node2_policy": {{
+                    "time_lock_delta": 14,
+                    "min_htlc": "1000",
+                    "fee_base_msat": "1000",
+                    "fee_rate_milli_msat": "{}",
+                    "max_htlc_msat": "{}"
+                }}
+            }}"##,
+            fee_rate_milli_msat, max_htlc_msat
+        );
+        serde_json::from_str(&json_str).unwrap()
+    }
+
+    #[test]
+    fn lnresearch_edge_with_zero_max_htlc_is_dropped() {
+        assert!(Edge::from_lnresearch_raw(&lnresearch_edge("5", "5564111000")).is_some());
+        assert!(Edge::from_lnresearch_raw(&lnresearch_edge("5", "0")).is_none());
+    }
+
+    #[test]
+    fn lnresearch_edge_with_negative_fee_is_dropped() {
+        assert!(Edge::from_lnresearch_raw(&lnresearch_edge("-5", "5564111000")).is_none());
+    }
+
+    #[test]
+    fn lnd_edge_with_zero_max_htlc_is_dropped() {
+        assert!(Edge::from_lnd_raw(&lnd_edge("1", "990000000")).is_some());
+        assert!(Edge::from_lnd_raw(&lnd_edge("1", "0")).is_none());
+    }
+
+    #[test]
+    fn lnd_edge_with_negative_fee_is_dropped() {
+        assert!(Edge::from_lnd_raw(&lnd_edge("-1", "990000000")).is_none());
+    }
+
+    #[test]
+    fn lnd_self_loop_is_dropped() {
+        let mut raw_edge = lnd_edge("1", "990000000");
+        raw_edge.destination = raw_edge.source.clone();
+        assert!(Edge::from_lnd_raw(&raw_edge).is_none());
+        assert_eq!(Edge::lnd_drop_reason(&raw_edge), DropReason::SelfLoop);
+    }
 }
@@ -1,3 +1,4 @@
+use log::warn;
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
@@ -27,6 +28,13 @@ pub struct Node {
     pub id: ID,
     pub alias: String,
     pub addresses: Vec<Address>,
+    /// Number of channels announced by the node; taken from the gossip metadata if available,
+    /// otherwise counted from the node's edges
+    #[serde(default)]
+    pub out_degree: usize,
+    /// Number of channels announced towards the node, see `out_degree`
+    #[serde(default)]
+    pub in_degree: usize,
 }
 
 #[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
@@ -63,6 +71,35 @@ pub struct Edge {
     pub capacity: usize,
 }
 
+/// Number of channels dropped while parsing, per reason
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DroppedEdges {
+    /// At least one of the channel's endpoints is not in the list of nodes
+    pub unknown_node: usize,
+    /// Channels from a node to itself
+    pub self_loop: usize,
+    /// Channels missing a policy or one of its necessary fields
+    pub missing_policy: usize,
+    /// Channels with a max HTLC of zero or negative fees
+    pub invalid_policy: usize,
+}
+
+impl DroppedEdges {
+    fn record(&mut self, reason: DropReason) {
+        match reason {
+            DropReason::UnknownNode => self.unknown_node += 1,
+            DropReason::SelfLoop => self.self_loop += 1,
+            DropReason::MissingPolicy => self.missing_policy += 1,
+            DropReason::InvalidPolicy => self.invalid_policy += 1,
+        }
+    }
+
+    /// Total number of dropped channels
+    pub fn total(&self) -> usize {
+        self.unknown_node + self.self_loop + self.missing_policy + self.invalid_policy
+    }
+}
+
 pub type ID = String;
 pub type NodeRanks = Vec<ID>;
 
@@ -77,6 +114,19 @@ impl Graph {
         }
     }
 
+    /// Same as [`Graph::from_json_str`] for documents that have already been parsed.
+    pub fn from_json_value(
+        value: serde_json::Value,
+        graph_source: GraphSource,
+    ) -> Result<Graph, serde_json::Error> {
+        match graph_source {
+            GraphSource::Lnd => Ok(Self::from_raw_lnd_graph(serde_json::from_value(value)?).0),
+            GraphSource::Lnresearch => Ok(Self::from_raw_lnresearch_graph(serde_json::from_value(
+                value,
+            )?)),
+        }
+    }
+
     pub fn from_json_file(
         path: &Path,
         graph_source: GraphSource,
@@ -103,9 +153,27 @@ impl Graph {
             .collect()
     }
 
+    /// Lenient alternative to [`Graph::from_json_str`] which accepts JSON5, i.e. comments and
+    /// trailing commas. Mostly useful for hand-edited fixtures.
+    pub fn from_json5_str(
+        json_str: &str,
+        graph_source: GraphSource,
+    ) -> Result<Graph, json5::Error> {
+        match graph_source {
+            GraphSource::Lnd => Ok(Self::from_raw_lnd_graph(json5::from_str(json_str)?).0),
+            GraphSource::Lnresearch => {
+                Ok(Self::from_raw_lnresearch_graph(json5::from_str(json_str)?))
+            }
+        }
+    }
+
     pub fn from_lnresearch_json_str(json_str: &str) -> Result<Graph, serde_json::Error> {
         let raw_graph: RawLnresearchGraph =
             serde_json::from_str(json_str).expect("Error deserialising JSON str!");
+        Ok(Self::from_raw_lnresearch_graph(raw_graph))
+    }
+
+    fn from_raw_lnresearch_graph(raw_graph: RawLnresearchGraph) -> Graph {
         let nodes = Self::nodes_from_raw_lnresearch_graph(&raw_graph.nodes);
         let mut edges: HashMap<ID, HashSet<Edge>> = HashMap::with_capacity(raw_graph.edges.len());
         // discard edges with unknown IDs
@@ -126,8 +194,7 @@ impl Graph {
                         };
                         nodes.contains(&src_node) && nodes.contains(&dest_node)
                     })
-                    .filter(|raw_edge| Edge::from_lnresearch_raw(&(*raw_edge).clone()).is_some())
-                    .map(|raw_edge| Edge::from_lnresearch_raw(raw_edge).unwrap())
+                    .filter_map(Edge::from_lnresearch_raw)
                     .collect()
             })
             .collect();
@@ -142,15 +209,47 @@ impl Graph {
                 };
             }
         }
-        Ok(Graph { nodes, edges })
+        let without_degrees: HashSet<ID> = raw_graph
+            .nodes
+            .iter()
+            .filter(|raw_node| raw_node.out_degree.is_none() || raw_node.in_degree.is_none())
+            .filter_map(|raw_node| raw_node.id.clone())
+            .collect();
+        let nodes = Self::count_missing_degrees(nodes, &edges, &without_degrees);
+        Graph { nodes, edges }
     }
+
     pub fn from_lnd_json_str(json_str: &str) -> Result<Graph, serde_json::Error> {
-        let raw_graph: RawLndGraph =
-            serde_json::from_str(json_str).expect("Error deserialising JSON str!");
+        Self::from_lnd_json_str_with_stats(json_str).map(|(graph, _)| graph)
+    }
+
+    /// Same as [`Graph::from_lnd_json_str`] but also returns how many channels were dropped
+    /// for each reason.
+    pub fn from_lnd_json_str_with_stats(
+        json_str: &str,
+    ) -> Result<(Graph, DroppedEdges), serde_json::Error> {
+        let raw_graph: RawLndGraph = serde_json::from_str(json_str)?;
+        let (graph, dropped, _) = Self::from_raw_lnd_graph(raw_graph);
+        Ok((graph, dropped))
+    }
+
+    /// Same as [`Graph::from_lnd_json_str`] but returns the edges whose endpoints are missing
+    /// instead of discarding them, so they can be merged once the nodes are known.
+    pub fn from_lnd_json_str_keep_orphans(
+        json_str: &str,
+    ) -> Result<(Graph, Vec<Edge>), serde_json::Error> {
+        let raw_graph: RawLndGraph = serde_json::from_str(json_str)?;
+        let (graph, _, orphans) = Self::from_raw_lnd_graph(raw_graph);
+        Ok((graph, orphans))
+    }
+
+    fn from_raw_lnd_graph(raw_graph: RawLndGraph) -> (Graph, DroppedEdges, Vec<Edge>) {
         let nodes = Self::nodes_from_raw_lnd_graph(&raw_graph.nodes);
         let mut edges: HashMap<ID, HashSet<Edge>> = HashMap::with_capacity(raw_graph.edges.len());
-        // discard edges with unknown IDs
+        let mut dropped = DroppedEdges::default();
+        // edges with unknown IDs are left out of the graph
         let mut edges_vec = vec![];
+        let mut orphans = vec![];
         for raw_edge in raw_graph.edges {
             let src_node = Node {
                 id: raw_edge.source.clone().unwrap(),
@@ -164,6 +263,14 @@ impl Graph {
                 if let Some(edge) = Edge::from_lnd_raw(&(raw_edge).clone()) {
                     edges_vec.push(edge.0);
                     edges_vec.push(edge.1);
+                } else {
+                    dropped.record(Edge::lnd_drop_reason(&raw_edge));
+                }
+            } else {
+                dropped.record(DropReason::UnknownNode);
+                if let Some(edge) = Edge::from_lnd_raw(&raw_edge) {
+                    orphans.push(edge.0);
+                    orphans.push(edge.1);
                 }
             }
         }
@@ -176,8 +283,38 @@ impl Graph {
                 }
             };
         }
-        Ok(Graph { nodes, edges })
+        // LND's describegraph carries no degrees
+        let without_degrees: HashSet<ID> = nodes.iter().map(|n| n.id.clone()).collect();
+        let nodes = Self::count_missing_degrees(nodes, &edges, &without_degrees);
+        (Graph { nodes, edges }, dropped, orphans)
     }
+
+    /// Sets the degrees of the nodes in `without_degrees` to the number of their out- and
+    /// in-edges
+    fn count_missing_degrees(
+        nodes: HashSet<Node>,
+        edges: &HashMap<ID, HashSet<Edge>>,
+        without_degrees: &HashSet<ID>,
+    ) -> HashSet<Node> {
+        if without_degrees.is_empty() {
+            return nodes;
+        }
+        let mut in_degrees: HashMap<&ID, usize> = HashMap::new();
+        for edge in edges.values().flatten() {
+            *in_degrees.entry(&edge.destination).or_default() += 1;
+        }
+        nodes
+            .into_iter()
+            .map(|mut node| {
+                if without_degrees.contains(&node.id) {
+                    node.out_degree = edges.get(&node.id).map(HashSet::len).unwrap_or_default();
+                    node.in_degree = in_degrees.get(&node.id).copied().unwrap_or_default();
+                }
+                node
+            })
+            .collect()
+    }
+
     pub fn get_nodes(self) -> HashSet<Node> {
         self.nodes
     }
@@ -224,6 +361,71 @@ pub fn read_node_rankings_from_file(
     Ok(ranks)
 }
 
+/// Reads node rankings from a CSV file with one `id,score` row per line and returns them sorted
+/// by descending score. Rows without a valid score, e.g. a header, are skipped.
+pub fn read_node_rankings_with_scores(path: &Path) -> Result<Vec<(ID, f32)>, std::io::Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut ranks = vec![];
+    for line in reader.lines().map_while(Result::ok) {
+        match line.split_once(',') {
+            Some((id, score)) => match score.trim().parse::<f32>() {
+                Ok(score) if !score.is_nan() => ranks.push((id.trim().to_owned(), score)),
+                _ => warn!("Skipping ranking without a valid score {}.", line),
+            },
+            None if line.trim().is_empty() => {}
+            None => warn!("Skipping ranking without a score {}.", line),
+        }
+    }
+    ranks.sort_by(|a: &(ID, f32), b| b.1.total_cmp(&a.1));
+    Ok(ranks)
+}
+
+/// Reads node rankings with one node ID per line and scales them to [0, 1] by their position so
+/// that rankings of different metrics can be combined. Only nodes in `nodes` are returned.
+pub fn read_normalised_node_rankings(
+    nodes: &[ID],
+    path: &Path,
+) -> Result<Vec<(ID, f32)>, std::io::Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let ranks: NodeRanks = reader
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| nodes.contains(line))
+        .collect();
+    let last = ranks.len().saturating_sub(1).max(1) as f32;
+    Ok(ranks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, id)| (id, 1.0 - idx as f32 / last))
+        .collect())
+}
+
+/// Reads node rankings with one `id,score` row per line and min-max normalises them over all
+/// scores in the file. Only nodes in `nodes` are returned.
+pub fn read_normalised_node_rankings_with_scores(
+    nodes: &[ID],
+    path: &Path,
+) -> Result<Vec<(ID, f32)>, std::io::Error> {
+    let scores = read_node_rankings_with_scores(path)?;
+    let (min, max) = scores
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), (_, score)| {
+            (min.min(*score), max.max(*score))
+        });
+    Ok(scores
+        .into_iter()
+        .filter(|(id, _)| nodes.contains(id))
+        .map(|(id, score)| {
+            let normalised = if max > min {
+                (score - min) / (max - min)
+            } else {
+                1.0
+            };
+            (id, normalised)
+        })
+        .collect())
+}
+
 impl Hash for Node {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.id.hash(state);
@@ -298,8 +500,13 @@ mod tests {
                 network: "tcp".to_string(),
                 addr: "80.115.186.52:9735".to_string(),
             }],
+            out_degree: 25,
+            in_degree: 9,
         };
         assert_eq!(*actual, expected);
+        // equality only compares IDs
+        assert_eq!(actual.out_degree, expected.out_degree);
+        assert_eq!(actual.in_degree, expected.in_degree);
     }
 
     #[test]
@@ -414,10 +621,48 @@ mod tests {
             id: "021f0f2a5b46871b23f690a5be893f5b3ec37cf5a0fd8b89872234e984df35ea32".to_string(),
             alias: String::default(),
             addresses: vec![],
+            ..Default::default()
         };
         assert_eq!(*actual, expected);
     }
 
+    #[test]
+    fn missing_degrees_are_counted_from_edges() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::from_json_file(path_to_file, GraphSource::Lnresearch).unwrap();
+        let degrees = |id: &str| {
+            let node = graph.nodes.iter().find(|n| n.id == id).unwrap();
+            (node.out_degree, node.in_degree)
+        };
+        // as announced in the file
+        assert_eq!(degrees("chan"), (30, 44));
+        let json_str = r##"{
+            "nodes": [
+                { "id": "alice" },
+                { "id": "bob", "out_degree": 7, "in_degree": 3 }
+            ],
+            "adjacency": [
+                [
+                  {
+                    "scid": "alice1",
+                    "source": "alice",
+                    "destination": "bob",
+                    "fee_base_msat": 5,
+                    "fee_proportional_millionths": 270,
+                    "htlc_minimim_msat": 1000,
+                    "htlc_maximum_msat": 5564111000,
+                    "cltv_expiry_delta": 34
+                  }
+                ]
+              ]
+            }"##;
+        let graph = Graph::from_lnresearch_json_str(json_str).unwrap();
+        let alice = graph.nodes.iter().find(|n| n.id == "alice").unwrap();
+        assert_eq!((alice.out_degree, alice.in_degree), (1, 0));
+        let bob = graph.nodes.iter().find(|n| n.id == "bob").unwrap();
+        assert_eq!((bob.out_degree, bob.in_degree), (7, 3));
+    }
+
     #[test]
     fn graph_from_lnresearch_json_file() {
         let path_to_file = Path::new("../test_data/trivial.json");
@@ -553,7 +798,7 @@ mod tests {
             "adjacency": [
               ]
             }"##;
-        let graph = Graph::from_lnresearch_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnresearch_json_str(json_str).unwrap();
         let actual = graph.get_edges_for_node(
             &"021f0f2a5b46871b23f690a5be893f5b3ec37cf5a0fd8b89872234e984df35ea32".to_string(),
         );
@@ -627,12 +872,223 @@ mod tests {
                 ]
               ]
             }"##;
-        let graph = Graph::from_lnresearch_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnresearch_json_str(json_str).unwrap();
         let actual = graph.edge_count();
         let expected = 2;
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn graph_from_commented_json5_str() {
+        let json_str = r##"{
+            // hand-edited fixture
+            "nodes": [
+                {
+                    "id": "021f0f2a5b46871b23f690a5be893f5b3ec37cf5a0fd8b89872234e984df35ea32",
+                    "alias": "MilliBit",
+                    "addresses": "ipv4://83.85.142.36:9735",
+                },
+                {
+                    "id": "03271338633d2d37b285dae4df40b413d8c6c791fbee7797bc5dc70812196d7d5c",
+                    "alias": "MilliBit",
+                    "addresses": "ipv4://83.85.142.36:9735", // trailing comma
+                },
+            ],
+            "adjacency": [
+                [
+                  {
+                    "scid": "714105x2146x0/0",
+                    "source": "021f0f2a5b46871b23f690a5be893f5b3ec37cf5a0fd8b89872234e984df35ea32",
+                    "destination": "03271338633d2d37b285dae4df40b413d8c6c791fbee7797bc5dc70812196d7d5c",
+                    "fee_base_msat": 5,
+                    "fee_proportional_millionths": 270,
+                    "htlc_minimim_msat": 1000,
+                    "htlc_maximum_msat": 5564111000,
+                    "cltv_expiry_delta": 34,
+                  },
+                ],
+              ],
+            }"##;
+        let graph = Graph::from_json5_str(json_str, GraphSource::Lnresearch).unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        let edges = graph.get_edges_for_node(
+            &"021f0f2a5b46871b23f690a5be893f5b3ec37cf5a0fd8b89872234e984df35ea32".to_string(),
+        );
+        assert_eq!(edges.len(), 1);
+    }
+
+    #[test]
+    fn graph_from_json_value() {
+        let lnd_json_str = r##"{
+            "nodes": [
+                { "pub_key": "alice" },
+                { "pub_key": "bob" }
+            ],
+            "edges": [
+                {
+                    "channel_id": "1",
+                    "node1_pub": "alice",
+                    "node2_pub": "bob",
+                    "capacity": "1000000",
+                    "node1_policy": {
+                        "time_lock_delta": 14,
+                        "min_htlc": "1000",
+                        "fee_base_msat": "1000",
+                        "fee_rate_milli_msat": "1",
+                        "max_htlc_msat": "990000000"
+                    },
+                    "node2_policy": {
+                        "time_lock_delta": 40,
+                        "min_htlc": "1000",
+                        "fee_base_msat": "1000",
+                        "fee_rate_milli_msat": "1",
+                        "max_htlc_msat": "990000000"
+                    }
+                }
+            ]
+        }"##;
+        for (json_str, graph_source) in [
+            (
+                fs::read_to_string("../test_data/lnbook_example.json").unwrap(),
+                GraphSource::Lnresearch,
+            ),
+            (lnd_json_str.to_string(), GraphSource::Lnd),
+        ] {
+            let expected = Graph::from_json_str(&json_str, graph_source.clone()).unwrap();
+            let value: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+            let actual = Graph::from_json_value(value, graph_source).unwrap();
+            assert_eq!(actual.nodes, expected.nodes);
+            assert_eq!(actual.edges, expected.edges);
+        }
+    }
+
+    #[test]
+    fn dropped_lnd_edges_are_counted() {
+        let edge = |channel_id: &str, source: &str, destination: &str, node2_policy: &str| {
+            format!(
+                r##"{{
+                    "channel_id": "{}",
+                    "node1_pub": "{}",
+                    "node2_pub": "{}",
+                    "capacity": "1000000",
+                    "node1_policy": {{
+                        "time_lock_delta": 14,
+                        "min_htlc": "1000",
+                        "fee_base_msat": "1000",
+                        "fee_rate_milli_msat": "1",
+                        "max_htlc_msat": "990000000"
+                    }},
+                    "node2_policy": {}
+                }}"##,
+                channel_id, source, destination, node2_policy
+            )
+        };
+        let policy = |fee_base_msat: &str| {
+            format!(
+                r##"{{
+                    "time_lock_delta": 14,
+                    "min_htlc": "1000",
+                    "fee_base_msat": "{}",
+                    "fee_rate_milli_msat": "1",
+                    "max_htlc_msat": "990000000"
+                }}"##,
+                fee_base_msat
+            )
+        };
+        let edges = [
+            edge("1", "alice", "bob", &policy("1000")),
+            edge("2", "alice", "alice", &policy("1000")),
+            edge("3", "bob", "bob", &policy("1000")),
+            edge("4", "bob", "chan", "null"),
+            edge("5", "alice", "chan", &policy("-1000")),
+            edge("6", "alice", "dina", &policy("1000")),
+        ];
+        let json_str = format!(
+            r##"{{
+                "nodes": [
+                    {{ "pub_key": "alice" }},
+                    {{ "pub_key": "bob" }},
+                    {{ "pub_key": "chan" }}
+                ],
+                "edges": [{}]
+            }}"##,
+            edges.join(",")
+        );
+        let (graph, dropped) = Graph::from_lnd_json_str_with_stats(&json_str).unwrap();
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(
+            dropped,
+            DroppedEdges {
+                unknown_node: 1,
+                self_loop: 2,
+                missing_policy: 1,
+                invalid_policy: 1,
+            }
+        );
+        assert_eq!(dropped.total(), 5);
+    }
+
+    #[test]
+    fn malformed_lnd_json_is_an_error() {
+        assert!(Graph::from_lnd_json_str_with_stats(r#"{"nodes": ["#).is_err());
+        assert!(Graph::from_lnd_json_str(r#"{"nodes": []}"#).is_err());
+    }
+
+    #[test]
+    fn orphaned_lnd_edges_are_kept() {
+        let edge = |channel_id: &str, source: &str, destination: &str| {
+            format!(
+                r##"{{
+                    "channel_id": "{}",
+                    "node1_pub": "{}",
+                    "node2_pub": "{}",
+                    "capacity": "1000000",
+                    "node1_policy": {{
+                        "time_lock_delta": 14,
+                        "min_htlc": "1000",
+                        "fee_base_msat": "1000",
+                        "fee_rate_milli_msat": "1",
+                        "max_htlc_msat": "990000000"
+                    }},
+                    "node2_policy": {{
+                        "time_lock_delta": 40,
+                        "min_htlc": "1000",
+                        "fee_base_msat": "1000",
+                        "fee_rate_milli_msat": "1",
+                        "max_htlc_msat": "990000000"
+                    }}
+                }}"##,
+                channel_id, source, destination
+            )
+        };
+        let json_str = format!(
+            r##"{{
+                "nodes": [
+                    {{ "pub_key": "alice" }},
+                    {{ "pub_key": "bob" }}
+                ],
+                "edges": [{},{}]
+            }}"##,
+            edge("1", "alice", "bob"),
+            edge("2", "bob", "chan")
+        );
+        let (graph, orphans) = Graph::from_lnd_json_str_keep_orphans(&json_str).unwrap();
+        assert!(graph
+            .edges
+            .values()
+            .flatten()
+            .all(|e| e.channel_id.starts_with('1')));
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(orphans.len(), 2);
+        assert!(orphans.iter().all(|e| e.channel_id.starts_with('2')));
+        assert!(orphans
+            .iter()
+            .any(|e| e.source == "bob" && e.destination == "chan"));
+        assert!(orphans
+            .iter()
+            .any(|e| e.source == "chan" && e.destination == "bob"));
+    }
+
     #[test]
     fn discard_edges_without_necessary_fields() {
         let json_str = r##"{
@@ -683,7 +1139,7 @@ mod tests {
                 ]
               ]
             }"##;
-        let graph = Graph::from_lnresearch_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnresearch_json_str(json_str).unwrap();
         let actual = graph.edge_count();
         let expected = 0;
         assert_eq!(expected, actual);
@@ -694,7 +1150,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial_connected.json");
         let graph = Graph::from_json_file(path_to_file, GraphSource::Lnresearch).unwrap();
         let actual = graph.get_node_ids();
-        let expected = vec!["025".to_owned(), "034".to_owned(), "036".to_owned()];
+        let expected = ["025".to_owned(), "034".to_owned(), "036".to_owned()];
         assert_eq!(actual.len(), expected.len());
         for id in actual {
             assert!(expected.contains(&id));
@@ -712,7 +1168,7 @@ mod tests {
         assert!(actual.is_ok());
         let actual = actual.unwrap();
         assert_eq!(actual.len(), nodes.len());
-        let expected = vec!["036".to_owned(), "025".to_owned(), "034".to_owned()];
+        let expected = ["036".to_owned(), "025".to_owned(), "034".to_owned()];
         assert_eq!(actual.len(), expected.len());
         for id in actual {
             assert!(expected.contains(&id));
@@ -723,13 +1179,67 @@ mod tests {
         assert!(actual.is_ok());
         let actual = actual.unwrap();
         assert_eq!(actual.len(), nodes.len());
-        let expected = vec!["036".to_owned(), "025".to_owned(), "034".to_owned()];
+        let expected = ["036".to_owned(), "025".to_owned(), "034".to_owned()];
         assert_eq!(actual.len(), expected.len());
         for id in actual {
             assert!(expected.contains(&id));
         }
     }
 
+    #[test]
+    fn read_rankings_with_scores() {
+        let mut rankings_file = NamedTempFile::new().expect("Error opening NamedTempFile.");
+        let _ = writeln!(rankings_file, "id,score");
+        let _ = writeln!(rankings_file, "034,0.2");
+        let _ = writeln!(rankings_file, "036,0.7");
+        let _ = writeln!(rankings_file, "043,0.5");
+        let _ = writeln!(rankings_file, "025,0.9");
+        let actual = read_node_rankings_with_scores(rankings_file.path()).unwrap();
+        let expected = vec![
+            ("025".to_owned(), 0.9),
+            ("036".to_owned(), 0.7),
+            ("043".to_owned(), 0.5),
+            ("034".to_owned(), 0.2),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn read_normalised_rankings() {
+        let mut rankings_file = NamedTempFile::new().expect("Error opening NamedTempFile.");
+        for id in ["036", "043", "034", "025"] {
+            let _ = writeln!(rankings_file, "{}", id);
+        }
+        let nodes = ["036".to_string(), "034".to_string(), "025".to_string()];
+        let actual = read_normalised_node_rankings(&nodes, rankings_file.path()).unwrap();
+        let expected = vec![
+            ("036".to_owned(), 1.0),
+            ("034".to_owned(), 0.5),
+            ("025".to_owned(), 0.0),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn read_normalised_rankings_with_scores() {
+        let mut rankings_file = NamedTempFile::new().expect("Error opening NamedTempFile.");
+        let _ = writeln!(rankings_file, "id,score");
+        let _ = writeln!(rankings_file, "034,2");
+        let _ = writeln!(rankings_file, "036,4");
+        let _ = writeln!(rankings_file, "043,10");
+        let _ = writeln!(rankings_file, "025,0");
+        // 043 is not in the graph but still sets the scale
+        let nodes = ["036".to_string(), "034".to_string(), "025".to_string()];
+        let actual =
+            read_normalised_node_rankings_with_scores(&nodes, rankings_file.path()).unwrap();
+        let expected = vec![
+            ("036".to_owned(), 0.4),
+            ("034".to_owned(), 0.2),
+            ("025".to_owned(), 0.0),
+        ];
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn edges_from_lnd_json_str() {
         let json_str = r##"{
@@ -774,7 +1284,7 @@ mod tests {
                 }
             ]
             }"##;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 2);
     }
@@ -815,7 +1325,7 @@ mod tests {
                 }
             ]
             }"##;
-        let graph = Graph::from_lnd_json_str(&json_str).unwrap();
+        let graph = Graph::from_lnd_json_str(json_str).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 0);
     }
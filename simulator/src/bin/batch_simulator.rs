@@ -6,6 +6,7 @@ use simlib::{
     AdversarySelection, WeightPartsCombi,
 };
 
+use rand::{rngs::StdRng, SeedableRng};
 use rayon::prelude::*;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -39,12 +40,21 @@ struct Cli {
     /// If neither this nor --betweenness nor --random is passed, no selection will be made.
     #[arg(short = 'd', long = "degree")]
     degree_file: Option<PathBuf>,
+    /// Select the nodes with the highest betweenness, computed on the graph, as adversaries
+    #[arg(long = "betweenness-computed")]
+    computed_betweenness: bool,
+    /// Select the nodes with the most channels, counted on the graph, as adversaries
+    #[arg(long = "degree-computed")]
+    computed_degree: bool,
     /// Select adversaries using random sampling
     #[arg(long = "random")]
     random_selection: bool,
     /// Min shard when using MPP
     #[arg(long = "min")]
     min_shard: Option<usize>,
+    /// Drop channels with less capacity (in sat) before reducing the graph to its greatest SCC
+    #[arg(long = "min-capacity", default_value_t = 0)]
+    min_capacity: usize,
     #[arg(long = "graph-source", short = 'g')]
     graph_type: network_parser::GraphSource,
     #[arg(long)]
@@ -67,7 +77,12 @@ fn main() {
     let seed = args.run;
     let number_of_sim_pairs = args.num_pairs;
     let graph = match g {
-        Ok(graph) => Graph::to_sim_graph(&graph, graph_source),
+        Ok(graph) => Graph::to_sim_graph_filtered(
+            &graph,
+            graph_source,
+            simlib::to_millisatoshi(args.min_capacity),
+            seed,
+        ),
         Err(e) => {
             error!("Error in graph file {}. Exiting.", e);
             std::process::exit(-1)
@@ -91,6 +106,12 @@ fn main() {
     if let Some(file) = args.degree_file {
         adversary_selection.push(AdversarySelection::HighDegree(file));
     };
+    if args.computed_betweenness {
+        adversary_selection.push(AdversarySelection::HighBetweennessComputed);
+    };
+    if args.computed_degree {
+        adversary_selection.push(AdversarySelection::HighDegreeComputed);
+    };
     if args.random_selection {
         adversary_selection.push(AdversarySelection::Random);
     };
@@ -104,7 +125,14 @@ fn main() {
         WeightPartsCombi::MinFeeMulti,
         WeightPartsCombi::MaxProbMulti,
     ];
-    let pairs = Simulation::draw_n_pairs_for_simulation(&graph, number_of_sim_pairs);
+    let pairs = Simulation::draw_n_pairs_for_simulation(
+        &graph,
+        number_of_sim_pairs,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    std::fs::create_dir_all(&output_dir).expect("Creating output directory failed.");
+    // written after every scenario so that a crashed run keeps the completed ones
+    let jsonl_path = output_dir.join(format!("simulation-run{}.jsonl", seed));
     let mut results = Vec::with_capacity(4);
     for combi in weight_parts {
         let sim_results = Arc::new(Mutex::new(Vec::with_capacity(amounts.len())));
@@ -129,7 +157,11 @@ fn main() {
         } else {
             vec![]
         };
-        results.push(Output::to_results_type(&combi_sim_results, combi, seed));
+        let combi_results = Output::to_results_type(&combi_sim_results, combi, seed);
+        if let Err(e) = Output::append_result(&combi_results, &jsonl_path) {
+            error!("Appending results to {:#?} failed: {}.", jsonl_path, e);
+        }
+        results.push(combi_results);
     }
     report_to_file(&results, output_dir, seed).expect("Writing to report failed.");
 }
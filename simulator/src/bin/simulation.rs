@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use clap::Parser;
 use env_logger::Env;
 use log::{error, info};
+use rand::{rngs::StdRng, SeedableRng};
 use simlib::{core_types::graph, sim::Simulation, AdversarySelection};
 
 #[derive(clap::Parser)]
@@ -40,12 +41,30 @@ struct Cli {
     /// Path to file containing betweenness scores
     #[arg(short = 'd', long = "degree")]
     degree_file: Option<PathBuf>,
+    /// Select the nodes with the highest betweenness, computed on the graph, as adversaries
+    #[arg(long = "betweenness-computed")]
+    computed_betweenness: bool,
+    /// Select the nodes with the most channels, counted on the graph, as adversaries
+    #[arg(long = "degree-computed")]
+    computed_degree: bool,
     /// Select adversaries using random sampling
     #[arg(long = "random")]
     random_selection: bool,
+    /// Simulate each of these amounts in sat on the same graph and pairs instead of --amount
+    #[arg(long = "sweep", num_args = 1.., value_delimiter = ' ')]
+    sweep_amounts: Option<Vec<usize>>,
+    /// File with fixed `src dest` pairs to simulate instead of drawing random pairs
+    #[arg(long = "pairs-file")]
+    pairs_file: Option<PathBuf>,
+    /// Lambdas to compute the effective path diversity of MPP payments for
+    #[arg(long = "lambdas", num_args = 1.., value_delimiter = ' ')]
+    diversity_lambdas: Option<Vec<f32>>,
     /// Min shard when using MPP
     #[arg(long = "min")]
     min_shard: Option<usize>,
+    /// Drop channels with less capacity (in sat) before reducing the graph to its greatest SCC
+    #[arg(long = "min-capacity", default_value_t = 0)]
+    min_capacity: usize,
     #[arg(long = "graph-source", short = 'g')]
     graph_type: network_parser::GraphSource,
     #[arg(long)]
@@ -76,7 +95,12 @@ fn main() {
         simlib::PaymentParts::Single
     };
     let graph = match g {
-        Ok(graph) => graph::Graph::to_sim_graph(&graph, graph_source),
+        Ok(graph) => graph::Graph::to_sim_graph_filtered(
+            &graph,
+            graph_source,
+            simlib::to_millisatoshi(args.min_capacity),
+            seed,
+        ),
         Err(e) => {
             error!("Error in graph file {}. Exiting.", e);
             std::process::exit(-1)
@@ -98,6 +122,12 @@ fn main() {
     if let Some(file) = args.degree_file {
         adversary_selection.push(AdversarySelection::HighDegree(file));
     };
+    if args.computed_betweenness {
+        adversary_selection.push(AdversarySelection::HighBetweennessComputed);
+    };
+    if args.computed_degree {
+        adversary_selection.push(AdversarySelection::HighDegreeComputed);
+    };
     if args.random_selection {
         adversary_selection.push(AdversarySelection::Random);
     };
@@ -111,6 +141,36 @@ fn main() {
         number_of_adversaries,
         &adversary_selection,
     );
-    let pairs = Simulation::draw_n_pairs_for_simulation(&graph, number_of_sim_pairs);
-    _ = simulator.run(pairs, args.min_shard, true);
+    if let Some(diversity_lambdas) = args.diversity_lambdas {
+        simulator.set_diversity_lambdas(&diversity_lambdas);
+    }
+    let pairs: Vec<(String, String)> = if let Some(pairs_file) = args.pairs_file {
+        match Simulation::read_pairs_from_file(&graph, &pairs_file) {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                error!(
+                    "Error reading pairs file {}: {}. Exiting.",
+                    pairs_file.display(),
+                    e
+                );
+                std::process::exit(-1)
+            }
+        }
+    } else {
+        Simulation::draw_n_pairs_for_simulation(
+            &graph,
+            number_of_sim_pairs,
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .collect()
+    };
+    if let Some(sweep_amounts) = args.sweep_amounts {
+        let amounts: Vec<usize> = sweep_amounts
+            .into_iter()
+            .map(simlib::to_millisatoshi)
+            .collect();
+        _ = simulator.run_amount_sweep(pairs.into_iter(), &amounts);
+    } else {
+        _ = simulator.run(pairs.into_iter(), args.min_shard, true);
+    }
 }
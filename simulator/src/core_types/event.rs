@@ -180,7 +180,7 @@ mod tests {
         };
         let mut times = Vec::new();
         for _ in 1..100 {
-            let rand_time: f32 = rng.gen_range(0.0..u64::max_value() as f32) / 1000.0;
+            let rand_time: f32 = rng.gen_range(0.0..u64::MAX as f32) / 1000.0;
             times.push(rand_time);
 
             let t = Time::from_millis(rand_time);
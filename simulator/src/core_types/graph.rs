@@ -1,18 +1,80 @@
-use crate::ID;
+use crate::{CapacityPolicy, ID};
 use network_parser::{Edge, Node};
 
 use itertools::Itertools;
 use log::{debug, info, warn};
 use pathfinding::directed::strongly_connected_components::strongly_connected_components;
-use rand::{seq::SliceRandom, Rng};
-use serde::Deserialize;
-use std::{cmp, collections::HashMap};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet, VecDeque},
+};
 
-#[derive(Clone, Deserialize, Debug)]
+/// Number of characters of a node ID used as DOT label if the node has no alias
+const DOT_ID_LEN: usize = 8;
+/// Number of nodes the diameter of a graph is estimated from
+const DIAMETER_SAMPLES: usize = 32;
+/// Seed of the channel balances unless the caller chooses one
+const DEFAULT_BALANCE_SEED: u64 = 0;
+
+#[derive(Clone, Deserialize, Debug, Default)]
 pub struct Graph {
     pub(crate) nodes: Vec<Node>,
     #[serde(rename = "adjacency")]
     pub(crate) edges: HashMap<ID, Vec<Edge>>,
+    /// Endpoints (source, destination) of each direction of a channel to avoid scanning all
+    /// edges. LND graphs name both directions of a channel alike
+    #[serde(skip)]
+    pub(crate) channel_index: HashMap<String, Vec<(ID, ID)>>,
+}
+
+/// Summary of the channel capacities of a graph
+#[derive(Clone, Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CapacityStats {
+    pub num_channels: usize,
+    pub total_capacity: usize,
+    pub mean_capacity: f32,
+    pub median_capacity: f32,
+    pub max_capacity: usize,
+    /// Gini coefficient [0, 1] of the capacity across channels; 0 if all are equally large
+    pub gini: f32,
+}
+
+/// Summary of the fee policies of a node's channels
+#[derive(Clone, Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeStats {
+    pub num_channels: usize,
+    pub min_fee_base_msat: usize,
+    pub median_fee_base_msat: f32,
+    pub max_fee_base_msat: usize,
+    pub min_fee_proportional_millionths: usize,
+    pub median_fee_proportional_millionths: f32,
+    pub max_fee_proportional_millionths: usize,
+}
+
+/// Characteristics of a graph's topology
+#[derive(Clone, Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TopologyReport {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    /// Number of strongly connected components
+    pub num_sccs: usize,
+    /// Mean number of out-edges per node
+    pub mean_degree: f32,
+    /// Largest number of hops between two nodes as seen from a sample of nodes; exact for graphs
+    /// with few nodes
+    pub diameter_estimate: usize,
+    pub capacity: CapacityStats,
 }
 
 impl Graph {
@@ -21,17 +83,104 @@ impl Graph {
         net_graph: &network_parser::Graph,
         graph_source: network_parser::GraphSource,
     ) -> Graph {
+        Self::to_sim_graph_filtered(net_graph, graph_source, 0, DEFAULT_BALANCE_SEED)
+    }
+
+    /// Like [`Graph::to_sim_graph`] but drops channels smaller than `min_capacity_msat` before the
+    /// greatest SCC is computed, so that tiny channels do not hold the SCC together. The channel
+    /// balances are drawn from `seed`.
+    pub fn to_sim_graph_filtered(
+        net_graph: &network_parser::Graph,
+        graph_source: network_parser::GraphSource,
+        min_capacity_msat: usize,
+        seed: u64,
+    ) -> Graph {
+        Self::build_sim_graph(
+            net_graph,
+            graph_source,
+            CapacityPolicy::default(),
+            min_capacity_msat,
+            false,
+            seed,
+        )
+    }
+
+    /// Like [`Graph::to_sim_graph`] but one-way channels are mirrored instead of deleted, i.e. the
+    /// topology is treated as undirected.
+    pub fn to_undirected_sim_graph(
+        net_graph: &network_parser::Graph,
+        graph_source: network_parser::GraphSource,
+    ) -> Graph {
+        Self::build_sim_graph(
+            net_graph,
+            graph_source,
+            CapacityPolicy::default(),
+            0,
+            true,
+            DEFAULT_BALANCE_SEED,
+        )
+    }
+
+    /// Like [`Graph::to_sim_graph`] but lets the caller decide how channel capacities are obtained
+    pub fn to_sim_graph_with_capacity_policy(
+        net_graph: &network_parser::Graph,
+        graph_source: network_parser::GraphSource,
+        capacity_policy: CapacityPolicy,
+    ) -> Graph {
+        Self::build_sim_graph(
+            net_graph,
+            graph_source,
+            capacity_policy,
+            0,
+            false,
+            DEFAULT_BALANCE_SEED,
+        )
+    }
+
+    fn build_sim_graph(
+        net_graph: &network_parser::Graph,
+        graph_source: network_parser::GraphSource,
+        capacity_policy: CapacityPolicy,
+        min_capacity_msat: usize,
+        undirected: bool,
+        seed: u64,
+    ) -> Graph {
+        // the capacity is only known for sure once both directions have been seen, hence we judge
+        // each direction by what it would contribute
+        let edge_capacity = |edge: &Edge| match (capacity_policy, &graph_source) {
+            (CapacityPolicy::AlwaysDeriveFromHtlcMax, _)
+            | (_, network_parser::GraphSource::Lnresearch) => edge.htlc_maximum_msat,
+            (CapacityPolicy::PreferParsed, network_parser::GraphSource::Lnd) => edge.capacity,
+        };
         let nodes: Vec<Node> = net_graph.nodes.clone().into_iter().collect();
         let edges: HashMap<ID, Vec<Edge>> = net_graph
             .clone()
             .edges
             .into_iter()
-            .map(|(id, edge)| (id, Vec::from_iter(edge)))
+            .map(|(id, edges)| {
+                let edges = edges
+                    .into_iter()
+                    .filter(|edge| edge_capacity(edge) >= min_capacity_msat)
+                    .collect();
+                (id, edges)
+            })
             .collect();
-        let graph = Graph { nodes, edges };
+        let mut graph = Graph {
+            nodes,
+            edges,
+            ..Default::default()
+        };
+        if undirected {
+            graph = graph.to_undirected();
+        }
         let greatest_scc = graph.reduce_to_greatest_scc();
         let mut greatest_scc = greatest_scc.remove_unidrectional_edges();
-        greatest_scc.set_channel_balances(graph_source);
+        greatest_scc.set_channel_balances(
+            graph_source,
+            capacity_policy,
+            &mut StdRng::seed_from_u64(seed),
+        );
+        greatest_scc.build_channel_index();
         greatest_scc
     }
 
@@ -43,6 +192,10 @@ impl Graph {
         );
         let mut sccs = self.get_sccs();
         sccs.retain(|scc| !scc.is_empty());
+        if sccs.is_empty() {
+            warn!("Graph has no SCC to reduce to.");
+            return Graph::default();
+        }
         let mut greatest_scc_idx: usize = 0;
         let mut greatest_scc_len: usize = 0;
         for (idx, cc) in sccs.iter().enumerate() {
@@ -60,12 +213,19 @@ impl Graph {
             .collect();
         let greatest_scc_edges: HashMap<ID, Vec<Edge>> = greatest_scc_nodes
             .iter()
-            .map(|n| (n.id.clone(), self.edges.get(&n.id).unwrap().clone()))
+            // nodes without channels have no adjacency entry
+            .map(|n| {
+                (
+                    n.id.clone(),
+                    self.edges.get(&n.id).cloned().unwrap_or_default(),
+                )
+            })
             .collect();
 
         let g = Graph {
             nodes: greatest_scc_nodes,
             edges: greatest_scc_edges,
+            ..Default::default()
         };
         info!(
             "Reduced to graph with {} nodes and {} edges.",
@@ -82,6 +242,30 @@ impl Graph {
         self.edges.clone().into_values().map(|v| v.len()).sum()
     }
 
+    /// Number of channels, i.e. directional edges whose opposite direction is counted only once.
+    /// Channels are identified by their endpoints since the data sets name the two directions of
+    /// a channel differently, e.g. lnresearch suffixes the scid with the direction.
+    pub fn channel_count(&self) -> usize {
+        let mut directions: HashMap<(&ID, &ID), (usize, usize)> = HashMap::new();
+        for edge in self.edges.values().flatten() {
+            if edge.source <= edge.destination {
+                directions
+                    .entry((&edge.source, &edge.destination))
+                    .or_default()
+                    .0 += 1;
+            } else {
+                directions
+                    .entry((&edge.destination, &edge.source))
+                    .or_default()
+                    .1 += 1;
+            }
+        }
+        directions
+            .values()
+            .map(|(forward, backward)| *forward.max(backward))
+            .sum()
+    }
+
     pub fn get_node_ids(&self) -> Vec<ID> {
         self.nodes.iter().map(|n| n.id.clone()).collect()
     }
@@ -96,6 +280,40 @@ impl Graph {
 
     pub fn set_edges(&mut self, edges: HashMap<ID, Vec<Edge>>) {
         self.edges = edges;
+        self.build_channel_index();
+    }
+
+    /// Indexes the endpoints of all channels. Graphs that are not indexed fall back to scanning
+    /// all edges.
+    pub(crate) fn build_channel_index(&mut self) {
+        let mut channel_index: HashMap<String, Vec<(ID, ID)>> = HashMap::new();
+        for e in self.edges.values().flatten() {
+            channel_index
+                .entry(e.channel_id.clone())
+                .or_default()
+                .push((e.source.clone(), e.destination.clone()));
+        }
+        self.channel_index = channel_index;
+    }
+
+    /// Drops the edges' directions from the channel index
+    fn unindex_edges(&mut self, edges: &[Edge]) {
+        for edge in edges {
+            if let Some(endpoints) = self.channel_index.get_mut(&edge.channel_id) {
+                endpoints.retain(|(src, dest)| *src != edge.source || *dest != edge.destination);
+                if endpoints.is_empty() {
+                    self.channel_index.remove(&edge.channel_id);
+                }
+            }
+        }
+    }
+
+    /// The node's edge of the channel
+    pub(crate) fn node_channel(&self, node: &ID, channel_id: &str) -> Option<&Edge> {
+        self.edges
+            .get(node)?
+            .iter()
+            .find(|e| e.channel_id == channel_id)
     }
 
     pub fn get_edges_for_node(&self, node_id: &ID) -> Option<Vec<Edge>> {
@@ -115,18 +333,33 @@ impl Graph {
     /// Will try to remove the edge in both directions
     /// FIXME: This will remove all parallel edges between src and dest. Instead use channel id
     pub(crate) fn remove_edge(&mut self, src: &ID, dest: &ID) {
-        // The edge (src, dest) exists
-        if let Some(src_edges) = self.edges.get_mut(src) {
-            src_edges.retain(|edges| edges.destination != dest.clone());
-        }
-        // The edge (dest, src) exists
-        if let Some(dest_edges) = self.edges.get_mut(dest) {
-            dest_edges.retain(|edges| edges.destination != src.clone());
+        for (from, to) in [(src, dest), (dest, src)] {
+            // The edge (from, to) exists
+            let removed: Vec<Edge> = match self.edges.get_mut(from) {
+                Some(edges) => {
+                    let (removed, kept) = edges.drain(..).partition(|e| e.destination == *to);
+                    *edges = kept;
+                    removed
+                }
+                None => continue,
+            };
+            self.unindex_edges(&removed);
         }
     }
 
     /// Discard the given channel_id from the graph
     pub(crate) fn remove_channel(&mut self, channel_id: &ID) {
+        // a channel's edges are only ever stored with its endpoints
+        if let Some(endpoints) = self.channel_index.remove(channel_id) {
+            for (src, dest) in endpoints {
+                for node in [src, dest] {
+                    if let Some(edges) = self.edges.get_mut(&node) {
+                        edges.retain(|edge| edge.channel_id != *channel_id);
+                    }
+                }
+            }
+            return;
+        }
         for node in self.edges.iter_mut() {
             node.1
                 .retain(|edges| edges.channel_id != channel_id.clone())
@@ -141,6 +374,16 @@ impl Graph {
         }
     }
 
+    /// Discards all of the nodes and their edges in a single pass over the graph
+    pub fn remove_nodes(&mut self, nodes: &HashSet<ID>) {
+        self.nodes.retain(|n| !nodes.contains(&n.id));
+        self.edges.retain(|node, _| !nodes.contains(node));
+        for edges in self.edges.values_mut() {
+            edges.retain(|e| !nodes.contains(&e.destination));
+        }
+        self.build_channel_index();
+    }
+
     pub(crate) fn get_outedges(&self, node_id: &ID) -> Vec<Edge> {
         if let Some(out_edges) = self.edges.get(node_id) {
             if out_edges.is_empty() {
@@ -153,16 +396,68 @@ impl Graph {
         }
     }
 
-    pub(crate) fn update_channel_balance(&mut self, channel_id: &ID, balance: usize) {
-        for edge_lists in self.edges.values_mut() {
-            for edge in edge_lists {
-                if edge.channel_id == channel_id.clone() {
-                    edge.balance = balance;
-                }
+    /// All edges whose destination is the node. Built on demand by scanning the adjacency lists
+    /// so that it does not rely on the graph being symmetric.
+    pub(crate) fn get_inedges(&self, node_id: &ID) -> Vec<Edge> {
+        self.edges
+            .values()
+            .flatten()
+            .filter(|e| e.destination == *node_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Sets the channel's balance. The liquidity is shifted by the same amount so that HTLCs that
+    /// are in flight remain reserved.
+    /// Balances are directional so only the edge of the spending node `src` is updated.
+    pub(crate) fn update_channel_balance(&mut self, src: &ID, channel_id: &ID, balance: usize) {
+        let edge = self
+            .edges
+            .get_mut(src)
+            .and_then(|edges| edges.iter_mut().find(|e| e.channel_id == *channel_id));
+        if let Some(edge) = edge {
+            if balance >= edge.balance {
+                edge.liquidity += balance - edge.balance;
+            } else {
+                edge.liquidity = edge.liquidity.saturating_sub(edge.balance - balance);
             }
+            edge.balance = balance;
+        }
+    }
+
+    /// Commits an HTLC of amount to src's side of the channel, i.e. src can no longer spend the
+    /// amount.
+    pub(crate) fn reserve_channel_liquidity(&mut self, src: &ID, channel_id: &ID, amount: usize) {
+        if let Some(edge) = self
+            .edges
+            .get_mut(src)
+            .and_then(|edges| edges.iter_mut().find(|e| e.channel_id == *channel_id))
+        {
+            edge.liquidity = edge.liquidity.saturating_sub(amount);
+        }
+    }
+
+    /// Releases a previously reserved amount once the HTLC has been resolved.
+    pub(crate) fn release_channel_liquidity(&mut self, src: &ID, channel_id: &ID, amount: usize) {
+        if let Some(edge) = self
+            .edges
+            .get_mut(src)
+            .and_then(|edges| edges.iter_mut().find(|e| e.channel_id == *channel_id))
+        {
+            edge.liquidity = cmp::min(edge.liquidity + amount, edge.balance);
         }
     }
 
+    /// The amount the node can currently spend via the channel, i.e. its balance minus the HTLCs
+    /// in flight.
+    pub(crate) fn get_channel_liquidity(&self, src_node: &ID, channel_id: &ID) -> usize {
+        self.get_outedges(src_node)
+            .iter()
+            .find(|out| out.channel_id == *channel_id)
+            .map(|e| e.liquidity)
+            .unwrap_or_else(|| 0)
+    }
+
     pub(crate) fn get_channel_balance(&self, src_node: &ID, channel_id: &ID) -> usize {
         self.get_outedges(src_node)
             .iter()
@@ -171,29 +466,66 @@ impl Graph {
             .unwrap_or_else(|| 0)
     }
 
-    /// True if the channel's balance after transferring the amount will not exceed the channel capacity
-    pub(crate) fn channel_can_receive_amount(&self, channel_id: &ID, amount: usize) -> bool {
-        for edges in self.get_edges().values() {
-            for edge in edges {
-                if edge.channel_id.eq_ignore_ascii_case(channel_id) {
-                    return edge.capacity > (edge.balance + amount);
-                }
+    /// Returns the balance of every edge as (node, channel_id, balance) sorted by node.
+    pub(crate) fn get_edge_balances(&self) -> Vec<(ID, String, usize)> {
+        let mut balances: Vec<(ID, String, usize)> = self
+            .edges
+            .iter()
+            .flat_map(|(node, edges)| {
+                edges
+                    .iter()
+                    .map(|e| (node.clone(), e.channel_id.clone(), e.balance))
+            })
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    /// Sets the balances as returned by [`Graph::get_edge_balances`]. Liquidity that was reserved
+    /// by HTLCs is released.
+    pub(crate) fn restore_edge_balances(&mut self, balances: &[(ID, String, usize)]) {
+        for (node, channel_id, balance) in balances {
+            if let Some(edge) = self
+                .edges
+                .get_mut(node)
+                .and_then(|edges| edges.iter_mut().find(|e| e.channel_id == *channel_id))
+            {
+                edge.balance = *balance;
+                edge.liquidity = *balance;
             }
         }
-        false
     }
 
+    /// True if the node's balance in the channel after receiving the amount will not exceed the
+    /// channel capacity
+    pub(crate) fn channel_can_receive_amount(
+        &self,
+        node: &ID,
+        channel_id: &ID,
+        amount: usize,
+    ) -> bool {
+        self.node_channel(node, channel_id)
+            .is_some_and(|edge| edge.capacity > (edge.balance + amount))
+    }
+
+    /// The largest amount the node can spend via a single channel (based on liquidity)
     pub(crate) fn get_max_node_balance(&self, node: &ID) -> usize {
         let out_edges = self.get_outedges(node);
-        let max_balance = out_edges.iter().map(|e| e.balance).max();
+        let max_balance = out_edges.iter().map(|e| e.liquidity).max();
         if max_balance.is_none() {
             warn!("Node {} not found. Returning 0 as balance.", node);
         }
         max_balance.unwrap_or(0)
     }
 
+    /// The total amount the node can spend via all of its channels (based on liquidity)
     pub(crate) fn get_total_node_balance(&self, node: &ID) -> usize {
-        self.get_outedges(node).iter().map(|e| e.balance).sum()
+        self.get_outedges(node).iter().map(|e| e.liquidity).sum()
+    }
+
+    /// The summed capacity of all of the node's channels
+    pub(crate) fn get_total_node_capacity(&self, node: &ID) -> usize {
+        self.get_outedges(node).iter().map(|e| e.capacity).sum()
     }
 
     // Get all edges going to 'node' then check how much of the channel capacity is already with
@@ -213,61 +545,137 @@ impl Graph {
 
     /// We calculate balances based on the edges' max_sat values using a random uniform
     /// distribution. We set the liquidity to the calculated balance
-    fn set_channel_balances(&mut self, graph_source: network_parser::GraphSource) {
+    fn set_channel_balances(
+        &mut self,
+        graph_source: network_parser::GraphSource,
+        capacity_policy: CapacityPolicy,
+        rng: &mut impl Rng,
+    ) {
+        // a single draw so that the balances can be computed in parallel
+        let seed: u64 = rng.gen();
+        self.set_channel_balances_with_seed(graph_source, capacity_policy, seed);
+    }
+
+    /// Computes the capacities and balances of all edges in parallel. Each channel draws the
+    /// balance of the direction leaving its lexicographically smaller endpoint from an RNG seeded
+    /// with `seed` and the channel's endpoints and ID; the other direction holds the rest of the
+    /// capacity. The result does not depend on the order in which edges are visited.
+    fn set_channel_balances_with_seed(
+        &mut self,
+        graph_source: network_parser::GraphSource,
+        capacity_policy: CapacityPolicy,
+        seed: u64,
+    ) {
         info!("Calculating channel balances.");
-        // hm
-        let graph_copy = self.clone();
-        let mut rng = crate::RNG.lock().unwrap();
-        for (src, edges) in self.edges.iter_mut() {
-            for out_edge in edges.iter_mut() {
-                // means we haven't visited the edge before; might break if htlc_maximum_msat == 0
-                if out_edge.balance == usize::default() {
-                    // Channel capacity is assumed to be the lower htlc_maximum_msat value
-                    if let Some(mut reverse_edge) = graph_copy.get_edge(&out_edge.destination, src)
-                    {
-                        let src_capacity_dist: f32 = rng.gen();
-                        let capacity = match graph_source {
-                            network_parser::GraphSource::Lnresearch => {
-                                let max_src_htlc = &out_edge.htlc_maximum_msat;
-                                let max_dest_htlc = reverse_edge.htlc_maximum_msat;
-                                *cmp::min(max_src_htlc, &max_dest_htlc) as f32
+        let graph: &Graph = self;
+        // (capacity, balance) for each edge that has not been visited before
+        let balances: HashMap<ID, Vec<Option<(usize, usize)>>> = graph
+            .edges
+            .par_iter()
+            .map(|(src, edges)| {
+                let balances = edges
+                    .iter()
+                    .map(|out_edge| {
+                        // means we haven't visited the edge before; the parser drops edges with
+                        // htlc_maximum_msat == 0
+                        if out_edge.balance != usize::default() {
+                            return None;
+                        }
+                        // Channel capacity is assumed to be the lower htlc_maximum_msat value
+                        // parallel channels are told apart by their short channel ID
+                        let reverse_edge = graph
+                            .get_all_src_dest_edges(&out_edge.destination, src)
+                            .into_iter()
+                            .find(|e| {
+                                short_channel_id(&e.channel_id)
+                                    == short_channel_id(&out_edge.channel_id)
+                            })
+                            .or_else(|| graph.get_edge(&out_edge.destination, src))?;
+                        let capacity = match (capacity_policy, &graph_source) {
+                            (CapacityPolicy::AlwaysDeriveFromHtlcMax, _)
+                            | (_, network_parser::GraphSource::Lnresearch) => {
+                                cmp::min(out_edge.htlc_maximum_msat, reverse_edge.htlc_maximum_msat)
                             }
-                            network_parser::GraphSource::Lnd =>
+                            (CapacityPolicy::PreferParsed, network_parser::GraphSource::Lnd) =>
                             // should not be necessary since the library ensures both edges are
                             // there
                             {
-                                cmp::min(out_edge.capacity, reverse_edge.capacity) as f32
+                                cmp::min(out_edge.capacity, reverse_edge.capacity)
                             }
                         };
-                        out_edge.capacity = capacity as usize;
-                        reverse_edge.capacity = capacity as usize;
-                        let src_balance = (src_capacity_dist * capacity).round();
-                        let dest_balance = capacity - src_balance;
-                        reverse_edge.balance = dest_balance as usize;
-                        reverse_edge.liquidity = reverse_edge.balance;
-                        out_edge.balance = src_balance as usize;
-                        out_edge.liquidity = out_edge.balance;
-                    }
+                        // both directions derive the same draw, whichever way the channel IDs go
+                        let (node1, node2) = if *src <= out_edge.destination {
+                            (src, &out_edge.destination)
+                        } else {
+                            (&out_edge.destination, src)
+                        };
+                        let channel = cmp::min(
+                            short_channel_id(&out_edge.channel_id),
+                            short_channel_id(&reverse_edge.channel_id),
+                        );
+                        let key = format!("{}-{}-{}", node1, node2, channel);
+                        let mut rng = StdRng::seed_from_u64(seed ^ stable_hash(&key));
+                        let node1_capacity_dist: f32 = rng.gen();
+                        let node1_balance = ((node1_capacity_dist * capacity as f32).round()
+                            as usize)
+                            .min(capacity);
+                        let src_balance = if src == node1 {
+                            node1_balance
+                        } else {
+                            capacity - node1_balance
+                        };
+                        Some((capacity, src_balance))
+                    })
+                    .collect();
+                (src.clone(), balances)
+            })
+            .collect();
+        for (src, edges) in self.edges.iter_mut() {
+            for (out_edge, balance) in edges.iter_mut().zip(balances[src].iter()) {
+                if let Some((capacity, balance)) = balance {
+                    out_edge.capacity = *capacity;
+                    out_edge.balance = *balance;
+                    out_edge.liquidity = *balance;
                 }
             }
         }
     }
 
+    /// Adds the missing direction of every one-way channel with the same policy so that the
+    /// adjacency is symmetric. The mirrored direction holds the rest of the channel's capacity.
+    /// Unlike `remove_unidrectional_edges` no edge is deleted.
+    pub fn to_undirected(&self) -> Graph {
+        let mut graph_copy = self.clone();
+        let mut num_added = 0;
+        for edge in self.edges.values().flatten() {
+            if !self.has_reverse_edge(&edge.source, &edge.destination) {
+                graph_copy
+                    .edges
+                    .entry(edge.destination.clone())
+                    .or_default()
+                    .push(Edge {
+                        source: edge.destination.clone(),
+                        destination: edge.source.clone(),
+                        balance: edge.capacity.saturating_sub(edge.balance),
+                        liquidity: edge.capacity.saturating_sub(edge.balance),
+                        ..edge.clone()
+                    });
+                num_added += 1;
+            }
+        }
+        debug!("Mirrored {} unidirectional edges.", num_added);
+        graph_copy.build_channel_index();
+        graph_copy
+    }
+
     fn remove_unidrectional_edges(&self) -> Self {
         info!("Deleting unidirectional edges from graph.");
         let mut graph_copy = self.clone();
         let mut num_removed = 0;
-        for (src, edges) in self.edges.iter() {
-            let from = src;
+        for (from, edges) in self.edges.iter() {
             for out in edges.iter() {
                 let to = &out.destination;
-                // check if to->from exists
-                let edges_from_to: Vec<ID> = if let Some(edges) = self.get_edges_for_node(to) {
-                    edges.iter().map(|edge| edge.destination.clone()).collect()
-                } else {
-                    Vec::default()
-                };
-                if !edges_from_to.contains(from) {
+                if !self.has_reverse_edge(from, to) {
                     graph_copy.remove_edge(from, to);
                     num_removed += 1;
                 }
@@ -282,6 +690,14 @@ impl Graph {
         graph_copy
     }
 
+    /// True if there is an edge from `to` back to `from`, i.e. the edge from `from` to `to` is
+    /// part of a bidirectional pair.
+    pub fn has_reverse_edge(&self, from: &ID, to: &ID) -> bool {
+        self.edges
+            .get(to)
+            .is_some_and(|edges| edges.iter().any(|e| e.destination == *from))
+    }
+
     /// Use get_all_src_dest_edges to get all such edges
     pub(crate) fn get_edge(&self, from: &ID, to: &ID) -> Option<Edge> {
         let out_edges = self.get_outedges(from);
@@ -300,10 +716,15 @@ impl Graph {
             .collect()
     }
 
+    /// Draws `num_nodes` source-destination pairs of distinct nodes.
+    /// If `unique` is set no pair is drawn twice which may yield fewer pairs when the graph has
+    /// less than `num_nodes` pairs.
     pub(crate) fn get_random_pairs_of_nodes(
         &self,
         num_nodes: usize,
-    ) -> (impl Iterator<Item = (ID, ID)> + Clone) {
+        unique: bool,
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = (ID, ID)> + Clone {
         let mut node_ids = self.get_node_ids();
         assert!(
             !node_ids.is_empty(),
@@ -313,25 +734,432 @@ impl Graph {
         // sort for reproducability because of HashMap
         node_ids.sort();
 
-        let mut pairs: Vec<(ID, ID)> = Vec::with_capacity(num_nodes);
-        for _ in 0u64..num_nodes as u64 {
-            // RNG initialised with seed
-            let mut rng = crate::RNG.lock().unwrap();
+        let num_pairs = if unique {
+            num_nodes.min(node_ids.len() * (node_ids.len() - 1))
+        } else {
+            num_nodes
+        };
+        let mut pairs: Vec<(ID, ID)> = Vec::with_capacity(num_pairs);
+        let mut drawn: HashSet<(ID, ID)> = HashSet::with_capacity(num_pairs);
+        while pairs.len() < num_pairs {
             if let Some(src_dest) = node_ids
-                .choose_multiple(&mut *rng, 2)
+                .choose_multiple(rng, 2)
                 .cloned()
-                .collect_tuple()
+                .collect_tuple::<(ID, ID)>()
             {
-                pairs.push(src_dest)
+                if !unique || drawn.insert(src_dest.clone()) {
+                    pairs.push(src_dest)
+                }
             }
         }
         pairs.into_iter()
     }
 
+    /// Draws pairs whose sources are sampled uniformly and whose destinations are weighted by
+    /// their inbound capacity raised to `sink_bias`.
+    /// A bias of 0 samples destinations uniformly whereas larger values increasingly favour sinks
+    /// such as merchants.
+    pub(crate) fn get_pairs_with_sink_bias(
+        &self,
+        num: usize,
+        sink_bias: f32,
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = (ID, ID)> + Clone {
+        let mut node_ids = self.get_node_ids();
+        assert!(node_ids.len() >= 2, "Set of nodes is too small to sample.");
+        // sort for reproducability because of HashMap
+        node_ids.sort();
+        let weights: Vec<f64> = node_ids
+            .iter()
+            .map(|n| (self.get_max_receive_amount(n) as f64).powf(sink_bias as f64))
+            .collect();
+        let dest_dist = match WeightedIndex::new(&weights) {
+            Ok(dist) => Some(dist),
+            Err(e) => {
+                warn!("Falling back to uniform destinations: {}.", e);
+                None
+            }
+        };
+
+        let mut pairs: Vec<(ID, ID)> = Vec::with_capacity(num);
+        for _ in 0..num {
+            let dest_idx = match &dest_dist {
+                Some(dist) => dist.sample(rng),
+                None => rng.gen_range(0..node_ids.len()),
+            };
+            // uniform over all remaining nodes
+            let mut src_idx = rng.gen_range(0..node_ids.len() - 1);
+            if src_idx >= dest_idx {
+                src_idx += 1;
+            }
+            pairs.push((node_ids[src_idx].clone(), node_ids[dest_idx].clone()));
+        }
+        pairs.into_iter()
+    }
+
     pub(crate) fn node_is_in_graph(&self, node: &ID) -> bool {
         self.get_node_ids().contains(node)
     }
 
+    /// Checks whether any path from src to dest exists, irrespective of balances.
+    /// A breadth-first search over the out-edges is much cheaper than looking for an actual route.
+    pub fn is_reachable(&self, src: &ID, dest: &ID) -> bool {
+        if src == dest {
+            return self.node_is_in_graph(src);
+        }
+        let mut visited = HashSet::from([src]);
+        let mut queue = VecDeque::from([src]);
+        while let Some(node) = queue.pop_front() {
+            for edge in self.edges.get(node).into_iter().flatten() {
+                if edge.destination == *dest {
+                    return true;
+                }
+                if visited.insert(&edge.destination) {
+                    queue.push_back(&edge.destination);
+                }
+            }
+        }
+        false
+    }
+
+    /// The ego network of `center`: all nodes within `radius` hops along out-edges and the edges
+    /// among them. The graph is empty if `center` is not part of the graph.
+    pub fn subgraph_around(&self, center: &ID, radius: usize) -> Graph {
+        if !self.node_is_in_graph(center) {
+            return Graph::default();
+        }
+        let mut reached = HashSet::from([center.clone()]);
+        let mut frontier = vec![center.clone()];
+        for _ in 0..radius {
+            let mut next = vec![];
+            for node in frontier.iter() {
+                for edge in self.get_outedges(node) {
+                    if reached.insert(edge.destination.clone()) {
+                        next.push(edge.destination);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        let nodes = self
+            .nodes
+            .iter()
+            .filter(|n| reached.contains(&n.id))
+            .cloned()
+            .collect();
+        let edges = reached
+            .iter()
+            .map(|node| {
+                let edges = self
+                    .get_outedges(node)
+                    .into_iter()
+                    .filter(|e| reached.contains(&e.destination))
+                    .collect();
+                (node.clone(), edges)
+            })
+            .collect();
+        let mut graph = Graph {
+            nodes,
+            edges,
+            ..Default::default()
+        };
+        graph.build_channel_index();
+        graph
+    }
+
+    /// Exports the graph as a directed [GEXF](https://gexf.net) document which can be loaded into
+    /// Gephi. Edges are labelled with their channel ID and weighted by capacity.
+    pub fn to_gexf(&self) -> String {
+        let mut gexf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        gexf.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+        gexf.push_str("  <graph defaultedgetype=\"directed\">\n");
+        gexf.push_str(&format!("    <nodes count=\"{}\">\n", self.node_count()));
+        for node in self.nodes.iter() {
+            gexf.push_str(&format!(
+                "      <node id=\"{}\" label=\"{}\" />\n",
+                xml_escape(&node.id),
+                xml_escape(&node.alias)
+            ));
+        }
+        gexf.push_str("    </nodes>\n");
+        gexf.push_str(&format!("    <edges count=\"{}\">\n", self.edge_count()));
+        // sort for reproducability because of HashMap
+        let edges = self
+            .edges
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .flat_map(|(_, edges)| edges);
+        for (idx, edge) in edges.enumerate() {
+            gexf.push_str(&format!(
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" label=\"{}\" weight=\"{}\" />\n",
+                idx,
+                xml_escape(&edge.source),
+                xml_escape(&edge.destination),
+                xml_escape(&edge.channel_id),
+                edge.capacity
+            ));
+        }
+        gexf.push_str("    </edges>\n  </graph>\n</gexf>\n");
+        gexf
+    }
+
+    /// Exports the graph in Graphviz' DOT format. Nodes are labelled with their alias or, if they
+    /// have none, a truncated ID. Every edge is emitted separately so that parallel channels are
+    /// rendered as distinct edges labelled with channel ID and capacity.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        for node in self.nodes.iter().sorted_by(|a, b| a.id.cmp(&b.id)) {
+            let label = if node.alias.is_empty() {
+                node.id.chars().take(DOT_ID_LEN).collect()
+            } else {
+                node.alias.clone()
+            };
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\"];\n",
+                dot_escape(&node.id),
+                dot_escape(&label)
+            ));
+        }
+        // sort for reproducability because of HashMap
+        let edges = self
+            .edges
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .flat_map(|(_, edges)| edges);
+        for edge in edges {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\\n{}\"];\n",
+                dot_escape(&edge.source),
+                dot_escape(&edge.destination),
+                dot_escape(&edge.channel_id),
+                edge.capacity
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Summarises the capacities of the graph's channels. Each channel is represented by two
+    /// edges so only the edge from the lexicographically smaller node is counted.
+    pub fn capacity_distribution(&self) -> CapacityStats {
+        let mut capacities: Vec<usize> = self
+            .edges
+            .values()
+            .flatten()
+            .filter(|e| e.source < e.destination)
+            .map(|e| e.capacity)
+            .collect();
+        if capacities.is_empty() {
+            return CapacityStats::default();
+        }
+        capacities.sort_unstable();
+        let num_channels = capacities.len();
+        let total_capacity: usize = capacities.iter().sum();
+        let median_capacity = median(&capacities);
+        // G = 2 * sum(i * x_i) / (n * sum(x_i)) - (n + 1) / n for ascending x_i and 1-based i
+        let gini = if total_capacity == 0 {
+            0.0
+        } else {
+            let weighted_sum: f64 = capacities
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (i + 1) as f64 * *c as f64)
+                .sum();
+            let n = num_channels as f64;
+            (2.0 * weighted_sum / (n * total_capacity as f64) - (n + 1.0) / n) as f32
+        };
+        CapacityStats {
+            num_channels,
+            total_capacity,
+            mean_capacity: total_capacity as f32 / num_channels as f32,
+            median_capacity,
+            max_capacity: capacities[num_channels - 1],
+            gini,
+        }
+    }
+
+    /// The range and median of the base fees and fee rates the node charges for forwarding via
+    /// its channels
+    pub fn node_fee_stats(&self, node: &ID) -> FeeStats {
+        let out_edges = self.get_outedges(node);
+        if out_edges.is_empty() {
+            return FeeStats::default();
+        }
+        let mut base_fees: Vec<usize> = out_edges.iter().map(|e| e.fee_base_msat).collect();
+        let mut fee_rates: Vec<usize> = out_edges
+            .iter()
+            .map(|e| e.fee_proportional_millionths)
+            .collect();
+        base_fees.sort_unstable();
+        fee_rates.sort_unstable();
+        FeeStats {
+            num_channels: out_edges.len(),
+            min_fee_base_msat: base_fees[0],
+            median_fee_base_msat: median(&base_fees),
+            max_fee_base_msat: base_fees[base_fees.len() - 1],
+            min_fee_proportional_millionths: fee_rates[0],
+            median_fee_proportional_millionths: median(&fee_rates),
+            max_fee_proportional_millionths: fee_rates[fee_rates.len() - 1],
+        }
+    }
+
+    /// Bundles the graph's characteristics so that they can be stored alongside the results of
+    /// a simulation. The nodes the diameter is estimated from are drawn using `rng`.
+    pub fn topology_report(&self, rng: &mut impl Rng) -> TopologyReport {
+        let node_ids = self.get_node_ids();
+        let num_nodes = node_ids.len();
+        let num_edges = self.edge_count();
+        let mean_degree = if num_nodes == 0 {
+            0.0
+        } else {
+            node_ids
+                .iter()
+                .map(|n| self.get_outedges(n).len())
+                .sum::<usize>() as f32
+                / num_nodes as f32
+        };
+        let num_sccs = self
+            .get_sccs()
+            .into_iter()
+            .filter(|scc| !scc.is_empty())
+            .count();
+        TopologyReport {
+            num_nodes,
+            num_edges,
+            num_sccs,
+            mean_degree,
+            diameter_estimate: self.estimate_diameter(rng),
+            capacity: self.capacity_distribution(),
+        }
+    }
+
+    /// The largest eccentricity of up to DIAMETER_SAMPLES randomly drawn nodes
+    fn estimate_diameter(&self, rng: &mut impl Rng) -> usize {
+        let mut node_ids = self.get_node_ids();
+        // sort for reproducability
+        node_ids.sort();
+        let samples: Vec<&ID> = if node_ids.len() <= DIAMETER_SAMPLES {
+            node_ids.iter().collect()
+        } else {
+            node_ids.choose_multiple(rng, DIAMETER_SAMPLES).collect()
+        };
+        samples
+            .into_par_iter()
+            .map(|src| {
+                // breadth-first search for the number of hops to every reachable node
+                let mut distances = HashMap::from([(src.clone(), 0)]);
+                let mut queue = VecDeque::from([src.clone()]);
+                while let Some(node) = queue.pop_front() {
+                    let distance = distances[&node];
+                    for edge in self.get_outedges(&node) {
+                        if !distances.contains_key(&edge.destination) {
+                            distances.insert(edge.destination.clone(), distance + 1);
+                            queue.push_back(edge.destination);
+                        }
+                    }
+                }
+                distances.into_values().max().unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Betweenness centrality of each node in the unweighted, directed topology using Brandes'
+    /// algorithm. Sorted in descending order of centrality
+    pub fn betweenness_centrality(&self) -> Vec<(ID, f32)> {
+        let ids = self.get_node_ids();
+        let index: HashMap<&ID, usize> = ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+        // parallel channels do not add shortest paths
+        let neighbours: Vec<Vec<usize>> = ids
+            .iter()
+            .map(|id| {
+                self.get_outedges(id)
+                    .iter()
+                    .filter_map(|e| index.get(&e.destination).copied())
+                    .unique()
+                    .collect()
+            })
+            .collect();
+        let n = ids.len();
+        let mut centrality = vec![0.0_f64; n];
+        for src in 0..n {
+            let mut stack = Vec::with_capacity(n);
+            let mut predecessors: Vec<Vec<usize>> = vec![vec![]; n];
+            let mut num_paths = vec![0.0_f64; n];
+            let mut distance: Vec<Option<usize>> = vec![None; n];
+            num_paths[src] = 1.0;
+            distance[src] = Some(0);
+            let mut queue = VecDeque::from([src]);
+            while let Some(v) = queue.pop_front() {
+                stack.push(v);
+                let dist = distance[v].unwrap_or_default();
+                for &w in &neighbours[v] {
+                    if distance[w].is_none() {
+                        distance[w] = Some(dist + 1);
+                        queue.push_back(w);
+                    }
+                    if distance[w] == Some(dist + 1) {
+                        num_paths[w] += num_paths[v];
+                        predecessors[w].push(v);
+                    }
+                }
+            }
+            let mut dependency = vec![0.0_f64; n];
+            while let Some(w) = stack.pop() {
+                for &v in &predecessors[w] {
+                    dependency[v] += num_paths[v] / num_paths[w] * (1.0 + dependency[w]);
+                }
+                if w != src {
+                    centrality[w] += dependency[w];
+                }
+            }
+        }
+        ids.into_iter()
+            .zip(centrality.into_iter().map(|c| c as f32))
+            .sorted_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect()
+    }
+
+    /// Number of out-edges of each node. Sorted in descending order of degree
+    pub fn degree_centrality(&self) -> Vec<(ID, usize)> {
+        self.nodes
+            .iter()
+            .map(|n| (n.id.clone(), self.get_outedges(&n.id).len()))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect()
+    }
+
+    /// Fraction of the possible edges among the nodes with an out-degree above
+    /// `degree_threshold` that exist. Parallel channels between two nodes count once.
+    /// Returns 0 if fewer than two nodes are above the threshold.
+    pub fn rich_club_coefficient(&self, degree_threshold: usize) -> f32 {
+        let rich_nodes: HashSet<ID> = self
+            .degree_centrality()
+            .into_iter()
+            .take_while(|(_, degree)| *degree > degree_threshold)
+            .map(|(id, _)| id)
+            .collect();
+        let num_rich = rich_nodes.len();
+        if num_rich < 2 {
+            return 0.0;
+        }
+        let num_edges: usize = rich_nodes
+            .iter()
+            .map(|node| {
+                self.get_outedges(node)
+                    .iter()
+                    .map(|e| &e.destination)
+                    .filter(|dest| *dest != node && rich_nodes.contains(*dest))
+                    .unique()
+                    .count()
+            })
+            .sum();
+        num_edges as f32 / (num_rich * (num_rich - 1)) as f32
+    }
+
     fn get_sccs(&self) -> Vec<Vec<ID>> {
         let successors = |node: &ID| -> Vec<ID> {
             if let Some(succs) = self.edges.get(&node.to_owned()) {
@@ -348,11 +1176,78 @@ impl Graph {
     }
 }
 
+/// Median of the ascendingly sorted, non-empty values
+fn median(sorted: &[usize]) -> f32 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) as f32 / 2.0
+    } else {
+        sorted[mid] as f32
+    }
+}
+
+/// The channel ID without the direction suffix of lnresearch IDs, e.g. `714505x2146x0/1`
+fn short_channel_id(channel_id: &str) -> &str {
+    channel_id
+        .split_once('/')
+        .map_or(channel_id, |(scid, _)| scid)
+}
+
+/// FNV-1a hash which, unlike the std hashers, is guaranteed to be stable across releases
+fn stable_hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Escapes the characters that would terminate a quoted DOT ID
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use approx::*;
     use std::path::Path;
 
+    /// Edge from `src` to `dest` whose channel ID is "src-dest"
+    fn edge(src: &str, dest: &str) -> Edge {
+        Edge {
+            channel_id: format!("{}-{}", src, dest),
+            source: src.to_string(),
+            destination: dest.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Graph of the given directed edges and the nodes they connect
+    fn graph_of_edges(edges: Vec<Edge>) -> Graph {
+        let nodes = edges
+            .iter()
+            .flat_map(|e| [e.source.clone(), e.destination.clone()])
+            .unique()
+            .map(|id| Node {
+                id,
+                ..Default::default()
+            })
+            .collect();
+        let mut graph = Graph {
+            nodes,
+            ..Default::default()
+        };
+        graph.set_edges(edges.into_iter().into_group_map_by(|e| e.source.clone()));
+        graph
+    }
+
     fn json_str() -> String {
         let json_str = r##"{
             "nodes": [
@@ -462,6 +1357,128 @@ mod tests {
         assert_eq!(num_edges, 2);
     }
 
+    #[test]
+    fn graph_without_edges_reduces_to_empty_scc() {
+        let json_str = r##"{
+            "nodes": [
+                { "pub_key": "alice" },
+                { "pub_key": "bob" }
+            ],
+            "edges": []
+        }"##;
+        let graph = network_parser::Graph::from_lnd_json_str(json_str).unwrap();
+        let actual = Graph::to_sim_graph(&graph, network_parser::GraphSource::Lnd);
+        assert_eq!(actual.edge_count(), 0);
+        // each node is an SCC of its own
+        assert_eq!(actual.node_count(), 1);
+        let json_str = r##"{ "nodes": [], "edges": [] }"##;
+        let graph = network_parser::Graph::from_lnd_json_str(json_str).unwrap();
+        let actual = Graph::to_sim_graph(&graph, network_parser::GraphSource::Lnd);
+        assert_eq!(actual.node_count(), 0);
+        assert_eq!(actual.edge_count(), 0);
+    }
+
+    #[test]
+    fn balances_are_drawn_from_seed() {
+        let graph = network_parser::Graph::from_json_file(
+            std::path::Path::new("../test_data/lnbook_example.json"),
+            network_parser::GraphSource::Lnresearch,
+        )
+        .unwrap();
+        let balances = |seed| {
+            Graph::to_sim_graph_filtered(&graph, network_parser::GraphSource::Lnresearch, 0, seed)
+                .get_edge_balances()
+        };
+        assert_eq!(balances(7), balances(7));
+        assert_ne!(balances(7), balances(8));
+    }
+
+    #[test]
+    fn small_channels_are_filtered_before_scc() {
+        let graph = network_parser::Graph::from_json_file(
+            std::path::Path::new("../test_data/lnbook_example.json"),
+            network_parser::GraphSource::Lnresearch,
+        )
+        .unwrap();
+        let filtered = |min_capacity_msat| {
+            Graph::to_sim_graph_filtered(
+                &graph,
+                network_parser::GraphSource::Lnresearch,
+                min_capacity_msat,
+                0,
+            )
+        };
+        let unfiltered = Graph::to_sim_graph(&graph, network_parser::GraphSource::Lnresearch);
+        assert_eq!(filtered(0).node_count(), unfiltered.node_count());
+        assert_eq!(filtered(0).edge_count(), unfiltered.edge_count());
+        // alice's only channel allows 140000 msat
+        let actual = filtered(200000);
+        assert_eq!(actual.node_count(), 3);
+        assert_eq!(actual.edge_count(), 4);
+        assert!(!actual.node_is_in_graph(&"alice".to_string()));
+        assert!(actual
+            .get_edges()
+            .values()
+            .flatten()
+            .all(|e| e.capacity >= 200000));
+        // only bob <-> chan is left
+        let actual = filtered(1000000);
+        let mut node_ids = actual.get_node_ids();
+        node_ids.sort();
+        assert_eq!(node_ids, vec!["bob", "chan"]);
+        assert_eq!(actual.edge_count(), 2);
+    }
+
+    #[test]
+    fn derived_capacities_are_source_independent() {
+        let lnresearch_graph = network_parser::Graph::from_json_str(
+            &json_str(),
+            network_parser::GraphSource::Lnresearch,
+        )
+        .unwrap();
+        // same channels as if imported from LND, i.e. with a parsed capacity
+        let mut lnd_graph = lnresearch_graph.clone();
+        for edges in lnd_graph.edges.values_mut() {
+            *edges = edges
+                .drain()
+                .map(|mut e| {
+                    e.capacity = 10000000000;
+                    e
+                })
+                .collect();
+        }
+        let capacities = |graph: &Graph| -> HashMap<ID, usize> {
+            graph
+                .edges
+                .values()
+                .flatten()
+                .map(|e| (e.channel_id.clone(), e.capacity))
+                .collect()
+        };
+        let from_lnresearch = Graph::to_sim_graph_with_capacity_policy(
+            &lnresearch_graph,
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::AlwaysDeriveFromHtlcMax,
+        );
+        let from_lnd = Graph::to_sim_graph_with_capacity_policy(
+            &lnd_graph,
+            network_parser::GraphSource::Lnd,
+            CapacityPolicy::AlwaysDeriveFromHtlcMax,
+        );
+        assert!(!capacities(&from_lnd).is_empty());
+        assert_eq!(capacities(&from_lnresearch), capacities(&from_lnd));
+        let parsed_lnd = Graph::to_sim_graph_with_capacity_policy(
+            &lnd_graph,
+            network_parser::GraphSource::Lnd,
+            CapacityPolicy::PreferParsed,
+        );
+        assert!(parsed_lnd
+            .edges
+            .values()
+            .flatten()
+            .all(|e| e.capacity == 10000000000));
+    }
+
     #[test]
     fn scc_compuatation() {
         let json_str = json_str();
@@ -539,11 +1556,147 @@ mod tests {
             .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
-        let random_pair: Vec<(ID, ID)> = graph.get_random_pairs_of_nodes(n).into_iter().collect();
+        let random_pair: Vec<(ID, ID)> = graph
+            .get_random_pairs_of_nodes(n, false, &mut StdRng::seed_from_u64(0))
+            .collect();
         assert!(graph.get_node_ids().contains(&random_pair[0].0));
         assert!(graph.get_node_ids().contains(&random_pair[0].1));
     }
 
+    #[test]
+    fn random_pairs_are_reproducible() {
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_str(
+                &json_str(),
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let draw = || {
+            graph
+                .get_random_pairs_of_nodes(20, false, &mut StdRng::seed_from_u64(7))
+                .collect::<Vec<(ID, ID)>>()
+        };
+        let pairs = draw();
+        assert_eq!(pairs.len(), 20);
+        assert!(pairs.iter().all(|(src, dest)| src != dest));
+        assert_eq!(pairs, draw());
+    }
+
+    #[test]
+    fn unique_random_pairs() {
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_str(
+                &json_str(),
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let n = graph.node_count();
+        // more pairs than there are ordered pairs of distinct nodes
+        let rng = &mut StdRng::seed_from_u64(0);
+        let pairs: Vec<(ID, ID)> = graph.get_random_pairs_of_nodes(100, true, rng).collect();
+        assert_eq!(pairs.len(), n * (n - 1));
+        let distinct: HashSet<&(ID, ID)> = pairs.iter().collect();
+        assert_eq!(distinct.len(), pairs.len());
+        assert!(pairs.iter().all(|(src, dest)| src != dest));
+        let pairs: Vec<(ID, ID)> = graph.get_random_pairs_of_nodes(3, true, rng).collect();
+        assert_eq!(pairs.len(), 3);
+    }
+
+    #[test]
+    fn export_gexf() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let gexf = graph.to_gexf();
+        assert!(gexf.starts_with("<?xml"));
+        assert!(gexf.contains("<graph defaultedgetype=\"directed\">"));
+        let nodes_start = gexf.find("<nodes").unwrap();
+        let nodes_end = gexf.find("</nodes>").unwrap();
+        let edges_start = gexf.find("<edges").unwrap();
+        let edges_end = gexf.find("</edges>").unwrap();
+        assert!(nodes_start < nodes_end && nodes_end < edges_start && edges_start < edges_end);
+        assert!(gexf.contains(&format!("<nodes count=\"{}\">", graph.node_count())));
+        assert!(gexf.contains(&format!("<edges count=\"{}\">", graph.edge_count())));
+        assert_eq!(
+            gexf[nodes_start..nodes_end].matches("<node ").count(),
+            graph.node_count()
+        );
+        assert_eq!(
+            gexf[edges_start..edges_end].matches("<edge ").count(),
+            graph.edge_count()
+        );
+        assert!(gexf.contains("label=\"alice1\""));
+    }
+
+    #[test]
+    fn export_dot() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph {"));
+        for node in graph.get_node_ids() {
+            assert!(dot.contains(&format!("\"{}\" [label=", node)));
+        }
+        let capacity = graph
+            .get_edge(&"alice".to_owned(), &"bob".to_owned())
+            .unwrap()
+            .capacity;
+        assert!(dot.contains(&format!(
+            "\"alice\" -> \"bob\" [label=\"alice1\\n{}\"];",
+            capacity
+        )));
+        assert_eq!(dot.matches(" -> ").count(), graph.edge_count());
+    }
+
+    #[test]
+    fn pairs_with_sink_bias() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let mut graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        // bob can receive the entire capacity of his channels, everyone else only half
+        let sink = String::from("bob");
+        for edges in graph.edges.values_mut() {
+            for e in edges {
+                e.balance = if e.destination == sink {
+                    0
+                } else {
+                    e.capacity / 2
+                };
+            }
+        }
+        let n = 1000;
+        let pairs: Vec<(ID, ID)> = graph
+            .get_pairs_with_sink_bias(n, 2.0, &mut StdRng::seed_from_u64(0))
+            .collect();
+        assert_eq!(pairs.len(), n);
+        assert!(pairs.iter().all(|(src, dest)| src != dest));
+        let sink_hits = pairs.iter().filter(|(_, dest)| *dest == sink).count();
+        assert!(sink_hits > n / graph.node_count());
+    }
+
     #[test]
     fn get_edge_from_src_to_dest() {
         let json_str = json_str();
@@ -606,6 +1759,21 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn get_nodes_inedges() {
+        // a -> b -> c -> a
+        let graph = graph_of_edges(vec![edge("a", "b"), edge("b", "c"), edge("c", "a")]);
+        let node = "b".to_string();
+        let in_edges = graph.get_inedges(&node);
+        assert_eq!(in_edges.len(), 1);
+        assert_eq!(in_edges[0].channel_id, "a-b");
+        assert_eq!(in_edges[0].source, "a");
+        let out_edges = graph.get_outedges(&node);
+        assert_eq!(out_edges.len(), 1);
+        assert_ne!(in_edges[0].channel_id, out_edges[0].channel_id);
+        assert!(graph.get_inedges(&"d".to_string()).is_empty());
+    }
+
     #[test]
     fn delete_edge() {
         let json_str = json_str();
@@ -643,7 +1811,11 @@ mod tests {
             .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
-        graph.set_channel_balances(network_parser::GraphSource::Lnresearch);
+        graph.set_channel_balances(
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::default(),
+            &mut StdRng::seed_from_u64(0),
+        );
         for edges in graph.edges.into_values() {
             for e in edges {
                 assert!(e.balance != usize::default());
@@ -652,11 +1824,393 @@ mod tests {
         }
     }
 
+    /// Ring of bidirectional channels with varying htlc_maximum_msat
+    fn synthetic_ring(num_nodes: usize) -> Graph {
+        let ring_edge = |src: usize, dest: usize, htlc_maximum_msat: usize| Edge {
+            htlc_maximum_msat,
+            ..edge(&src.to_string(), &dest.to_string())
+        };
+        graph_of_edges(
+            (0..num_nodes)
+                .flat_map(|src| {
+                    let next = (src + 1) % num_nodes;
+                    let prev = (src + num_nodes - 1) % num_nodes;
+                    [
+                        ring_edge(src, next, 1000 * (src + 1)),
+                        ring_edge(src, prev, 1000 * (prev + 1)),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn star_betweenness() {
+        let num_leaves = 4;
+        // node 0 is the center
+        let graph = graph_of_edges(
+            (1..=num_leaves)
+                .flat_map(|leaf| {
+                    let leaf = leaf.to_string();
+                    [edge("0", &leaf), edge(&leaf, "0")]
+                })
+                .collect(),
+        );
+        let actual = graph.betweenness_centrality();
+        assert_eq!(actual.len(), num_leaves + 1);
+        // all ordered pairs of leaves pass the center
+        assert_eq!(actual[0], ("0".to_string(), 12.0));
+        assert!(actual[1..].iter().all(|(_, score)| *score == 0.0));
+    }
+
+    #[test]
+    fn indexed_channel_lookups() {
+        let path = Path::new("../test_data/lnbook_example.json");
+        let mut indexed = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        assert_eq!(indexed.channel_index.len(), indexed.edge_count());
+        let mut scanned = indexed.clone();
+        scanned.channel_index.clear();
+        let channels: Vec<ID> = indexed.channel_index.keys().cloned().collect();
+        for (idx, channel_id) in channels.iter().enumerate() {
+            let balance = 1000 * (idx + 1);
+            let (src, _) = indexed.channel_index[channel_id][0].clone();
+            indexed.update_channel_balance(&src, channel_id, balance);
+            scanned.update_channel_balance(&src, channel_id, balance);
+            assert_eq!(indexed.edges, scanned.edges);
+        }
+        let unknown = "unknown".to_string();
+        assert!(!indexed.channel_can_receive_amount(&unknown, &unknown, 0));
+        indexed.update_channel_balance(&unknown, &unknown, 1);
+        assert_eq!(indexed.edges, scanned.edges);
+        for channel_id in channels.iter() {
+            indexed.remove_channel(channel_id);
+            scanned.remove_channel(channel_id);
+            assert_eq!(indexed.edges, scanned.edges);
+        }
+        assert_eq!(indexed.edge_count(), 0);
+        assert!(indexed.channel_index.is_empty());
+    }
+
+    #[test]
+    fn indexed_lnd_channels() {
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "carol", 0),
+            ("carol", "dave", 0),
+            ("dave", "alice", 0),
+        ]);
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+        let channel_id = "alice-bob".to_string();
+        // both directions are indexed
+        assert_eq!(graph.channel_index.len(), 4);
+        assert!(graph.channel_index.values().all(|e| e.len() == 2));
+        // each side is checked against its own balance
+        graph.update_channel_balance(&bob, &channel_id, 95000);
+        assert!(graph.channel_can_receive_amount(&alice, &channel_id, 10000));
+        assert!(!graph.channel_can_receive_amount(&bob, &channel_id, 10000));
+        // the index follows the removal of edges and nodes
+        graph.remove_edge(&alice, &bob);
+        assert!(!graph.channel_index.contains_key(&channel_id));
+        graph.remove_node(&"carol".to_string());
+        let mut indexed: Vec<&String> = graph.channel_index.keys().collect();
+        indexed.sort();
+        assert_eq!(indexed, vec!["dave-alice"]);
+        let mut rebuilt = graph.clone();
+        rebuilt.build_channel_index();
+        assert_eq!(graph.channel_index, rebuilt.channel_index);
+        // both directions of a channel are removed
+        graph.remove_channel(&"dave-alice".to_string());
+        assert_eq!(graph.edge_count(), 0);
+        assert!(graph.channel_index.is_empty());
+    }
+
+    #[test]
+    fn lnbook_capacity_distribution() {
+        let path = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let channel_capacities: HashMap<Vec<ID>, usize> = graph
+            .edges
+            .values()
+            .flatten()
+            .map(|e| {
+                let mut nodes = vec![e.source.clone(), e.destination.clone()];
+                nodes.sort();
+                (nodes, e.capacity)
+            })
+            .collect();
+        let actual = graph.capacity_distribution();
+        assert_eq!(actual.num_channels, 3);
+        assert_eq!(
+            actual.total_capacity,
+            channel_capacities.values().sum::<usize>()
+        );
+        assert_eq!(actual.total_capacity, 140000 + 5000000 + 270000);
+        assert_eq!(actual.max_capacity, 5000000);
+        assert_eq!(actual.median_capacity, 270000.0);
+        assert_abs_diff_eq!(actual.mean_capacity, 5410000.0 / 3.0, epsilon = 0.5);
+        assert_abs_diff_eq!(actual.gini, 0.5989, epsilon = 0.001);
+        assert_eq!(
+            Graph::default().capacity_distribution(),
+            CapacityStats::default()
+        );
+    }
+
+    #[test]
+    fn fee_stats_of_node() {
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 100),
+            ("alice", "carol", 1000),
+        ]);
+        for edge in graph.edges.values_mut().flatten() {
+            edge.fee_proportional_millionths = if edge.channel_id == "alice-bob" {
+                1
+            } else {
+                250
+            };
+        }
+        let actual = graph.node_fee_stats(&"alice".to_string());
+        let expected = FeeStats {
+            num_channels: 2,
+            min_fee_base_msat: 100,
+            median_fee_base_msat: 550.0,
+            max_fee_base_msat: 1000,
+            min_fee_proportional_millionths: 1,
+            median_fee_proportional_millionths: 125.5,
+            max_fee_proportional_millionths: 250,
+        };
+        assert_eq!(actual, expected);
+        // bob only has a single channel
+        let actual = graph.node_fee_stats(&"bob".to_string());
+        assert_eq!(actual.median_fee_base_msat, 100.0);
+        assert_eq!(actual.median_fee_proportional_millionths, 1.0);
+        assert_eq!(
+            graph.node_fee_stats(&"dave".to_string()),
+            FeeStats::default()
+        );
+    }
+
+    #[test]
+    fn lnbook_topology_report() {
+        let path = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let actual = graph.topology_report(&mut StdRng::seed_from_u64(0));
+        assert_eq!(actual.num_nodes, graph.node_count());
+        assert_eq!(actual.num_edges, graph.edge_count());
+        assert_eq!(actual.num_sccs, 1);
+        assert_abs_diff_eq!(actual.mean_degree, 6.0 / 4.0);
+        // alice -> bob -> chan -> dina
+        assert_eq!(actual.diameter_estimate, 3);
+        assert_eq!(actual.capacity, graph.capacity_distribution());
+        let json = serde_json::to_value(&actual).unwrap();
+        assert_eq!(json["numNodes"], 4);
+        assert_eq!(json["diameterEstimate"], 3);
+        assert_eq!(
+            Graph::default().topology_report(&mut StdRng::seed_from_u64(0)),
+            TopologyReport::default()
+        );
+    }
+
+    #[test]
+    fn lnbook_channel_count() {
+        let path = Path::new("../test_data/lnbook_example.json");
+        let mut graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        // all channels are bidirectional
+        assert_eq!(graph.edge_count(), 6);
+        assert_eq!(graph.channel_count(), graph.edge_count() / 2);
+        // a channel with a single direction is still a channel
+        graph.edges.get_mut("alice").unwrap().clear();
+        assert_eq!(graph.edge_count(), 5);
+        assert_eq!(graph.channel_count(), 3);
+    }
+
+    #[test]
+    fn lnbook_degree_centrality() {
+        let path = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let actual = graph.degree_centrality();
+        assert_eq!(actual.len(), graph.node_count());
+        // bob and chan both have two channels, ties are broken by ID
+        assert_eq!(actual[0], ("bob".to_string(), 2));
+        for (id, degree) in actual.iter() {
+            assert_eq!(*degree, graph.get_outedges(id).len());
+        }
+        assert!(actual.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn rich_club_of_hubs() {
+        let hubs_with_leaves = |hub_channels: &[(&'static str, &'static str, usize)]| {
+            let mut channels = vec![
+                ("alice", "xavier", 0),
+                ("bob", "yves", 0),
+                ("chan", "zoe", 0),
+            ];
+            channels.extend_from_slice(hub_channels);
+            crate::attempt::tests::graph_of_channels(&channels)
+        };
+        // the hubs alice, bob and chan are fully connected
+        let graph = hubs_with_leaves(&[
+            ("alice", "bob", 0),
+            ("bob", "chan", 0),
+            ("alice", "chan", 0),
+        ]);
+        assert_abs_diff_eq!(graph.rich_club_coefficient(1), 1.0);
+        // with the leaves, 12 of the 30 possible directed edges exist
+        assert_abs_diff_eq!(graph.rich_club_coefficient(0), 12.0 / 30.0);
+        // bob and chan are not connected directly
+        let graph = hubs_with_leaves(&[("alice", "bob", 0), ("alice", "chan", 0)]);
+        assert_abs_diff_eq!(graph.rich_club_coefficient(1), 4.0 / 6.0);
+        // only alice is left
+        assert_abs_diff_eq!(graph.rich_club_coefficient(2), 0.0);
+    }
+
+    #[test]
+    fn balances_of_large_graph() {
+        let num_nodes = 20000;
+        let mut graph = synthetic_ring(num_nodes);
+        graph.set_channel_balances(
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::default(),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(graph.edge_count(), 2 * num_nodes);
+        for edges in graph.edges.values() {
+            for e in edges {
+                let reverse = graph.get_edge(&e.destination, &e.source).unwrap();
+                assert_eq!(
+                    e.capacity,
+                    cmp::min(e.htlc_maximum_msat, reverse.htlc_maximum_msat)
+                );
+                assert!(e.balance <= e.capacity);
+                assert_eq!(e.liquidity, e.balance);
+            }
+        }
+        // not all edges get the same split
+        let balances: std::collections::HashSet<usize> =
+            graph.edges.values().flatten().map(|e| e.balance).collect();
+        assert!(balances.len() > 1);
+    }
+
+    #[test]
+    fn directions_of_a_channel_share_its_capacity() {
+        let mut graph = synthetic_ring(1000);
+        graph.set_channel_balances(
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::default(),
+            &mut StdRng::seed_from_u64(0),
+        );
+        for e in graph.edges.values().flatten() {
+            let reverse = graph.get_edge(&e.destination, &e.source).unwrap();
+            assert_eq!(e.balance + reverse.balance, e.capacity);
+        }
+        // LND channels name both directions alike
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "carol", 0),
+            ("carol", "alice", 0),
+        ]);
+        for e in graph.edges.values_mut().flatten() {
+            e.balance = 0;
+        }
+        graph.set_channel_balances(
+            network_parser::GraphSource::Lnd,
+            CapacityPolicy::default(),
+            &mut StdRng::seed_from_u64(0),
+        );
+        for e in graph.edges.values().flatten() {
+            let reverse = graph.get_edge(&e.destination, &e.source).unwrap();
+            assert_eq!(e.balance + reverse.balance, e.capacity);
+        }
+    }
+
+    #[test]
+    fn balances_are_reproducible() {
+        let balances = |graph: &Graph| -> HashMap<ID, usize> {
+            graph
+                .edges
+                .values()
+                .flatten()
+                .map(|e| (e.channel_id.clone(), e.balance))
+                .collect()
+        };
+        let mut graph = synthetic_ring(100);
+        let mut other = synthetic_ring(100);
+        // visiting edges in a different order does not matter
+        for edges in other.edges.values_mut() {
+            edges.reverse();
+        }
+        for g in [&mut graph, &mut other] {
+            g.set_channel_balances_with_seed(
+                network_parser::GraphSource::Lnresearch,
+                CapacityPolicy::default(),
+                19,
+            );
+        }
+        assert_eq!(balances(&graph), balances(&other));
+        let mut reseeded = synthetic_ring(100);
+        reseeded.set_channel_balances_with_seed(
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::default(),
+            20,
+        );
+        assert_ne!(balances(&graph), balances(&reseeded));
+    }
+
+    #[test]
+    fn parallel_channels_get_independent_splits() {
+        let parallel_edge = |src: &str, dest: &str, scid: &str| Edge {
+            channel_id: format!("{}/{}", scid, (src > dest) as u8),
+            htlc_maximum_msat: 1000000,
+            ..edge(src, dest)
+        };
+        let mut graph = graph_of_edges(vec![
+            parallel_edge("alice", "bob", "1x1x0"),
+            parallel_edge("bob", "alice", "1x1x0"),
+            parallel_edge("alice", "bob", "2x1x0"),
+            parallel_edge("bob", "alice", "2x1x0"),
+        ]);
+        graph.set_channel_balances_with_seed(
+            network_parser::GraphSource::Lnresearch,
+            CapacityPolicy::default(),
+            19,
+        );
+        let alice_edges = graph.get_all_src_dest_edges(&"alice".to_string(), &"bob".to_string());
+        assert_ne!(alice_edges[0].balance, alice_edges[1].balance);
+        for e in graph.edges.values().flatten() {
+            let reverse = graph
+                .get_all_src_dest_edges(&e.destination, &e.source)
+                .into_iter()
+                .find(|r| short_channel_id(&r.channel_id) == short_channel_id(&e.channel_id))
+                .unwrap();
+            assert_eq!(e.balance + reverse.balance, e.capacity);
+        }
+    }
+
     #[test]
     fn all_edges_between_two_nodes() {
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &Path::new("../test_data/trivial_connected.json"),
+                Path::new("../test_data/trivial_connected.json"),
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -667,7 +2221,7 @@ mod tests {
             let from = node;
             let to = nodes[idx + 1 % nodes.len() - 1].clone();
             if *from != to {
-                let actual = graph.get_all_src_dest_edges(&from, &to);
+                let actual = graph.get_all_src_dest_edges(from, &to);
                 assert_eq!(actual.len(), 1);
             }
         }
@@ -678,7 +2232,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -689,6 +2243,7 @@ mod tests {
         for edges in graph.edges.values_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let node = String::from("alice");
@@ -702,7 +2257,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -711,16 +2266,40 @@ mod tests {
         let node = String::from("alice");
         let channel_id = String::from("alice1");
         let new_balance = 1234;
-        graph.update_channel_balance(&channel_id, new_balance);
+        graph.update_channel_balance(&node, &channel_id, new_balance);
         assert_eq!(new_balance, graph.get_channel_balance(&node, &channel_id));
     }
 
+    #[test]
+    fn update_edge_balance_of_one_direction() {
+        // both directions share the channel ID as in LND graphs
+        let mut graph = crate::attempt::tests::graph_of_channels(&[("a", "b", 0)]);
+        let (a, b, channel_id) = ("a".to_string(), "b".to_string(), "a-b".to_string());
+        graph.update_channel_balance(&a, &channel_id, 200);
+        assert_eq!(graph.get_channel_balance(&a, &channel_id), 200);
+        assert_eq!(graph.get_channel_liquidity(&a, &channel_id), 200);
+        assert_eq!(graph.get_channel_balance(&b, &channel_id), 10000);
+        assert_eq!(graph.get_channel_liquidity(&b, &channel_id), 10000);
+    }
+
+    #[test]
+    fn reserve_liquidity_of_one_direction() {
+        let mut graph = crate::attempt::tests::graph_of_channels(&[("a", "b", 0)]);
+        let (a, b, channel_id) = ("a".to_string(), "b".to_string(), "a-b".to_string());
+        graph.reserve_channel_liquidity(&a, &channel_id, 3000);
+        assert_eq!(graph.get_channel_liquidity(&a, &channel_id), 7000);
+        assert_eq!(graph.get_channel_liquidity(&b, &channel_id), 10000);
+        graph.release_channel_liquidity(&a, &channel_id, 3000);
+        assert_eq!(graph.get_channel_liquidity(&a, &channel_id), 10000);
+        assert_eq!(graph.get_channel_liquidity(&b, &channel_id), 10000);
+    }
+
     #[test]
     fn max_send_capacity() {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -729,10 +2308,10 @@ mod tests {
         let node = String::from("bob");
         let channel_id = String::from("bob1");
         let new_balance = 10000;
-        graph.update_channel_balance(&channel_id, new_balance);
+        graph.update_channel_balance(&node, &channel_id, new_balance);
         let channel_id = String::from("bob2");
         let new_balance = 50;
-        graph.update_channel_balance(&channel_id, new_balance);
+        graph.update_channel_balance(&node, &channel_id, new_balance);
         let actual = graph.get_max_node_balance(&node);
         let expected = 10000;
         assert_eq!(actual, expected);
@@ -743,7 +2322,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -752,10 +2331,10 @@ mod tests {
         let node = String::from("bob");
         let channel_id = String::from("bob1");
         let new_balance = 10000;
-        graph.update_channel_balance(&channel_id, new_balance);
+        graph.update_channel_balance(&node, &channel_id, new_balance);
         let channel_id = String::from("bob2");
         let new_balance = 50;
-        graph.update_channel_balance(&channel_id, new_balance);
+        graph.update_channel_balance(&node, &channel_id, new_balance);
         let actual = graph.get_total_node_balance(&node);
         let expected = 10050;
         assert_eq!(actual, expected);
@@ -786,7 +2365,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -799,13 +2378,14 @@ mod tests {
             for e in edges {
                 e.capacity = capacity;
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let amount = 2000;
-        let channel_id = "alice1".to_string();
-        assert!(graph.channel_can_receive_amount(&channel_id, amount));
+        let (node, channel_id) = ("alice".to_string(), "alice1".to_string());
+        assert!(graph.channel_can_receive_amount(&node, &channel_id, amount));
         let amount = capacity * 2;
-        assert!(!graph.channel_can_receive_amount(&channel_id, amount));
+        assert!(!graph.channel_can_receive_amount(&node, &channel_id, amount));
     }
 
     #[test]
@@ -813,7 +2393,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -826,6 +2406,7 @@ mod tests {
             for e in edges {
                 e.capacity = capacity;
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let node = "bob".to_string();
@@ -843,7 +2424,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -856,12 +2437,59 @@ mod tests {
         assert_eq!(graph.edge_count(), 0);
     }
 
+    #[test]
+    fn delete_nodes_at_once() {
+        let graph = crate::attempt::tests::graph_of_channels(&[
+            ("a", "b", 0),
+            ("b", "c", 0),
+            ("c", "d", 0),
+            ("d", "a", 0),
+            ("a", "c", 0),
+            ("d", "e", 0),
+        ]);
+        let targets = HashSet::from(["a".to_string(), "d".to_string()]);
+        let incident_edges = graph
+            .edges
+            .values()
+            .flatten()
+            .filter(|e| targets.contains(&e.source) || targets.contains(&e.destination))
+            .count();
+        let mut individually = graph.clone();
+        for node in targets.iter() {
+            individually.remove_node(node);
+        }
+        let mut at_once = graph.clone();
+        at_once.remove_nodes(&targets);
+        assert_eq!(at_once.edge_count(), graph.edge_count() - incident_edges);
+        assert_eq!(at_once.edge_count(), individually.edge_count());
+        let sorted_ids = |graph: &Graph| {
+            let mut ids = graph.get_node_ids();
+            ids.sort();
+            ids
+        };
+        assert_eq!(sorted_ids(&at_once), vec!["b", "c", "e"]);
+        assert_eq!(sorted_ids(&at_once), sorted_ids(&individually));
+        for node in sorted_ids(&at_once) {
+            assert_eq!(
+                at_once.get_outedges(&node),
+                individually.get_outedges(&node)
+            );
+        }
+        // e is isolated now
+        assert!(at_once.get_outedges(&"e".to_string()).is_empty());
+        assert!(at_once
+            .get_edges()
+            .values()
+            .flatten()
+            .all(|e| !targets.contains(&e.source) && !targets.contains(&e.destination)));
+    }
+
     #[test]
     fn contains_node() {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -872,4 +2500,102 @@ mod tests {
         graph.remove_node(&node);
         assert!(!graph.node_is_in_graph(&node));
     }
+
+    #[test]
+    fn one_way_edges_are_pruned() {
+        let graph = graph_of_edges(vec![edge("a", "b"), edge("a", "c"), edge("c", "a")]);
+        let (a, b, c) = ("a".to_string(), "b".to_string(), "c".to_string());
+        assert!(!graph.has_reverse_edge(&a, &b));
+        assert!(graph.has_reverse_edge(&a, &c));
+        assert!(graph.has_reverse_edge(&c, &a));
+        let actual = graph.remove_unidrectional_edges();
+        assert_eq!(actual.edge_count(), 2);
+        assert!(actual.get_edge(&a, &b).is_none());
+        assert!(actual.get_edge(&a, &c).is_some());
+        assert!(actual.get_edge(&c, &a).is_some());
+    }
+
+    #[test]
+    fn undirected_graph_is_symmetric() {
+        let funded_edge = |src: &str, dest: &str| Edge {
+            fee_base_msat: 7,
+            capacity: 1000,
+            balance: 400,
+            ..edge(src, dest)
+        };
+        let graph = graph_of_edges(vec![
+            funded_edge("a", "b"),
+            funded_edge("a", "c"),
+            funded_edge("c", "a"),
+        ]);
+        let actual = graph.to_undirected();
+        assert_eq!(actual.edge_count(), 4);
+        for edge in actual.edges.values().flatten() {
+            assert!(actual.get_edge(&edge.destination, &edge.source).is_some());
+        }
+        let mirrored = actual.get_edge(&"b".to_string(), &"a".to_string()).unwrap();
+        assert_eq!(mirrored.channel_id, "a-b");
+        assert_eq!(mirrored.fee_base_msat, 7);
+        assert_eq!(mirrored.balance, 600);
+        // the original direction is untouched
+        assert_eq!(
+            actual.get_edge(&"a".to_string(), &"b".to_string()),
+            graph.get_edge(&"a".to_string(), &"b".to_string())
+        );
+        // the directed conversion drops b instead
+        assert_eq!(graph.remove_unidrectional_edges().edge_count(), 2);
+    }
+
+    #[test]
+    fn reachability_across_components() {
+        let mut graph = graph_of_edges(vec![
+            edge("a", "b"),
+            edge("b", "a"),
+            edge("b", "c"),
+            edge("c", "b"),
+            edge("d", "e"),
+            edge("e", "d"),
+        ]);
+        // f has no channels
+        graph.nodes.push(Node {
+            id: "f".to_string(),
+            ..Default::default()
+        });
+        let reachable =
+            |src: &str, dest: &str| graph.is_reachable(&src.to_string(), &dest.to_string());
+        assert!(reachable("a", "c"));
+        assert!(reachable("c", "a"));
+        assert!(reachable("d", "e"));
+        assert!(reachable("a", "a"));
+        assert!(!reachable("a", "d"));
+        assert!(!reachable("e", "c"));
+        assert!(!reachable("a", "f"));
+        assert!(!reachable("f", "a"));
+        assert!(!reachable("x", "x"));
+    }
+
+    #[test]
+    fn ego_network_around_node() {
+        let graph = crate::attempt::tests::init_sim(None, None).graph;
+        let sorted_ids = |graph: &Graph| {
+            let mut ids = graph.get_node_ids();
+            ids.sort();
+            ids
+        };
+        let actual = graph.subgraph_around(&"bob".to_string(), 1);
+        assert_eq!(sorted_ids(&actual), vec!["alice", "bob", "chan"]);
+        // alice1, bob1, bob2, chan1
+        assert_eq!(actual.edge_count(), 4);
+        assert!(actual
+            .get_outedges(&"chan".to_string())
+            .iter()
+            .all(|e| e.destination == "bob"));
+        let actual = graph.subgraph_around(&"bob".to_string(), 0);
+        assert_eq!(sorted_ids(&actual), vec!["bob"]);
+        assert_eq!(actual.edge_count(), 0);
+        let actual = graph.subgraph_around(&"alice".to_string(), 10);
+        assert_eq!(sorted_ids(&actual), sorted_ids(&graph));
+        assert_eq!(actual.edge_count(), graph.edge_count());
+        assert_eq!(graph.subgraph_around(&"x".to_string(), 1).node_count(), 0);
+    }
 }
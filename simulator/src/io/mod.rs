@@ -1,10 +1,11 @@
 use crate::{
-    payment::Payment,
+    payment::{FailureReason, Payment},
     stats::{Adversaries, Diversity},
     traversal::pathfinding::CandidatePath,
-    WeightPartsCombi,
+    WeightPartsCombi, ID,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub mod output;
 
@@ -12,7 +13,7 @@ pub mod output;
 #[serde(rename_all = "camelCase")]
 pub struct Output(Vec<Results>);
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Report {
     pub amount: usize,
@@ -23,10 +24,14 @@ pub struct Report {
     pub adversaries: Vec<Adversaries>,
     pub path_distances: Vec<usize>,
     pub path_diversity: Vec<Diversity>,
+    /// Number of failed attempts per index of the hop they failed at
+    pub failure_hop_histogram: BTreeMap<usize, usize>,
+    /// How often each node was an intermediary of a payment path, most frequent first
+    pub node_hits: Vec<(ID, usize)>,
 }
 
 /// run and reports
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Results {
     pub scenario: WeightPartsCombi,
@@ -34,7 +39,7 @@ pub struct Results {
     pub reports: Vec<Report>,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PaymentInfo {
     pub id: usize,
@@ -42,11 +47,26 @@ pub struct PaymentInfo {
     /// Number of parts this payment has been split into
     pub num_parts: usize,
     pub htlc_attempts: usize,
+    /// Alternate routes tried after an attempt failed
+    #[serde(default)]
+    pub route_attempts: usize,
     pub used_paths: Vec<PathInfo>,
     pub failed_paths: Vec<PathInfo>,
+    /// Wall-clock time spent on pathfinding for this payment
+    pub pathfinding_ms: u128,
+    /// Why the payment failed; None for successful payments
+    pub failure_reason: Option<FailureReason>,
+    /// Amount that reached the receiver, including the parts of a failed MPP payment that were
+    /// delivered before it was reverted
+    #[serde(default)]
+    pub delivered_msat: usize,
+    /// Total amount of the failed attempts. A part that failed and was split again counts once
+    /// per attempt
+    #[serde(default)]
+    pub failed_msat: usize,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 /// Describes the path used by amounts - may or may not have failed
 pub struct PathInfo {
@@ -56,6 +76,15 @@ pub struct PathInfo {
     pub total_fees: usize,
     pub total_time: usize,
     pub path_len: usize,
+    /// The channels the path forwards along from the sender to the receiver
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// The estimated probability that the path succeeds
+    #[serde(default)]
+    pub success_probability: f32,
+    /// Simulated time the path's HTLCs were held for
+    #[serde(default)]
+    pub hold_time_secs: f32,
 }
 
 impl PathInfo {
@@ -68,6 +97,16 @@ impl PathInfo {
                 total_fees: crate::to_sat(path.path_fees()),
                 total_time: path.time,
                 path_len: path.path.path_length(),
+                // the receiver's hop only names the channel's other direction
+                channels: path
+                    .path
+                    .hops
+                    .iter()
+                    .take(path.path.hops.len() - 1)
+                    .map(|hop| hop.3.clone())
+                    .collect(),
+                success_probability: path.success_probability(),
+                hold_time_secs: path.hold_time_secs,
             })
             .collect()
     }
@@ -82,8 +121,27 @@ impl PaymentInfo {
             succeeded: payment.succeeded,
             num_parts: payment.num_parts,
             htlc_attempts: payment.htlc_attempts,
+            route_attempts: payment.route_attempts,
             used_paths,
             failed_paths,
+            pathfinding_ms: payment.pathfinding_ms,
+            failure_reason: payment.failure_reason,
+            delivered_msat: Self::delivered_msat(payment),
+            failed_msat: payment.failed_amounts.iter().sum(),
+        }
+    }
+
+    /// The successful shards are only kept until the payment completes
+    fn delivered_msat(payment: &Payment) -> usize {
+        if payment.succeeded {
+            payment.amount_msat
+        } else {
+            payment
+                .successful_shards
+                .iter()
+                .filter(|(node, _, _)| *node == payment.dest)
+                .map(|(_, _, amount)| amount)
+                .sum()
         }
     }
 }
@@ -110,6 +168,8 @@ mod tests {
                 weight: 1010.0,
                 amount: 2010,
                 time: 5,
+                hold_time_secs: 0.0,
+                hop_probabilities: vec![],
             },
             CandidatePath {
                 path: Path {
@@ -125,30 +185,38 @@ mod tests {
                 weight: 3000.0,
                 amount: 5030,
                 time: 10,
+                hold_time_secs: 0.0,
+                hop_probabilities: vec![],
             },
         ];
         let source = "bob".to_string();
         let dest = "alice".to_string();
         let amount_msat = 2000;
         let payment = Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
-            succeeded: false,
             min_shard_amt: 10,
             htlc_attempts: 2,
+            route_attempts: 1,
             num_parts: 1,
             used_paths,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         let actual = PaymentInfo::from_payment(&payment);
+        for (info, path) in actual.used_paths.iter().zip(payment.used_paths.iter()) {
+            assert_eq!(info.channels.len(), path.path.hops.len() - 1);
+            assert!(info
+                .channels
+                .iter()
+                .zip(path.path.hops.iter())
+                .all(|(channel, hop)| *channel == hop.3));
+        }
         let expected = PaymentInfo {
             id: 0,
             num_parts: 1,
             htlc_attempts: 2,
+            route_attempts: 1,
             succeeded: false,
             // values are in sat
             used_paths: vec![
@@ -157,16 +225,54 @@ mod tests {
                     total_fees: 1,
                     total_time: 5,
                     path_len: 2,
+                    channels: vec!["bob-carol".to_string(), "carol-alice".to_string()],
+                    success_probability: 1.0,
+                    hold_time_secs: 0.0,
                 },
                 PathInfo {
                     amount: 6,
                     total_fees: 3,
                     total_time: 10,
                     path_len: 3,
+                    channels: vec![
+                        "bob-eve".to_string(),
+                        "eve-carol".to_string(),
+                        "carol-alice".to_string(),
+                    ],
+                    success_probability: 1.0,
+                    hold_time_secs: 0.0,
                 },
             ],
             failed_paths: vec![],
+            pathfinding_ms: 0,
+            failure_reason: None,
+            delivered_msat: 0,
+            failed_msat: 0,
         };
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn failure_hop_histogram_in_report() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        // bob, the first hop after alice, cannot forward the amount
+        simulator
+            .graph
+            .update_channel_balance(&"bob".to_string(), &"bob2".to_string(), amount);
+        simulator.add_invoice(crate::Invoice::new(0, amount, &source, &dest));
+        let mut payment = Payment::new(0, source, dest, amount, None);
+        assert!(!simulator.send_single_payment(&mut payment));
+        assert_eq!(payment.failure_hops, vec![1]);
+        let sim_result = crate::sim::SimResult {
+            total_num: 1,
+            num_failed: 1,
+            failed_payments: vec![payment],
+            ..Default::default()
+        };
+        let report = Report::sim_result_to_report(&sim_result);
+        assert_eq!(report.failure_hop_histogram, BTreeMap::from([(1, 1)]));
+    }
 }
@@ -3,9 +3,11 @@ use crate::{sim::SimResult, WeightPartsCombi};
 
 use log::{error, info};
 use std::{
+    collections::BTreeMap,
     error::Error,
-    fs::{self, File},
-    path::PathBuf,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
 };
 
 impl Output {
@@ -54,6 +56,68 @@ impl Output {
         Ok(())
     }
 
+    /// Appends `result` as a single JSON line to the file at `path` so that completed scenarios
+    /// survive if a long batch run is interrupted
+    pub fn append_result(result: &Results, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, result)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads results written by [`Output::append_result`] back in the order they were appended
+    pub fn read_results_jsonl(path: &Path) -> Result<Vec<Results>, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut results = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                results.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Flattens the payments of all reports into one row each and writes them to
+    /// `<prefix>-payments.csv` in `dir`
+    pub fn write_csv(&self, prefix: String, dir: PathBuf) -> Result<(), Box<dyn Error>> {
+        Self::create_dir(&dir)?;
+        let mut file_output_path = dir;
+        file_output_path.push(format!("{}-payments.csv", prefix));
+        let mut writer = BufWriter::new(File::create(file_output_path.clone())?);
+        writeln!(
+            writer,
+            "id,succeeded,num_parts,htlc_attempts,total_amount,total_fees,path_len"
+        )?;
+        for payment in self
+            .0
+            .iter()
+            .flat_map(|results| results.reports.iter())
+            .flat_map(|report| report.payments.iter())
+        {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                payment.id,
+                payment.succeeded,
+                payment.num_parts,
+                payment.htlc_attempts,
+                payment.used_paths.iter().map(|p| p.amount).sum::<usize>(),
+                payment
+                    .used_paths
+                    .iter()
+                    .map(|p| p.total_fees)
+                    .sum::<usize>(),
+                payment.used_paths.iter().map(|p| p.path_len).sum::<usize>(),
+            )?;
+        }
+        writer.flush()?;
+        info!("Payments CSV written to {}.", file_output_path.display());
+        Ok(())
+    }
+
     fn create_dir(path: &PathBuf) -> Result<(), std::io::Error> {
         fs::create_dir_all(path)
     }
@@ -81,6 +145,130 @@ impl Report {
             adversaries: sim_result.adversaries.to_owned(),
             path_distances: sim_result.path_distances.0.to_owned(),
             path_diversity: sim_result.path_diversity.0.to_owned(),
+            failure_hop_histogram: Self::failure_hop_histogram(sim_result),
+            node_hits: sim_result.node_hits.to_owned(),
         }
     }
+
+    /// Aggregates the hops at which attempts failed across all payments; successful payments
+    /// may also have failed attempts.
+    fn failure_hop_histogram(sim_result: &SimResult) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for payment in sim_result
+            .successful_payments
+            .iter()
+            .chain(sim_result.failed_payments.iter())
+        {
+            for hop in payment.failure_hops.iter() {
+                histogram
+                    .entry(*hop)
+                    .and_modify(|count| *count += 1)
+                    .or_insert(1);
+            }
+        }
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::PathInfo;
+
+    #[test]
+    fn write_payments_csv() {
+        let payment = PaymentInfo {
+            id: 7,
+            succeeded: true,
+            num_parts: 2,
+            htlc_attempts: 3,
+            route_attempts: 0,
+            used_paths: vec![
+                PathInfo {
+                    amount: 600,
+                    total_fees: 2,
+                    total_time: 40,
+                    path_len: 3,
+                    channels: vec!["a-b".to_string(), "b-c".to_string(), "c-d".to_string()],
+                    success_probability: 0.9,
+                    hold_time_secs: 0.0,
+                },
+                PathInfo {
+                    amount: 400,
+                    total_fees: 1,
+                    total_time: 40,
+                    path_len: 2,
+                    channels: vec!["a-e".to_string(), "e-d".to_string()],
+                    success_probability: 0.9,
+                    hold_time_secs: 0.0,
+                },
+            ],
+            failed_paths: vec![],
+            pathfinding_ms: 0,
+            failure_reason: None,
+            delivered_msat: 1000,
+            failed_msat: 0,
+        };
+        let report = Report {
+            amount: 1000,
+            total_num: 1,
+            num_succesful: 1,
+            num_failed: 0,
+            payments: vec![payment],
+            adversaries: vec![],
+            path_distances: vec![],
+            path_diversity: vec![],
+            failure_hop_histogram: BTreeMap::new(),
+            node_hits: vec![],
+        };
+        let output = Output(vec![Results {
+            scenario: WeightPartsCombi::MinFeeMulti,
+            run: 1,
+            reports: vec![report],
+        }]);
+        let dir = tempfile::tempdir().unwrap();
+        output
+            .write_csv(String::from("test"), dir.path().to_path_buf())
+            .unwrap();
+        let csv = fs::read_to_string(dir.path().join("test-payments.csv")).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,succeeded,num_parts,htlc_attempts,total_amount,total_fees,path_len")
+        );
+        assert_eq!(lines.next(), Some("7,true,2,3,1000,3,5"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn append_and_read_results() {
+        let report = |amount| Report {
+            amount,
+            total_num: 0,
+            num_succesful: 0,
+            num_failed: 0,
+            payments: vec![],
+            adversaries: vec![],
+            path_distances: vec![3, 4],
+            path_diversity: vec![],
+            failure_hop_histogram: BTreeMap::from([(1, 2)]),
+            node_hits: vec![("bob".to_string(), 2)],
+        };
+        let first = Results {
+            scenario: WeightPartsCombi::MinFeeSingle,
+            run: 1,
+            reports: vec![report(100)],
+        };
+        let second = Results {
+            scenario: WeightPartsCombi::MaxProbMulti,
+            run: 1,
+            reports: vec![report(500)],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.jsonl");
+        Output::append_result(&first, &path).unwrap();
+        Output::append_result(&second, &path).unwrap();
+        let actual = Output::read_results_jsonl(&path).unwrap();
+        assert_eq!(actual, vec![first, second]);
+    }
 }
@@ -1,7 +1,5 @@
-use lazy_static::lazy_static;
-use rand::{rngs::StdRng, SeedableRng};
-use serde::Serialize;
-use std::{fmt, path::PathBuf, sync::Mutex};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf};
 
 pub mod core_types;
 pub mod io;
@@ -21,7 +19,24 @@ pub type Node = network_parser::Node;
 pub type Edge = network_parser::Edge;
 pub type EdgeWeight = ordered_float::OrderedFloat<f32>;
 
+/// Default delay between two scheduled payments
 pub(crate) static SIM_DELAY_IN_SECS: f32 = 120.0;
+/// Default time it takes a HTLC to be forwarded by a single hop
+pub(crate) static HOP_DELAY_IN_SECS: f32 = 1.0;
+/// Default lambdas the effective path diversity is computed for
+pub(crate) static DIVERSITY_LAMBDAS: [f32; 4] = [0.2, 0.5, 0.7, 1.0];
+/// Factor the failure penalties of a payment's channels decay by with every further failure
+pub(crate) static FAILURE_PENALTY_DECAY: f32 = 0.5;
+/// Factor a channel's learned success probability is multiplied by when a HTLC fails along it
+pub(crate) static PRIOR_FAILURE_DECAY: f32 = 0.5;
+/// Share of the gap to certain success a learned success probability recovers by per attempt
+pub(crate) static PRIOR_RECOVERY_RATE: f32 = 0.1;
+/// Share of extra shards redundant payments of the [`WeightPartsCombi`] scenarios send
+pub(crate) static REDUNDANCY_OVERHEAD: f32 = 0.5;
+/// Number of most used channels reported in a [`sim::SimResult`]
+pub(crate) static TOP_CHANNELS: usize = 10;
+/// Number of completed payments between two progress reports
+pub(crate) static PROGRESS_INTERVAL: usize = 100;
 /// Max number of hops in a path from an adversary's adjacent node
 pub(crate) static _DEPTH: usize = 3;
 /// Minimum amount of msats that can be sent in a shard
@@ -37,6 +52,10 @@ pub(crate) static MAX_PARTS: usize = 16;
 pub static SAT_SCALE: usize = 1000;
 /// Up to 20 intermediaries (vaious [LND code snippets](https://github.com/lightningnetwork/lnd/blob/bbbf7d33fb1527acebb44e2a69d16fbcf24cc2fa/routing/pathfind_test.go#LL1690C34-L1690C34)
 pub static MAX_HOPS: usize = 20;
+/// Default ceiling of a route's total timelock in blocks, i.e. the maximum HTLC expiry of two weeks
+pub static MAX_TOTAL_CLTV: usize = 2016;
+/// Default weight of the timelock penalty in the fee metric, matching LND
+pub static RISK_FACTOR: usize = 15;
 
 /// Metric to use when looking for a route
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
@@ -48,49 +67,122 @@ pub enum RoutingMetric {
 }
 
 /// How should the payment be sent
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PaymentParts {
     /// Send the whole payment at once
     Single,
     /// Split the payment into multiple payments and route independently
     Split,
+    /// Send the halves of the payment plus `overhead` times as much in extra shards across
+    /// disjoint paths. Shards arriving after the payment is complete are cancelled
+    Redundant { overhead: f32 },
+    /// Send the whole payment at once and split it only if that fails, as wallets usually do
+    SingleThenSplit,
 }
+/// Where channel capacities come from when transforming a parsed graph
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CapacityPolicy {
+    /// Use the parsed capacity for LND graphs and derive it from htlc_maximum_msat for lnresearch
+    /// graphs which carry no capacity
+    #[default]
+    PreferParsed,
+    /// Always derive the capacity from htlc_maximum_msat so that graphs from both sources are
+    /// comparable
+    AlwaysDeriveFromHtlcMax,
+}
+
 /// Enum combining RoutingMetric and PaymentParts enums- used to eval different scnerios
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WeightPartsCombi {
     MinFeeSingle,
     MinFeeMulti,
     MaxProbSingle,
     MaxProbMulti,
+    MinFeeRedundant,
+    MaxProbRedundant,
+    MinFeeSingleThenSplit,
+    MaxProbSingleThenSplit,
+}
+
+impl WeightPartsCombi {
+    /// All scenarios in the order they are simulated in
+    pub fn variants() -> &'static [Self] {
+        &[
+            Self::MinFeeSingle,
+            Self::MaxProbSingle,
+            Self::MinFeeMulti,
+            Self::MaxProbMulti,
+            Self::MinFeeRedundant,
+            Self::MaxProbRedundant,
+            Self::MinFeeSingleThenSplit,
+            Self::MaxProbSingleThenSplit,
+        ]
+    }
+}
+
+/// Distribution the per-payment amounts (in msat) are drawn from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AmountDistribution {
+    /// Every payment sends the same amount
+    Fixed(usize),
+    /// Amounts drawn uniformly from the inclusive range
+    Uniform { min: usize, max: usize },
+    /// Amounts whose natural logarithm is normally distributed with mean `mu` and standard
+    /// deviation `sigma`
+    LogNormal { mu: f64, sigma: f64 },
+    /// Amounts drawn with replacement from a file with one amount per line
+    FromFile(PathBuf),
 }
 
 /// How should the adversaries be selected
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum AdversarySelection {
     Random,
     HighBetweenness(#[serde(skip)] PathBuf),
     HighDegree(#[serde(skip)] PathBuf),
     /// WASM callers can pass the deserialised rankings in order to avoid IO
     HighBetweennessWeb(#[serde(skip)] Vec<String>),
+    /// WASM callers can pass the deserialised degree rankings in order to avoid IO
+    HighDegreeWeb(#[serde(skip)] Vec<String>),
+    /// Betweenness rankings computed on the simulation graph instead of read from a file
+    HighBetweennessComputed,
+    /// Degree rankings computed on the simulation graph instead of read from a file
+    HighDegreeComputed,
+    /// An explicit set of adversaries, e.g. known LSPs, used in the given order
+    Custom(#[serde(skip)] Vec<ID>),
+    /// Random nodes drawn with probability proportional to their total channel capacity
+    CapacityWeightedRandom,
+    /// Several ranking files, each normalised to [0, 1] and combined by the given weights.
+    /// Files with a `.csv` extension contain `id,score` rows, all others one node ID per line
+    CompositeRanking(Vec<(PathBuf, ordered_float::OrderedFloat<f32>)>),
 }
 
 impl fmt::Display for AdversarySelection {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Random => write!(f, "Random"),
-            Self::HighBetweenness(_) | Self::HighBetweennessWeb(_) => write!(f, "High Betweenness"),
-            Self::HighDegree(_) => write!(f, "High Degree"),
+            Self::HighBetweenness(_)
+            | Self::HighBetweennessWeb(_)
+            | Self::HighBetweennessComputed => write!(f, "High Betweenness"),
+            Self::HighDegree(_) | Self::HighDegreeWeb(_) | Self::HighDegreeComputed => {
+                write!(f, "High Degree")
+            }
+            Self::Custom(_) => write!(f, "Custom"),
+            Self::CapacityWeightedRandom => write!(f, "Capacity Weighted Random"),
+            Self::CompositeRanking(rankings) => {
+                let weights: Vec<String> = rankings
+                    .iter()
+                    .map(|(path, weight)| {
+                        let name = path.file_stem().unwrap_or(path.as_os_str());
+                        format!("{}: {}", name.to_string_lossy(), weight)
+                    })
+                    .collect();
+                write!(f, "Composite Ranking ({})", weights.join(", "))
+            }
         }
     }
 }
 
-lazy_static! {
-    static ref RNG: Mutex<StdRng> = {
-        let std_rng = StdRng::from_entropy();
-        Mutex::new(std_rng)
-    };
-}
-
 impl clap::ValueEnum for RoutingMetric {
     fn value_variants<'a>() -> &'a [Self] {
         &[Self::MinFee, Self::MaxProb]
@@ -1,8 +1,11 @@
 use crate::{
-    payment::{Payment, PaymentShard},
+    payment::{FailureReason, Payment, PaymentShard},
+    sim::Htlc,
     traversal::pathfinding::{CandidatePath, PathFinder},
-    Simulation, ID,
+    PaymentId, PaymentParts, Simulation, ID,
 };
+use rand::Rng;
+use std::time::Instant;
 
 #[cfg(not(test))]
 use log::{debug, error, info, trace};
@@ -13,9 +16,11 @@ impl Simulation {
     /// attempts to send a payment until it fails.
     /// Unsuccessful payments are reversed immediately while we return the successful ones in case
     /// they should be reversed later
+    /// `payment_parts` decides how paths are searched for, e.g. for the shards of a split payment
     pub(crate) fn send_one_payment(
         &mut self,
         payment: &mut Payment,
+        payment_parts: PaymentParts,
     ) -> (bool, Vec<(ID, String, usize)>) {
         let graph = self.graph.clone();
         let mut succeeded = false;
@@ -27,6 +32,7 @@ impl Simulation {
         let max_out_balance = graph.get_max_node_balance(&payment.source);
         if max_out_balance < payment.amount_msat {
             error!("Payment shard failing. Sender {} does not have sufficient balance. Amount {}, max balance {}",  payment.source, payment.amount_msat, max_out_balance);
+            payment.failure_reason = Some(FailureReason::InsufficientSenderBalance);
             failed = true;
         }
         let graph_copy = self.graph.clone();
@@ -37,57 +43,98 @@ impl Simulation {
                 payment.amount_msat,
                 &graph_copy,
                 self.routing_metric,
-                self.payment_parts,
+                payment_parts,
             );
+            path_finder.set_max_total_cltv(self.max_total_cltv);
+            path_finder.set_candidate_pool(self.candidate_pool);
+            path_finder.set_risk_factor(self.risk_factor);
+            path_finder.set_channel_priors(self.channel_priors.clone());
             path_finder
                 .graph
                 .set_edges(PathFinder::remove_inadequate_edges(
                     &graph_copy,
                     payment.amount_msat,
                 ));
+            let max_fee_msat = payment.max_fee_msat.or(self.max_fee_msat);
+            let mut num_retries = 0;
             while !succeeded && !failed {
-                if let Some(candidate_path) = path_finder.find_path() {
+                let start = Instant::now();
+                let candidate_path = path_finder.find_path();
+                let duration_in_ms = start.elapsed().as_millis();
+                trace!("Pathfinding took {} ms.", duration_in_ms);
+                payment.pathfinding_ms += duration_in_ms;
+                if let Some(mut candidate_path) = candidate_path {
                     let hops = candidate_path.path.hops.clone();
-                    for hop in hops.iter().take(hops.len() - 1).skip(1) {
-                        // not source and dest
-                        let id = hop.0.clone();
-                        self.node_hits
-                            .entry(id)
-                            .and_modify(|occurences| *occurences += 1)
-                            .or_insert(1);
-                    }
+                    self.record_node_hits(&candidate_path);
                     // maybe the sender's balance is not enough after we have discovered the full
                     // path's fees
                     let (sender, out_channel) = (&hops[0].0, &hops[0].3);
-                    let channel_balance = self.graph.get_channel_balance(sender, out_channel);
-                    if channel_balance < candidate_path.amount {
-                        error!("Payment shard failing. Sender does not have sufficient balance to cover fees. Amount {}, channel balance {}", candidate_path.amount, channel_balance);
+                    let channel_liquidity = self.graph.get_channel_liquidity(sender, out_channel);
+                    if channel_liquidity < candidate_path.amount {
+                        error!("Payment shard failing. Sender does not have sufficient liquidity to cover fees. Amount {}, channel liquidity {}", candidate_path.amount, channel_liquidity);
+                        payment.failure_reason = Some(FailureReason::InsufficientSenderBalance);
                         succeeded = false;
                         failed = true;
                     }
                     // edge's receive capacity not sufficient?
-                    let receive_channel = &hops[hops.len() - 1].3;
-                    if !self
-                        .graph
-                        .channel_can_receive_amount(receive_channel, payment.amount_msat)
+                    let (receiver, _, _, receive_channel) = &hops[hops.len() - 1];
+                    if self.check_receive_capacity
+                        && !self.graph.channel_can_receive_amount(
+                            receiver,
+                            receive_channel,
+                            payment.amount_msat,
+                        )
                     {
                         error!(
                             "Payment {} of {} msat failing at destination due to max capacity. Not trying to deliver..",
                             payment.payment_id, payment.amount_msat
                         );
+                        payment.failure_reason = Some(FailureReason::DestinationCapacity);
                         succeeded = false;
                         failed = true;
                     }
-                    if !failed {
+                    // too expensive: discard the path's most expensive hop and look for another
+                    // route
+                    let exceeds_budget =
+                        max_fee_msat.is_some_and(|max_fee| candidate_path.path_fees() > max_fee);
+                    if exceeds_budget {
+                        error!(
+                            "Payment {} rejecting path with {} msat fees exceeding the budget of {:?} msat.",
+                            payment.payment_id, candidate_path.path_fees(), max_fee_msat
+                        );
+                        payment.failure_reason = Some(FailureReason::FeeBudgetExceeded);
+                        if let Some(hop) = hops
+                            .iter()
+                            .take(hops.len() - 1)
+                            .skip(1)
+                            .max_by_key(|hop| hop.1)
+                        {
+                            path_finder.graph.remove_channel(&hop.3);
+                        }
+                    }
+                    if !failed && !exceeds_budget && self.dry_run {
+                        trace!(
+                            "Dry run: payment {} found a route without transferring funds.",
+                            payment.payment_id
+                        );
+                        payment.used_paths = vec![candidate_path.clone()];
+                        payment.succeeded = true;
+                        succeeded = true;
+                    } else if !failed && !exceeds_budget {
                         let mut payment_shard = payment.to_shard(payment.amount_msat);
                         (succeeded, to_revert) = self.attempt_payment(
                             &mut payment_shard,
-                            &candidate_path,
+                            &mut candidate_path,
                             &mut path_finder,
+                            payment_parts,
                         );
                         *payment = payment_shard.to_payment(1);
                         if !succeeded {
                             self.revert_payment(&to_revert);
+                            // another route would not deliver more
+                            if payment.failure_reason == Some(FailureReason::InvoiceUnderpaid) {
+                                failed = true;
+                            }
                         }
                     }
                     // note paths that were attempted but failed for some reason
@@ -95,14 +142,30 @@ impl Simulation {
                         payment.failed_paths.push(candidate_path);
                         payment.used_paths.clear();
                     }
+                    if !succeeded && !failed {
+                        if num_retries >= self.max_retries {
+                            error!(
+                                "Payment {} failing after {} retries.",
+                                payment.payment_id, num_retries
+                            );
+                            payment.failure_reason = Some(FailureReason::MaxRetries);
+                            failed = true;
+                        } else {
+                            num_retries += 1;
+                            payment.route_attempts += 1;
+                        }
+                    }
                 } else {
                     error!("No paths to destination found.");
+                    // keep the reason an earlier attempt failed for, if any
+                    payment.failure_reason.get_or_insert(FailureReason::NoRoute);
                     succeeded = false;
                     failed = true;
                 }
             }
         }
         if succeeded {
+            payment.failure_reason = None;
             (succeeded, to_revert)
         } else {
             (succeeded, Vec::new()) // the payments have already been reversed if the payment was
@@ -110,15 +173,55 @@ impl Simulation {
         }
     }
 
+    /// Lets the learned success probabilities recover and lowers the probability of the channel
+    /// the attempt failed at, if any.
+    fn update_channel_priors(&mut self, failing_channel: Option<&String>) {
+        if !self.learn_channel_priors {
+            return;
+        }
+        for prior in self.channel_priors.values_mut() {
+            *prior += (1.0 - *prior) * crate::PRIOR_RECOVERY_RATE;
+        }
+        if let Some(channel_id) = failing_channel {
+            *self.channel_priors.entry(channel_id.clone()).or_insert(1.0) *=
+                crate::PRIOR_FAILURE_DECAY;
+        }
+    }
+
+    /// Counts an HTLC forwarded through the channel.
+    fn record_channel_usage(&mut self, channel_id: &str) {
+        *self
+            .channel_usage
+            .entry(channel_id.to_string())
+            .or_default() += 1;
+    }
+
+    /// Counts how often the path's intermediaries are included in a payment path.
+    pub(crate) fn record_node_hits(&mut self, candidate_path: &CandidatePath) {
+        let hops = &candidate_path.path.hops;
+        for hop in hops.iter().take(hops.len() - 1).skip(1) {
+            // not source and dest
+            let id = hop.0.clone();
+            self.node_hits
+                .entry(id)
+                .and_modify(|occurences| *occurences += 1)
+                .or_insert(1);
+        }
+    }
+
     /// Tries to move the funds as is specified in the shard.
     /// This is the actual transaction
+    /// `payment_parts` decides whether the shard has to cover the entire invoice
     pub(crate) fn attempt_payment(
         &mut self,
         payment_shard: &mut PaymentShard,
-        candidate_path: &CandidatePath,
+        candidate_path: &mut CandidatePath,
         path_finder: &mut PathFinder,
+        payment_parts: PaymentParts,
     ) -> (bool, Vec<(ID, String, usize)>) {
         let hops = candidate_path.path.hops.clone();
+        // the HTLCs are held from being committed until the attempt is resolved
+        candidate_path.hold_time_secs = 0.0;
         info!(
             "{} attempting to send {} msats to {} via {} hops.",
             payment_shard.source,
@@ -129,26 +232,37 @@ impl Simulation {
         let mut remaining_transferable_amount = 0;
         // used in case we need to revert (node, channel_id, amount)
         let mut transferred_amounts: Vec<(ID, String, usize)> = Vec::new();
+        // liquidity locked along the path (node, channel_id, amount)
+        let mut reserved_amounts: Vec<Htlc> = Vec::new();
         for (idx, node) in hops.iter().enumerate() {
             let (id, fees, _timelock, channel_id) = node.clone();
             // Subtract payment amount (includes fees) from source
             if id == payment_shard.source {
                 let current_balance = self.graph.get_channel_balance(&id, &channel_id);
-                if current_balance > candidate_path.amount {
+                let current_liquidity = self.graph.get_channel_liquidity(&id, &channel_id);
+                if current_liquidity > candidate_path.amount {
                     self.graph.update_channel_balance(
+                        &id,
                         &channel_id,
                         current_balance - candidate_path.amount,
                     );
                     remaining_transferable_amount = candidate_path.amount;
+                    candidate_path.hold_time_secs += self.hop_delay_secs;
+                    self.record_channel_usage(&channel_id);
                     transferred_amounts.push((id, channel_id, remaining_transferable_amount));
                     payment_shard.htlc_attempts += 1;
                 } else {
                     error!(
-                        "Payment {} failed at source {} due to insufficient balance. available balamce {}, total amount {}",
-                        payment_shard.payment_id, payment_shard.source, current_balance, candidate_path.amount,
+                        "Payment {} failed at source {} due to insufficient liquidity. available liquidity {}, total amount {}",
+                        payment_shard.payment_id, payment_shard.source, current_liquidity, candidate_path.amount,
                     );
                     payment_shard.htlc_attempts += 1;
                     payment_shard.succeeded = false;
+                    payment_shard.failure_reason = Some(FailureReason::InsufficientSenderBalance);
+                    payment_shard.failure_hops.push(idx);
+                    payment_shard
+                        .failing_channels
+                        .push((id.clone(), channel_id.clone()));
                     return (payment_shard.succeeded, transferred_amounts);
                 }
             } else if id == payment_shard.dest {
@@ -156,56 +270,79 @@ impl Simulation {
                 // check if we have such an invoice and received amount matches
                 // if yes: success = true
                 //if remaining_transferable_amount == invoice
-                match self.get_invoices_for_node(&id) {
-                    Some(invoices) => {
-                        if let Some(invoice) = invoices.get(&payment_shard.payment_id) {
-                            if invoice.source == payment_shard.source {
-                                //&&invoice.amount == remaining_transferable_amount
-
-                                // receiver would exceed channel capacity - should never get this
-                                // far as we check before attempting
-                                if !self.graph.channel_can_receive_amount(
+                let invoice = self
+                    .get_invoices_for_node(&id)
+                    .and_then(|invoices| invoices.get(&payment_shard.payment_id))
+                    .cloned();
+                match invoice {
+                    Some(invoice) => {
+                        // a single-path payment has to cover the entire invoice while
+                        // the parts of an MPP payment only sum to it
+                        let expected_amount = match payment_parts {
+                            PaymentParts::Single | PaymentParts::SingleThenSplit => invoice.amount,
+                            PaymentParts::Split | PaymentParts::Redundant { .. } => {
+                                payment_shard.amount.min(invoice.amount)
+                            }
+                        };
+                        if invoice.source != payment_shard.source {
+                            error!("Payment failure at destination (no invoice). Payment {:?}, remaining_amount {}, invoice {:?}", payment_shard, remaining_transferable_amount, invoice);
+                            payment_shard.succeeded = false;
+                            payment_shard.failure_reason = Some(FailureReason::NoInvoice);
+                        } else if self.enforce_invoice_amount
+                            && remaining_transferable_amount < expected_amount
+                        {
+                            error!(
+                                    "Payment {} failing at destination. Received {} msat, expected {} msat.",
+                                    payment_shard.payment_id, remaining_transferable_amount, expected_amount
+                                );
+                            payment_shard.succeeded = false;
+                            payment_shard.failure_reason = Some(FailureReason::InvoiceUnderpaid);
+                        } else {
+                            // receiver would exceed channel capacity - should never get this
+                            // far as we check before attempting
+                            if self.check_receive_capacity
+                                && !self.graph.channel_can_receive_amount(
+                                    &id,
                                     &channel_id,
                                     remaining_transferable_amount,
-                                ) {
-                                    error!(
-                                        "Payment {} failing at destination due to max capacity.",
-                                        payment_shard.payment_id
-                                    );
-                                    payment_shard.succeeded = false;
-                                    let src = &id;
-                                    let dest = hops[idx - 1].0.clone();
-                                    // this is the failing edge
-                                    trace!("Discarding channel {} due to max capacity", channel_id,);
-                                    path_finder.graph.remove_channel(&channel_id);
-                                    path_finder.graph.remove_edge(src, &dest);
-                                } else {
-                                    let current_balance =
-                                        self.graph.get_channel_balance(&id, &channel_id);
-                                    self.graph.update_channel_balance(
-                                        &channel_id,
-                                        current_balance + remaining_transferable_amount,
-                                    );
-                                    candidate_path.clone_into(&mut payment_shard.used_path);
-                                    // TODO: remove invoice
-                                    info!(
-                                        "Successfully delivered payment of {} msats from {} to {}.",
-                                        payment_shard.amount,
-                                        payment_shard.source,
-                                        payment_shard.dest,
-                                    );
-                                    // necessary as we may reverse the payment if its part of an MPP
-                                    // payment
-                                    transferred_amounts.push((
-                                        id,
-                                        channel_id,
-                                        remaining_transferable_amount,
-                                    ));
-                                    payment_shard.succeeded = true;
-                                }
-                            } else {
-                                error!("Payment failure at destination (no invoice). Payment {:?}, remaining_amount {}, invoice {:?}", payment_shard, remaining_transferable_amount, invoice);
+                                )
+                            {
+                                error!(
+                                    "Payment {} failing at destination due to max capacity.",
+                                    payment_shard.payment_id
+                                );
                                 payment_shard.succeeded = false;
+                                payment_shard.failure_reason =
+                                    Some(FailureReason::DestinationCapacity);
+                                let src = &id;
+                                let dest = hops[idx - 1].0.clone();
+                                // this is the failing edge
+                                trace!("Discarding channel {} due to max capacity", channel_id,);
+                                path_finder.graph.remove_channel(&channel_id);
+                                path_finder.graph.remove_edge(src, &dest);
+                            } else {
+                                let current_balance =
+                                    self.graph.get_channel_balance(&id, &channel_id);
+                                self.graph.update_channel_balance(
+                                    &id,
+                                    &channel_id,
+                                    current_balance + remaining_transferable_amount,
+                                );
+                                candidate_path.clone_into(&mut payment_shard.used_path);
+                                // the invoice is only removed once all parts have
+                                // been delivered
+                                info!(
+                                    "Successfully delivered payment of {} msats from {} to {}.",
+                                    payment_shard.amount, payment_shard.source, payment_shard.dest,
+                                );
+                                // necessary as we may reverse the payment if its part of an MPP
+                                // payment
+                                transferred_amounts.push((
+                                    id,
+                                    channel_id,
+                                    remaining_transferable_amount,
+                                ));
+                                payment_shard.succeeded = true;
                             }
                         }
                     }
@@ -220,6 +357,7 @@ impl Simulation {
                         path_finder.graph.remove_channel(&channel_id);
                         path_finder.graph.remove_edge(src, &hops[idx - 1].0);
                         payment_shard.succeeded = false;
+                        payment_shard.failure_reason = Some(FailureReason::NoInvoice);
                     }
                 };
             // a hop along the path
@@ -227,14 +365,25 @@ impl Simulation {
                 payment_shard.htlc_attempts += 1;
                 // subtract fee and add to own balance
                 let current_balance = self.graph.get_channel_balance(&id, &channel_id);
-                if current_balance > (remaining_transferable_amount - fees)
-                    && self
-                        .graph
-                        .channel_can_receive_amount(&channel_id, remaining_transferable_amount)
+                let current_liquidity = self.graph.get_channel_liquidity(&id, &channel_id);
+                let forwarded_amount = remaining_transferable_amount - fees;
+                if current_liquidity > forwarded_amount
+                    && self.graph.channel_can_receive_amount(
+                        &id,
+                        &channel_id,
+                        remaining_transferable_amount,
+                    )
+                    && !self.htlc_fails_randomly()
                 {
                     self.graph
-                        .update_channel_balance(&channel_id, current_balance + fees);
+                        .update_channel_balance(&id, &channel_id, current_balance + fees);
+                    // the forwarded amount is locked until the payment is resolved
+                    self.graph
+                        .reserve_channel_liquidity(&id, &channel_id, forwarded_amount);
+                    reserved_amounts.push((id.clone(), channel_id.clone(), forwarded_amount));
                     remaining_transferable_amount -= fees;
+                    candidate_path.hold_time_secs += self.hop_delay_secs;
+                    self.record_channel_usage(&channel_id);
                     transferred_amounts.push((id, channel_id, fees));
                 } else {
                     let src = &id;
@@ -252,28 +401,109 @@ impl Simulation {
                     // this is the failing edge
                     path_finder.graph.remove_channel(&channel_id);
                     path_finder.graph.remove_edge(src, &hops[idx - 1].0);
+                    // the remaining channels of the node are likely depleted, too
+                    path_finder.penalize_failure(src);
+                    self.update_channel_priors(Some(&channel_id));
                     payment_shard.succeeded = false;
+                    payment_shard.failure_reason = Some(FailureReason::InsufficientLiquidity);
+                    payment_shard.failure_hops.push(idx);
+                    payment_shard
+                        .failing_channels
+                        .push((id.clone(), channel_id.clone()));
+                    self.release_htlcs(&reserved_amounts);
                     return (payment_shard.succeeded, transferred_amounts);
                 }
             }
         }
+        self.update_channel_priors(None);
+        if payment_shard.succeeded {
+            self.in_flight_htlcs.extend(reserved_amounts);
+        } else {
+            // all remaining failures occur at the destination
+            payment_shard.failure_hops.push(hops.len() - 1);
+            let (dest, _, _, channel_id) = &hops[hops.len() - 1];
+            payment_shard
+                .failing_channels
+                .push((dest.clone(), channel_id.clone()));
+            self.release_htlcs(&reserved_amounts);
+        }
         (payment_shard.succeeded, transferred_amounts)
     }
 
+    /// Intermittent failures of a hop regardless of its liquidity.
+    fn htlc_fails_randomly(&mut self) -> bool {
+        if self.random_failure_prob <= 0.0 {
+            return false;
+        }
+        self.rng.gen::<f32>() < self.random_failure_prob
+    }
+
+    /// Makes the liquidity that was locked by HTLCs available again.
+    pub(crate) fn release_htlcs(&mut self, reserved_amounts: &[Htlc]) {
+        for (node, channel_id, amount) in reserved_amounts {
+            self.graph
+                .release_channel_liquidity(node, channel_id, *amount);
+        }
+    }
+
+    /// Called once a payment has been resolved (i.e. all of its shards either settled or failed).
+    pub(crate) fn resolve_in_flight_htlcs(&mut self) {
+        let in_flight = std::mem::take(&mut self.in_flight_htlcs);
+        self.release_htlcs(&in_flight);
+    }
+
+    /// Called once a payment has been sent. The HTLCs of a successful payment stay in flight
+    /// until it settles if payments are sent concurrently.
+    pub(crate) fn hold_in_flight_htlcs(&mut self, payment_id: PaymentId, succeeded: bool) {
+        if self.concurrency > 1 && succeeded {
+            let in_flight = std::mem::take(&mut self.in_flight_htlcs);
+            self.unsettled_htlcs.push_back((payment_id, in_flight));
+        } else {
+            self.resolve_in_flight_htlcs();
+        }
+    }
+
+    /// Releases the liquidity the HTLCs of a settled payment have locked.
+    pub(crate) fn settle_htlcs(&mut self, payment_id: PaymentId) {
+        if let Some(idx) = self
+            .unsettled_htlcs
+            .iter()
+            .position(|(id, _)| *id == payment_id)
+        {
+            if let Some((_, htlcs)) = self.unsettled_htlcs.remove(idx) {
+                self.release_htlcs(&htlcs);
+            }
+        }
+    }
+
+    /// Settles the oldest payments until another payment may be sent without exceeding the
+    /// concurrency.
+    pub(crate) fn make_room_for_payment(&mut self) {
+        while self.unsettled_htlcs.len() >= self.concurrency {
+            if let Some((_, htlcs)) = self.unsettled_htlcs.pop_front() {
+                self.release_htlcs(&htlcs);
+            }
+        }
+    }
+
     /// Credits all edges in the path (Source gains whereas the rest lose)
+    /// The amounts may contain several MPP shards, all of which share the same source.
     pub(crate) fn revert_payment(&mut self, amounts: &[(ID, String, usize)]) {
         let total: usize = amounts.iter().map(|t| t.2).sum::<usize>();
         debug!("Reverting {} msat.", total);
-        for (idx, (node, channel_id, amt)) in amounts.iter().enumerate() {
-            // source
-            if idx == 0 {
+        let source = match amounts.first() {
+            Some(first) => first.0.clone(),
+            None => return,
+        };
+        for (node, channel_id, amt) in amounts.iter() {
+            if *node == source {
                 let current_balance = self.graph.get_channel_balance(node, channel_id);
                 self.graph
-                    .update_channel_balance(channel_id, current_balance + amt);
+                    .update_channel_balance(node, channel_id, current_balance + amt);
             } else {
                 let current_balance = self.graph.get_channel_balance(node, channel_id);
                 self.graph
-                    .update_channel_balance(channel_id, current_balance - amt);
+                    .update_channel_balance(node, channel_id, current_balance - amt);
             }
         }
     }
@@ -286,6 +516,7 @@ pub(crate) mod tests {
     use crate::{
         core_types::graph::Graph, AdversarySelection, Invoice, PaymentParts, RoutingMetric,
     };
+    use std::collections::HashMap;
 
     pub fn init_sim(path: Option<String>, number_of_adversaries: Option<Vec<usize>>) -> Simulation {
         let seed = 0;
@@ -297,7 +528,7 @@ pub(crate) mod tests {
         };
         let path = std::path::Path::new(&path);
         let mut graph = Graph::to_sim_graph(
-            &network_parser::Graph::from_json_file(&path, network_parser::GraphSource::Lnresearch)
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
                 .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
@@ -308,6 +539,7 @@ pub(crate) mod tests {
         for edges in graph.edges.values_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let adversary_selection = vec![AdversarySelection::Random];
@@ -322,6 +554,42 @@ pub(crate) mod tests {
         )
     }
 
+    /// Graph of bidirectional channels (src, dest, fee_base_msat) named "src-dest" whose edges
+    /// all have a balance of 10000 msat
+    pub fn graph_of_channels(channels: &[(&str, &str, usize)]) -> Graph {
+        let mut edges: HashMap<ID, Vec<crate::Edge>> = HashMap::new();
+        for (src, dest, fee_base_msat) in channels {
+            for (source, destination) in [(src, dest), (dest, src)] {
+                edges
+                    .entry(source.to_string())
+                    .or_default()
+                    .push(crate::Edge {
+                        channel_id: format!("{}-{}", src, dest),
+                        source: source.to_string(),
+                        destination: destination.to_string(),
+                        fee_base_msat: *fee_base_msat,
+                        capacity: 100000,
+                        htlc_maximum_msat: 100000,
+                        balance: 10000,
+                        liquidity: 10000,
+                        ..Default::default()
+                    });
+            }
+        }
+        let mut graph = Graph {
+            nodes: edges
+                .keys()
+                .map(|id| crate::Node {
+                    id: id.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        graph.set_edges(edges);
+        graph
+    }
+
     #[test]
     fn reverse_payment() {
         let balance = 4711;
@@ -351,6 +619,37 @@ pub(crate) mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn reverse_concatenated_mpp_shards() {
+        let balance = 4711;
+        let mut simulator = init_sim(None, None);
+        // two concatenated shards from alice to chan, only the first starts the list
+        let amounts_to_reverse = Vec::from([
+            ("alice".to_string(), "alice1".to_string(), 130),
+            ("bob".to_string(), "bob2".to_string(), 30),
+            ("chan".to_string(), "chan1".to_string(), 100),
+            ("alice".to_string(), "alice1".to_string(), 60),
+            ("bob".to_string(), "bob2".to_string(), 10),
+            ("chan".to_string(), "chan1".to_string(), 50),
+        ]);
+        simulator.revert_payment(&amounts_to_reverse);
+        let expected = balance + 130 + 60;
+        let actual = simulator
+            .graph
+            .get_channel_balance(&"alice".to_string(), &"alice1".to_string());
+        assert_eq!(expected, actual);
+        let expected = balance - 30 - 10;
+        let actual = simulator
+            .graph
+            .get_channel_balance(&"bob".to_string(), &"bob2".to_string());
+        assert_eq!(expected, actual);
+        let expected = balance - 100 - 50;
+        let actual = simulator
+            .graph
+            .get_channel_balance(&"chan".to_string(), &"chan1".to_string());
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn payment_transfer_success() {
         let source = "alice".to_string();
@@ -368,21 +667,20 @@ pub(crate) mod tests {
             RoutingMetric::MinFee,
             PaymentParts::Single,
         );
-        let candidate_paths = path_finder.find_path().unwrap();
+        let mut candidate_paths = path_finder.find_path().unwrap();
         let payment_shard = &mut PaymentShard {
-            payment_id: 0,
-            source,
-            dest,
-            amount,
             succeeded: true,
             used_path: candidate_paths.clone(),
-            min_shard_amt: 10,
-            htlc_attempts: 0,
-            failed_paths: vec![],
+            ..Payment::new(0, source, dest, amount, Some(10)).to_shard(amount)
         };
         assert!(
             simulator
-                .attempt_payment(payment_shard, &candidate_paths, &mut path_finder)
+                .attempt_payment(
+                    payment_shard,
+                    &mut candidate_paths,
+                    &mut path_finder,
+                    PaymentParts::Single
+                )
                 .0
         );
         let expected = balance - 1100;
@@ -402,6 +700,60 @@ pub(crate) mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn shards_cannot_share_tight_channel() {
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = init_sim(None, None);
+        let amount = 1000;
+        // bob's channel can forward only one of the shards at a time
+        let tight_channel = "bob2".to_string();
+        simulator
+            .graph
+            .update_channel_balance(&"bob".to_string(), &tight_channel, 1500);
+        simulator.add_invoice(Invoice::new(0, 2 * amount, &source, &dest));
+        let graph = Box::new(simulator.graph.clone());
+        let mut path_finder = PathFinder::new(
+            source.clone(),
+            dest.clone(),
+            amount,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Split,
+        );
+        let mut candidate_path = path_finder.find_path().unwrap();
+        let mut shard = Payment::new(0, source, dest, amount, Some(10)).to_shard(amount);
+        let mut first_shard = shard.clone();
+        let (succeeded, _) = simulator.attempt_payment(
+            &mut first_shard,
+            &mut candidate_path,
+            &mut path_finder,
+            PaymentParts::Split,
+        );
+        assert!(succeeded);
+        // the first shard's HTLC is still in flight
+        let bob = "bob".to_string();
+        let balance = simulator.graph.get_channel_balance(&bob, &tight_channel);
+        assert_eq!(
+            simulator.graph.get_channel_liquidity(&bob, &tight_channel),
+            balance - amount
+        );
+        let (succeeded, to_revert) = simulator.attempt_payment(
+            &mut shard,
+            &mut candidate_path,
+            &mut path_finder,
+            PaymentParts::Split,
+        );
+        assert!(!succeeded);
+        simulator.revert_payment(&to_revert);
+        // once the payment is resolved the liquidity is available again
+        simulator.resolve_in_flight_htlcs();
+        assert_eq!(
+            simulator.graph.get_channel_liquidity(&bob, &tight_channel),
+            balance
+        );
+    }
+
     #[test]
     // checking that balances are unaltered. Failure at the last node due to no invoice
     fn payment_failure_no_invoice() {
@@ -418,20 +770,18 @@ pub(crate) mod tests {
             RoutingMetric::MinFee,
             PaymentParts::Single,
         );
-        let candidate_paths = path_finder.find_path().unwrap();
+        let mut candidate_paths = path_finder.find_path().unwrap();
         let payment_shard = &mut PaymentShard {
-            payment_id: 0,
-            source,
-            dest,
-            amount,
             succeeded: true,
             used_path: candidate_paths.clone(),
-            min_shard_amt: 10,
-            htlc_attempts: 0,
-            failed_paths: vec![],
+            ..Payment::new(0, source, dest, amount, Some(10)).to_shard(amount)
         };
-        let (success, transferred) =
-            simulator.attempt_payment(payment_shard, &candidate_paths, &mut path_finder);
+        let (success, transferred) = simulator.attempt_payment(
+            payment_shard,
+            &mut candidate_paths,
+            &mut path_finder,
+            PaymentParts::Single,
+        );
         simulator.revert_payment(&transferred);
         assert!(!success);
         for edges in simulator.graph.edges.values() {
@@ -452,7 +802,9 @@ pub(crate) mod tests {
         let balance = 100;
         let mut simulator = init_sim(None, None);
         let graph = simulator.graph.clone();
-        simulator.graph.update_channel_balance(&channel_id, balance);
+        simulator
+            .graph
+            .update_channel_balance(&"bob".to_string(), &channel_id, balance);
         let mut path_finder = PathFinder::new(
             source.clone(),
             dest.clone(),
@@ -461,20 +813,17 @@ pub(crate) mod tests {
             RoutingMetric::MinFee,
             PaymentParts::Single,
         );
-        let candidate_paths = path_finder.find_path().unwrap();
+        let mut candidate_paths = path_finder.find_path().unwrap();
         let payment_shard = &mut PaymentShard {
-            payment_id: 0,
-            source,
-            dest,
-            amount,
-            succeeded: false,
             used_path: candidate_paths.clone(),
-            min_shard_amt: 10,
-            htlc_attempts: 0,
-            failed_paths: vec![],
+            ..Payment::new(0, source, dest, amount, Some(10)).to_shard(amount)
         };
-        let (success, transferred) =
-            simulator.attempt_payment(payment_shard, &candidate_paths, &mut path_finder);
+        let (success, transferred) = simulator.attempt_payment(
+            payment_shard,
+            &mut candidate_paths,
+            &mut path_finder,
+            PaymentParts::Single,
+        );
         simulator.revert_payment(&transferred);
         assert!(!success);
         assert_eq!(
@@ -494,7 +843,9 @@ pub(crate) mod tests {
         let balance = 100;
         let mut simulator = init_sim(None, None);
         let graph = simulator.graph.clone();
-        simulator.graph.update_channel_balance(&channel_id, balance);
+        simulator
+            .graph
+            .update_channel_balance(&"bob".to_string(), &channel_id, balance);
         let mut path_finder = PathFinder::new(
             source.clone(),
             dest.clone(),
@@ -503,21 +854,19 @@ pub(crate) mod tests {
             RoutingMetric::MinFee,
             PaymentParts::Single,
         );
-        let candidate_paths = path_finder.find_path().unwrap();
+        let mut candidate_paths = path_finder.find_path().unwrap();
         let payment_shard = &mut PaymentShard {
-            payment_id: 0,
-            source,
-            dest,
-            amount,
-            succeeded: false,
             used_path: candidate_paths.clone(),
-            min_shard_amt: 10,
-            htlc_attempts: 0,
-            failed_paths: vec![],
+            ..Payment::new(0, source, dest, amount, Some(10)).to_shard(amount)
         };
         assert!(
             !simulator
-                .attempt_payment(payment_shard, &candidate_paths, &mut path_finder)
+                .attempt_payment(
+                    payment_shard,
+                    &mut candidate_paths,
+                    &mut path_finder,
+                    PaymentParts::Single
+                )
                 .0
         );
         // edge is still there for future payments
@@ -525,10 +874,10 @@ pub(crate) mod tests {
             .graph
             .get_edge(&String::from("alice"), &String::from("bob"))
             .is_some());
-        assert!(!path_finder
+        assert!(path_finder
             .graph
             .get_edge(&String::from("alice"), &String::from("bob"))
-            .is_some());
+            .is_none());
         // 0 because edges have been removed and get_balance returns 0 if edge is not found
         assert_eq!(
             path_finder
@@ -551,7 +900,7 @@ pub(crate) mod tests {
         let amount = 500000;
         let path = std::path::Path::new("../data/gossip-20210906_1000UTC.json");
         let graph = Graph::to_sim_graph(
-            &network_parser::Graph::from_json_file(&path, network_parser::GraphSource::Lnresearch)
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
                 .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
@@ -571,23 +920,73 @@ pub(crate) mod tests {
             "03c45cf25622ec07c56d13b7043e59c8c27ca822be58140b213edaea6849380349".to_string();
         let dest = "0329ae9a574b7120456d2ebf6626506e6a75255edd91ac4ea03ea008b9bad67bd2".to_string();
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
         assert!(simulator.send_single_payment(payment));
     }
 
+    #[test]
+    fn underpaying_shard_fails_when_invoice_amount_is_enforced() {
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = init_sim(None, None);
+        let amount = 1000;
+        simulator.add_invoice(Invoice::new(0, 2 * amount, &source, &dest));
+        simulator.set_enforce_invoice_amount(true);
+        let payment = &mut Payment::new(0, source.clone(), dest.clone(), amount, Some(10));
+        assert!(!simulator.send_single_payment(payment));
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::InvoiceUnderpaid)
+        );
+        let balance = simulator
+            .graph
+            .get_channel_balance(&source, &"alice1".to_string());
+        assert_eq!(balance, 4711);
+        // lenient destinations accept any amount
+        simulator.set_enforce_invoice_amount(false);
+        let payment = &mut Payment::new(0, source, dest, amount, Some(10));
+        assert!(simulator.send_single_payment(payment));
+    }
+
+    #[test]
+    fn retry_avoids_area_of_failure() {
+        let mut graph = graph_of_channels(&[
+            ("alice", "carol", 0),
+            ("carol", "eve", 10),
+            ("alice", "bob", 0),
+            ("bob", "carol", 5),
+            ("carol", "dave", 10),
+            ("dave", "eve", 10),
+            ("alice", "frank", 0),
+            ("frank", "eve", 100),
+        ]);
+        // carol cannot forward the payment to eve
+        graph.update_channel_balance(&"carol".to_string(), &"carol-eve".to_string(), 1000);
+        let mut simulator = init_sim(None, None);
+        simulator.graph = graph;
+        let (source, dest) = ("alice".to_string(), "eve".to_string());
+        simulator.add_invoice(Invoice::new(0, 1000, &source, &dest));
+        let payment = &mut Payment::new(0, source, dest, 1000, Some(10));
+        assert!(simulator.send_single_payment(payment));
+        assert_eq!(payment.failed_paths.len(), 1);
+        assert_eq!(
+            payment.failed_paths[0].path.get_involved_nodes(),
+            vec!["alice", "carol", "eve"]
+        );
+        // the route via bob and carol is cheaper but carol's channels are penalised
+        assert_eq!(
+            payment.used_paths[0].path.get_involved_nodes(),
+            vec!["alice", "frank", "eve"]
+        );
+    }
+
     #[test]
     fn payment_failure_max_channel_capacity() {
         let source = "alice".to_string();
@@ -599,19 +998,110 @@ pub(crate) mod tests {
         let amount = capacity * 2;
         simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         assert!(!simulator.send_single_payment(payment));
     }
+
+    #[test]
+    fn learned_priors_avoid_failing_channel() {
+        let mut graph = graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "dave", 0),
+            ("alice", "carol", 0),
+            ("carol", "dave", 0),
+        ]);
+        // the route via carol is less likely to succeed a priori
+        for edges in graph.edges.values_mut() {
+            for edge in edges.iter_mut().filter(|e| e.channel_id == "carol-dave") {
+                edge.capacity = 50000;
+            }
+        }
+        // but bob cannot forward to dave
+        graph.update_channel_balance(&"bob".to_string(), &"bob-dave".to_string(), 1000);
+        let (source, dest) = ("alice".to_string(), "dave".to_string());
+        let send = |simulator: &mut Simulation, id: usize| {
+            simulator.add_invoice(Invoice::new(id, 1000, &source, &dest));
+            let mut payment = Payment::new(id, source.clone(), dest.clone(), 1000, None);
+            assert!(simulator.send_single_payment(&mut payment));
+            payment
+        };
+        let mut simulator = init_sim(None, None);
+        simulator.graph = graph;
+        simulator.routing_metric = RoutingMetric::MaxProb;
+        let mut learning = simulator.clone();
+        learning.set_learn_channel_priors(true);
+        for id in 0..3 {
+            let payment = send(&mut simulator, id);
+            assert_eq!(payment.failed_paths.len(), 1);
+        }
+        assert!(simulator.channel_priors.is_empty());
+        let payment = send(&mut learning, 0);
+        assert_eq!(payment.failed_paths.len(), 1);
+        assert!(learning.channel_priors["bob-dave"] < 1.0);
+        for id in 1..3 {
+            let payment = send(&mut learning, id);
+            assert!(payment.failed_paths.is_empty());
+            assert_eq!(
+                payment.used_paths[0].path.get_involved_nodes(),
+                vec!["alice", "carol", "dave"]
+            );
+        }
+        // another run starts without any knowledge
+        learning.reset(0);
+        assert!(learning.channel_priors.is_empty());
+    }
+
+    #[test]
+    fn longer_paths_hold_htlcs_longer() {
+        let mut simulator = init_sim(None, None);
+        simulator.set_hop_delay_secs(2.0);
+        let source = "alice".to_string();
+        let hold_times: Vec<f32> = ["bob", "chan", "dina"]
+            .iter()
+            .enumerate()
+            .map(|(id, dest)| {
+                let dest = dest.to_string();
+                simulator.add_invoice(Invoice::new(id, 1000, &source, &dest));
+                let mut payment = Payment::new(id, source.clone(), dest, 1000, None);
+                assert!(simulator.send_single_payment(&mut payment));
+                let info = crate::io::PaymentInfo::from_payment(&payment);
+                assert_eq!(
+                    info.used_paths[0].hold_time_secs,
+                    payment.used_paths[0].hold_time_secs
+                );
+                info.used_paths[0].hold_time_secs
+            })
+            .collect();
+        assert_eq!(hold_times, vec![2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn receive_capacity_check_can_be_disabled() {
+        let source = "alice".to_string();
+        let dest = "bob".to_string();
+        let amount = 1000;
+        let mut simulator = init_sim(None, None);
+        // bob's side of the channel is almost full
+        let capacity = simulator.graph.get_edge(&dest, &source).unwrap().capacity;
+        simulator
+            .graph
+            .update_channel_balance(&dest, &"bob1".to_string(), capacity - amount / 2);
+        simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+        let payment = &mut Payment::new(0, source.clone(), dest.clone(), amount, None);
+        assert!(!simulator.send_single_payment(payment));
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::DestinationCapacity)
+        );
+        simulator.set_check_receive_capacity(false);
+        let payment = &mut Payment::new(1, source.clone(), dest.clone(), amount, None);
+        simulator.add_invoice(Invoice::new(1, amount, &source, &dest));
+        assert!(simulator.send_single_payment(payment));
+    }
 }
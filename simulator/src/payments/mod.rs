@@ -1,11 +1,12 @@
 use crate::ID;
+use serde::{Deserialize, Serialize};
 
 pub mod attempt;
 pub mod payment;
 
 /// the recipient generates an invoice on their node, which will contain basic information,
 /// such as amount, destination and validity
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Invoice {
     /// Unique invoice id (represents the hash)
     pub(crate) id: usize,
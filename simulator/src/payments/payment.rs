@@ -1,9 +1,9 @@
 use crate::{traversal::pathfinding::CandidatePath, PaymentId, ID};
 
 use log::error;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Payment {
     /// Unique payment identifier
     pub(crate) payment_id: PaymentId,
@@ -18,11 +18,49 @@ pub struct Payment {
     /// Paths payment can take
     /// unstable, might change
     pub used_paths: Vec<CandidatePath>,
+    /// HTLCs sent along the attempted paths
     pub htlc_attempts: usize,
+    /// Alternate routes tried after an attempt failed
+    pub route_attempts: usize,
     /// Payment amounts that have already succeed, used for MPP payments
     pub(crate) failed_amounts: Vec<usize>,
     pub(crate) successful_shards: Vec<(ID, String, usize)>,
     pub(crate) failed_paths: Vec<CandidatePath>,
+    /// Index of the hop each failed attempt failed at (0 being the sender)
+    pub(crate) failure_hops: Vec<usize>,
+    /// Node and channel each failed attempt failed at
+    pub(crate) failing_channels: Vec<(ID, String)>,
+    /// Accumulated wall-clock time spent searching for paths
+    pub(crate) pathfinding_ms: u128,
+    /// Net balance change per node and channel caused by this payment. Only recorded if enabled
+    pub balance_deltas: Vec<(ID, String, i64)>,
+    /// Why the payment failed; None for successful payments
+    pub failure_reason: Option<FailureReason>,
+    /// Most the sender is willing to pay in fees. Falls back to the simulation's budget if None
+    pub max_fee_msat: Option<usize>,
+}
+
+/// The reason a payment (or its last attempt) failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureReason {
+    /// The sender's balance does not cover the amount (including fees)
+    InsufficientSenderBalance,
+    /// No path to the destination could be found
+    NoRoute,
+    /// The destination cannot receive the amount without exceeding its channels' capacity
+    DestinationCapacity,
+    /// An intermediate hop did not have enough liquidity to forward the amount
+    InsufficientLiquidity,
+    /// The destination has no matching invoice
+    NoInvoice,
+    /// The payment was not delivered within the maximum number of retries
+    MaxRetries,
+    /// The MPP payment could not be split any further
+    SplitLimit,
+    /// No route whose fees fit the fee budget was found
+    FeeBudgetExceeded,
+    /// The destination received less than the invoice amount
+    InvoiceUnderpaid,
 }
 
 #[derive(Debug, Clone)]
@@ -37,7 +75,13 @@ pub struct PaymentShard {
     pub(crate) used_path: CandidatePath,
     pub(crate) min_shard_amt: usize,
     pub(crate) htlc_attempts: usize,
+    pub(crate) route_attempts: usize,
     pub(crate) failed_paths: Vec<CandidatePath>,
+    pub(crate) failure_hops: Vec<usize>,
+    pub(crate) failing_channels: Vec<(ID, String)>,
+    pub(crate) pathfinding_ms: u128,
+    pub(crate) failure_reason: Option<FailureReason>,
+    pub(crate) max_fee_msat: Option<usize>,
 }
 
 impl Payment {
@@ -62,9 +106,16 @@ impl Payment {
             num_parts: 1,
             used_paths: Vec::default(),
             htlc_attempts: 0,
+            route_attempts: 0,
             failed_amounts: Vec::default(),
             successful_shards: Vec::default(),
             failed_paths: Vec::default(),
+            failure_hops: Vec::default(),
+            failing_channels: Vec::default(),
+            pathfinding_ms: 0,
+            balance_deltas: Vec::default(),
+            failure_reason: None,
+            max_fee_msat: None,
         }
     }
 
@@ -92,7 +143,7 @@ impl Payment {
         } else {
             // ceil one, floor the either
             let prev_amt = amt_to_split;
-            let shard1_amount = (prev_amt + 2 - 1) / 2;
+            let shard1_amount = prev_amt.div_ceil(2);
             let shard2_amount = prev_amt / 2;
             assert_eq!(
                 shard1_amount + shard2_amount,
@@ -101,14 +152,26 @@ impl Payment {
                 shard1_amount + shard2_amount,
                 amt_to_split
             );
+            // the fee budget is shared in proportion to the shards' amounts
+            let shard_budget = |shard_amount: usize| {
+                payment
+                    .max_fee_msat
+                    .map(|max_fee| max_fee * shard_amount / amt_to_split)
+            };
             let shard1 = Payment {
                 amount_msat: shard1_amount,
                 htlc_attempts: 0,
+                route_attempts: 0,
+                pathfinding_ms: 0,
+                max_fee_msat: shard_budget(shard1_amount),
                 ..payment.clone()
             };
             let shard2 = Payment {
                 amount_msat: shard2_amount,
                 htlc_attempts: 0,
+                route_attempts: 0,
+                pathfinding_ms: 0,
+                max_fee_msat: shard_budget(shard2_amount),
                 ..payment.clone()
             };
             Some((shard1, shard2))
@@ -127,7 +190,13 @@ impl PaymentShard {
             min_shard_amt: crate::MIN_SHARD_AMOUNT,
             succeeded: payment.succeeded,
             htlc_attempts: payment.htlc_attempts,
+            route_attempts: payment.route_attempts,
             failed_paths: payment.failed_paths.clone(),
+            failure_hops: payment.failure_hops.clone(),
+            failing_channels: payment.failing_channels.clone(),
+            pathfinding_ms: payment.pathfinding_ms,
+            failure_reason: payment.failure_reason,
+            max_fee_msat: payment.max_fee_msat,
         }
     }
 
@@ -142,9 +211,16 @@ impl PaymentShard {
             num_parts,
             used_paths: vec![self.used_path.clone()],
             htlc_attempts: self.htlc_attempts,
+            route_attempts: self.route_attempts,
             failed_amounts: Vec::default(),
             successful_shards: Vec::default(),
             failed_paths: self.failed_paths.clone(),
+            failure_hops: self.failure_hops.clone(),
+            failing_channels: self.failing_channels.clone(),
+            pathfinding_ms: self.pathfinding_ms,
+            balance_deltas: Vec::default(),
+            failure_reason: self.failure_reason,
+            max_fee_msat: self.max_fee_msat,
         }
     }
 }
@@ -179,14 +255,9 @@ mod tests {
             source: source.clone(),
             dest,
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: crate::MIN_SHARD_AMOUNT,
-            used_paths: Vec::default(),
             num_parts: 1,
-            htlc_attempts: 0,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         assert_eq!(actual, expected);
         assert_eq!(actual.succeeded, expected.succeeded);
@@ -208,12 +279,9 @@ mod tests {
             amount_msat: amount,
             succeeded: true,
             min_shard_amt: crate::MIN_SHARD_AMOUNT,
-            used_paths: Vec::default(),
             num_parts: 1,
             htlc_attempts: 1,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         let shard = payment.to_shard(amount);
         assert_eq!(shard.payment_id, id);
@@ -231,18 +299,13 @@ mod tests {
         let dest = "dest".to_string();
         let amount = crate::MIN_SHARD_AMOUNT * 2 + 1;
         let payment = Payment {
-            payment_id: 0,
             source: source.clone(),
             dest,
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: crate::MIN_SHARD_AMOUNT,
-            used_paths: Vec::default(),
             num_parts: 1,
             htlc_attempts: 1,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         let actual = Payment::split_payment(&payment).unwrap();
         let expected = (
@@ -265,18 +328,13 @@ mod tests {
         let dest = "dest".to_string();
         let amount = crate::MIN_SHARD_AMOUNT + 1;
         let payment = Payment {
-            payment_id: 0,
             source: source.clone(),
             dest,
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: crate::MIN_SHARD_AMOUNT,
-            used_paths: Vec::default(),
             num_parts: 1,
             htlc_attempts: 1,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         assert!(Payment::split_payment(&payment).is_none());
     }
@@ -287,18 +345,13 @@ mod tests {
         let dest = "dest".to_string();
         let amount = crate::MIN_SHARD_AMOUNT;
         let payment = Payment {
-            payment_id: 0,
             source: source.clone(),
             dest,
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: crate::MIN_SHARD_AMOUNT / 2,
-            used_paths: Vec::default(),
             num_parts: 1,
             htlc_attempts: 1,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         let actual = Payment::split_payment(&payment).unwrap();
         let expected = (
@@ -334,14 +387,9 @@ mod tests {
             source: source.clone(),
             dest,
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt,
-            used_paths: Vec::default(),
             num_parts: 1,
-            htlc_attempts: 0,
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         assert_eq!(actual, expected);
         assert_eq!(actual.succeeded, expected.succeeded);
@@ -0,0 +1,131 @@
+use crate::{
+    core_types::graph::Graph, sim::Simulation, AdversarySelection, PaymentParts, RoutingMetric,
+};
+
+/// Fluent alternative to [`Simulation::new`].
+/// Unless set otherwise, a simulation of 1000 sat single-path payments routed by minimal fees on
+/// an empty graph with seed 19 and no adversaries is built.
+#[derive(Clone, Debug)]
+pub struct SimulationBuilder {
+    seed: u64,
+    graph: Graph,
+    amount: usize,
+    routing_metric: RoutingMetric,
+    payment_parts: PaymentParts,
+    number_of_adversaries: Option<Vec<usize>>,
+    adversary_selection: Vec<AdversarySelection>,
+}
+
+impl Default for SimulationBuilder {
+    fn default() -> Self {
+        Self {
+            seed: 19,
+            graph: Graph::default(),
+            amount: crate::to_millisatoshi(1000),
+            routing_metric: RoutingMetric::MinFee,
+            payment_parts: PaymentParts::Single,
+            number_of_adversaries: None,
+            adversary_selection: vec![],
+        }
+    }
+}
+
+impl SimulationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn graph(mut self, graph: Graph) -> Self {
+        self.graph = graph;
+        self
+    }
+
+    /// Payment amount in msat
+    pub fn amount(mut self, amount: usize) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn routing_metric(mut self, routing_metric: RoutingMetric) -> Self {
+        self.routing_metric = routing_metric;
+        self
+    }
+
+    pub fn payment_parts(mut self, payment_parts: PaymentParts) -> Self {
+        self.payment_parts = payment_parts;
+        self
+    }
+
+    /// Numbers of adversaries to evaluate; see [`Simulation::new`]
+    pub fn adversaries(mut self, number_of_adversaries: Vec<usize>) -> Self {
+        self.number_of_adversaries = Some(number_of_adversaries);
+        self
+    }
+
+    pub fn adversary_selection(mut self, adversary_selection: &[AdversarySelection]) -> Self {
+        self.adversary_selection = adversary_selection.to_owned();
+        self
+    }
+
+    pub fn build(self) -> Simulation {
+        Simulation::new(
+            self.seed,
+            self.graph,
+            self.amount,
+            self.routing_metric,
+            self.payment_parts,
+            self.number_of_adversaries,
+            &self.adversary_selection,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{simulator::tests::without_timing, SimResult};
+
+    #[test]
+    fn builder_and_constructor_are_equivalent() {
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                std::path::Path::new("../test_data/lnbook_example.json"),
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let pairs = vec![
+            ("alice".to_owned(), "dina".to_owned()),
+            ("chan".to_owned(), "bob".to_owned()),
+            ("dina".to_owned(), "alice".to_owned()),
+        ];
+        let run = |mut simulation: Simulation| -> SimResult {
+            without_timing(simulation.run(pairs.clone().into_iter(), None, false))
+        };
+        let constructed = Simulation::new(
+            7,
+            graph.clone(),
+            2000,
+            RoutingMetric::MinFee,
+            PaymentParts::Split,
+            None,
+            &[],
+        );
+        let built = SimulationBuilder::new()
+            .seed(7)
+            .graph(graph)
+            .amount(2000)
+            .routing_metric(RoutingMetric::MinFee)
+            .payment_parts(PaymentParts::Split)
+            .build();
+        let expected = run(constructed);
+        assert_eq!(expected.total_num, pairs.len());
+        assert_eq!(run(built), expected);
+    }
+}
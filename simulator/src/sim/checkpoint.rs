@@ -0,0 +1,193 @@
+use crate::{
+    payment::Payment,
+    payments::Invoice,
+    sim::{Htlc, SimResult, Simulation},
+    PaymentId, ID,
+};
+
+use log::info;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// The state a simulation accumulates while running payments
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    /// (node, channel_id, balance) of every edge
+    balances: Vec<(ID, String, usize)>,
+    current_payment_id: PaymentId,
+    total_num_payments: usize,
+    num_successful: usize,
+    successful_payments: Vec<Payment>,
+    num_failed: usize,
+    failed_payments: Vec<Payment>,
+    node_hits: HashMap<ID, usize>,
+    #[serde(default)]
+    channel_usage: HashMap<String, usize>,
+    /// State of the simulation's RNG so that the resumed run draws what the interrupted one would
+    /// have drawn
+    rng: ChaCha12Rng,
+    outstanding_invoices: BTreeMap<ID, HashMap<usize, Invoice>>,
+    in_flight_htlcs: Vec<Htlc>,
+    unsettled_htlcs: VecDeque<(PaymentId, Vec<Htlc>)>,
+    /// Success probabilities learned from earlier payments
+    #[serde(default)]
+    channel_priors: HashMap<String, f32>,
+}
+
+impl Simulation {
+    /// Writes the state of the simulation to `path` so that an interrupted batch job can resume
+    /// with [`Simulation::load_checkpoint`] or [`Simulation::resume_from_checkpoint`].
+    /// Only what changes while running is saved, i.e. neither the topology nor the configuration.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let checkpoint = Checkpoint {
+            balances: self.graph.get_edge_balances(),
+            current_payment_id: self.current_payment_id,
+            total_num_payments: self.total_num_payments,
+            num_successful: self.num_successful,
+            successful_payments: self.successful_payments.clone(),
+            num_failed: self.num_failed,
+            failed_payments: self.failed_payments.clone(),
+            node_hits: self.node_hits.clone(),
+            channel_usage: self.channel_usage.clone(),
+            rng: self.rng.clone(),
+            outstanding_invoices: self.outstanding_invoices.clone(),
+            in_flight_htlcs: self.in_flight_htlcs.clone(),
+            unsettled_htlcs: self.unsettled_htlcs.clone(),
+            channel_priors: self.channel_priors.clone(),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &checkpoint)?;
+        writer.flush()?;
+        info!("Simulation checkpoint written to {}.", path.display());
+        Ok(())
+    }
+
+    /// Loads the checkpoint at `path` with [`Simulation::load_checkpoint`] and runs the remaining
+    /// payment pairs. The result covers the payments of the interrupted run, too.
+    pub fn resume_from_checkpoint(
+        &mut self,
+        path: &Path,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        min_shard_amt: Option<usize>,
+        run_all_adversary_scenarios: bool,
+    ) -> Result<SimResult, Box<dyn Error>> {
+        self.load_checkpoint(path)?;
+        let amount = self.amount;
+        Ok(self.run_payments(
+            payment_pairs.map(|(src, dest)| (src, dest, amount)),
+            min_shard_amt,
+            run_all_adversary_scenarios,
+            None,
+            None,
+            self.total_num_payments,
+        ))
+    }
+
+    /// Restores the state saved by [`Simulation::save_checkpoint`].
+    /// The simulation has to be created with the same graph and configuration as the one that was
+    /// saved.
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let checkpoint: Checkpoint = serde_json::from_reader(reader)?;
+        // the checkpoint's in-flight HTLCs lock liquidity on top of the saved balances
+        self.graph.restore_edge_balances(&checkpoint.balances);
+        for (node, channel_id, amount) in checkpoint.in_flight_htlcs.iter().chain(
+            checkpoint
+                .unsettled_htlcs
+                .iter()
+                .flat_map(|(_, htlcs)| htlcs),
+        ) {
+            self.graph
+                .reserve_channel_liquidity(node, channel_id, *amount);
+        }
+        self.current_payment_id = checkpoint.current_payment_id;
+        self.total_num_payments = checkpoint.total_num_payments;
+        self.num_successful = checkpoint.num_successful;
+        self.successful_payments = checkpoint.successful_payments;
+        self.num_failed = checkpoint.num_failed;
+        self.failed_payments = checkpoint.failed_payments;
+        self.node_hits = checkpoint.node_hits;
+        self.channel_usage = checkpoint.channel_usage;
+        self.rng = checkpoint.rng;
+        self.outstanding_invoices = checkpoint.outstanding_invoices;
+        self.in_flight_htlcs = checkpoint.in_flight_htlcs;
+        self.unsettled_htlcs = checkpoint.unsettled_htlcs;
+        self.channel_priors = checkpoint.channel_priors;
+        info!("Simulation resumed from checkpoint {}.", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::simulator::tests::without_timing;
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(src, dest)| (src.to_string(), dest.to_string()))
+                .collect::<Vec<(ID, ID)>>()
+        };
+        let before = pairs(&[("alice", "dina"), ("chan", "alice"), ("bob", "dina")]);
+        let after = pairs(&[("dina", "alice"), ("alice", "chan"), ("alice", "dina")]);
+        let payments = |result: SimResult| {
+            let result = without_timing(result);
+            let mut payments = result.successful_payments;
+            payments.extend(result.failed_payments);
+            payments.sort_by_key(|payment| payment.payment_id);
+            payments
+        };
+        let simulation = || {
+            let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+            // HTLCs failing at random depend on the simulation's RNG
+            simulator.set_random_failure_prob(0.3);
+            simulator
+        };
+        let mut uninterrupted = simulation();
+        let all_pairs: Vec<(ID, ID)> = before.iter().chain(after.iter()).cloned().collect();
+        let expected = uninterrupted.run(all_pairs.into_iter(), None, false);
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut interrupted = simulation();
+        interrupted.run(before.into_iter(), None, false);
+        interrupted.save_checkpoint(file.path()).unwrap();
+        let mut resumed = simulation();
+        let actual = resumed
+            .resume_from_checkpoint(file.path(), after.into_iter(), None, false)
+            .unwrap();
+        assert_eq!(resumed.current_payment_id, 6);
+        assert_eq!(actual.total_num, 6);
+        assert_eq!(actual.total_num, expected.total_num);
+        assert_eq!(actual.num_succesful, expected.num_succesful);
+        assert_eq!(payments(actual), payments(expected));
+        assert_eq!(
+            resumed.graph.get_edge_balances(),
+            uninterrupted.graph.get_edge_balances()
+        );
+    }
+
+    #[test]
+    fn channel_priors_are_checkpointed() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        simulator.set_learn_channel_priors(true);
+        simulator
+            .channel_priors
+            .insert("bob2".to_string(), crate::PRIOR_FAILURE_DECAY);
+        simulator.save_checkpoint(file.path()).unwrap();
+        let mut resumed = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        resumed.set_learn_channel_priors(true);
+        resumed.load_checkpoint(file.path()).unwrap();
+        assert_eq!(resumed.channel_priors, simulator.channel_priors);
+    }
+}
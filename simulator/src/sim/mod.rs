@@ -1,16 +1,21 @@
 use crate::{
     payment::Payment,
-    stats::{Adversaries, PathDistances, PathDiversity},
+    stats::{Adversaries, FailureStats, PathDistances, PathDiversity},
+    AdversarySelection, ID,
 };
 use serde::Serialize;
 
+mod builder;
+mod checkpoint;
 mod simulator;
+pub use builder::*;
 pub use simulator::*;
 
 #[derive(Debug, Default, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SimResult {
     pub run: u64,
+    /// Amount of each payment or, if the amounts were drawn from a distribution, their mean
     pub amount: usize,
     pub total_num: usize,
     pub num_succesful: usize,
@@ -20,4 +25,87 @@ pub struct SimResult {
     pub adversaries: Vec<Adversaries>,
     pub path_distances: PathDistances,
     pub path_diversity: PathDiversity,
+    /// How often each node and channel was the failing hop of failed payments
+    pub failure_stats: FailureStats,
+    /// Share of payments that succeeded
+    pub success_rate: f32,
+    /// Median of the total fees paid by successful payments
+    pub median_fee_msat: usize,
+    /// Mean number of hops of the paths used by successful payments
+    pub mean_path_length: f32,
+    /// How often each node was an intermediary of a payment path, most frequent first
+    pub node_hits: Vec<(ID, usize)>,
+    /// How often HTLCs were forwarded through the most used channels, most used first
+    pub channel_usage: Vec<(String, usize)>,
+    /// Balance of each edge as (node, channel_id, balance) when the simulation was constructed
+    pub initial_balances: Vec<(ID, String, usize)>,
+    /// Balance of each edge as (node, channel_id, balance) once all payments have completed
+    pub final_balances: Vec<(ID, String, usize)>,
+}
+
+impl SimResult {
+    /// Mean anonymity set size of the payments observed by adversaries selected using `strategy`,
+    /// weighted by the payments' values.
+    /// A set's size is the mean of its sender and recipient set sizes.
+    pub fn value_weighted_anonymity(&self, strategy: &AdversarySelection) -> f32 {
+        let (weighted_size, total_value) = self
+            .adversaries
+            .iter()
+            .filter(|adv| adv.selection_strategy == *strategy)
+            .flat_map(|adv| adv.statistics.iter())
+            .flat_map(|stats| stats.anonymity_sets.iter())
+            .fold((0.0, 0.0), |(weighted_size, total_value), set| {
+                let size = (set.sender + set.recipient) as f32 / 2.0;
+                let value = set.amount_msat as f32;
+                (weighted_size + size * value, total_value + value)
+            });
+        if total_value == 0.0 {
+            0.0
+        } else {
+            weighted_size / total_value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{AnonymitySet, Statistics, TargetedAttack};
+
+    #[test]
+    fn value_weighted_anonymity() {
+        let anonymity_set = |sender, recipient, amount_msat| {
+            AnonymitySet::new(sender, recipient, true, true, amount_msat)
+        };
+        let statistics = Statistics {
+            number: 1,
+            hits: 2,
+            hits_successful: 2,
+            anonymity_sets: vec![anonymity_set(2, 4, 1000), anonymity_set(10, 20, 3000)],
+            targeted_attack: TargetedAttack::default(),
+            correlated: 0,
+            correlated_successful: 0,
+            prone_paths_prob: 0.0,
+            prone_paths_successful_prob: 0.0,
+            prone_payments_prob: 0.0,
+            prone_payments_successful_prob: 0.0,
+        };
+        let result = SimResult {
+            adversaries: vec![Adversaries {
+                selection_strategy: AdversarySelection::Random,
+                statistics: vec![statistics],
+            }],
+            ..Default::default()
+        };
+        // (3 * 1000 + 15 * 3000) / 4000
+        let expected = 12.0;
+        assert_eq!(
+            result.value_weighted_anonymity(&AdversarySelection::Random),
+            expected
+        );
+        assert_eq!(
+            result.value_weighted_anonymity(&AdversarySelection::HighDegree(Default::default())),
+            0.0
+        );
+    }
 }
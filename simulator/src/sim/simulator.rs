@@ -1,15 +1,31 @@
 use crate::{
     core_types::graph::Graph,
     event::*,
+    io::PaymentInfo,
     payment::Payment,
     sim::SimResult,
-    stats::{Adversaries, PathDistances, PathDiversity},
+    stats::{Adversaries, FailureStats, PathDistances, PathDiversity},
     time::Time,
-    AdversarySelection, Invoice, PaymentId, PaymentParts, RoutingMetric, WeightPartsCombi, ID,
+    AdversarySelection, AmountDistribution, Invoice, PaymentId, PaymentParts, RoutingMetric,
+    WeightPartsCombi, ID,
 };
-use log::{debug, error, info};
-use rand::{seq::IteratorRandom, SeedableRng};
-use std::collections::{BTreeMap, HashMap};
+use log::{debug, error, info, warn};
+use rand::{
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha12Rng;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Liquidity an HTLC has locked on a node's side of a channel (node, channel_id, amount)
+pub(crate) type Htlc = (ID, String, usize);
 
 #[derive(Clone)]
 pub struct Simulation {
@@ -18,7 +34,11 @@ pub struct Simulation {
     /// Payment amount to simulate
     pub(crate) amount: usize,
     /// Sim seed
-    run: u64,
+    pub(crate) run: u64,
+    /// Seeded with `run`; owned by the simulation so that simulations can run in parallel without
+    /// affecting each other's draws. The generator behind `StdRng` whose state can be saved in
+    /// checkpoints
+    pub(crate) rng: ChaCha12Rng,
     /// Fee minimisation or probability maximisation
     pub(crate) routing_metric: RoutingMetric,
     /// Single or multi-path
@@ -26,9 +46,9 @@ pub struct Simulation {
     /// Queue of events to be simulated
     pub(crate) event_queue: EventQueue,
     /// Assigned to each new payment
-    current_payment_id: PaymentId,
+    pub(super) current_payment_id: PaymentId,
     /// Invoices each node has issued; map of <node, <invoice id, invoice>
-    outstanding_invoices: BTreeMap<ID, HashMap<usize, Invoice>>,
+    pub(super) outstanding_invoices: BTreeMap<ID, HashMap<usize, Invoice>>,
     pub(crate) total_num_payments: usize,
     pub(crate) num_successful: usize,
     pub(crate) successful_payments: Vec<Payment>,
@@ -39,9 +59,55 @@ pub struct Simulation {
     pub(crate) adversaries: Vec<Adversaries>,
     // the number of times a node is included in a payment path
     pub(crate) node_hits: HashMap<ID, usize>,
+    /// The number of times an HTLC was forwarded through a channel
+    pub(crate) channel_usage: HashMap<String, usize>,
     pub(crate) path_distances: PathDistances,
     pub(crate) path_diversity: PathDiversity,
+    /// Lambdas the effective path diversity of MPP payments is computed for
+    pub(crate) diversity_lambdas: Vec<f32>,
+    /// Where the failed payments failed
+    pub(crate) failure_stats: FailureStats,
     pub(crate) adversary_selection: Vec<AdversarySelection>,
+    /// Record the net change of channel balances caused by each payment
+    pub(crate) balance_delta_log: bool,
+    /// Simulated time between two scheduled payments
+    pub(crate) payment_delay_secs: f32,
+    /// Simulated time each hop of a path adds until a payment is settled
+    pub(crate) hop_delay_secs: f32,
+    /// Number of alternate routes that are tried after the first route failed. Unbounded by
+    /// default
+    pub(crate) max_retries: usize,
+    /// Most a payment may pay in fees unless the payment sets its own budget. Unbounded by
+    /// default
+    pub(crate) max_fee_msat: Option<usize>,
+    /// Most blocks the timelocks of a route may sum to
+    pub(crate) max_total_cltv: usize,
+    /// Number of shortest paths single-path payments choose from
+    pub(crate) candidate_pool: usize,
+    /// Weight of the timelock penalty in the fee metric
+    pub(crate) risk_factor: usize,
+    /// Probability with which a hop fails to forward an HTLC despite sufficient liquidity
+    pub(crate) random_failure_prob: f32,
+    /// Whether destinations reject payments that deliver less than the invoice amount
+    pub(crate) enforce_invoice_amount: bool,
+    /// Whether payments fail if they would exceed the capacity of the receiver's channel
+    pub(crate) check_receive_capacity: bool,
+    /// Whether channel failures are remembered across payments
+    pub(crate) learn_channel_priors: bool,
+    /// Success probabilities of channels learned from failed attempts keyed by channel ID
+    pub(crate) channel_priors: HashMap<String, f32>,
+    /// Whether the simulation's state is checked for inconsistencies after each run
+    pub(crate) check_invariants: bool,
+    /// Only look for routes; payments whose route is found succeed without moving any funds
+    pub(crate) dry_run: bool,
+    /// Liquidity locked by HTLCs of the payment currently being sent
+    pub(crate) in_flight_htlcs: Vec<Htlc>,
+    /// Number of payments whose HTLCs may be in flight at the same time
+    pub(crate) concurrency: usize,
+    /// HTLCs of sent payments that have not settled yet, oldest first
+    pub(crate) unsettled_htlcs: VecDeque<(PaymentId, Vec<Htlc>)>,
+    /// Balances (node, channel_id, balance) of the graph's edges the simulation started with
+    initial_balances: Vec<(ID, String, usize)>,
 }
 
 impl Simulation {
@@ -55,14 +121,14 @@ impl Simulation {
         adversary_selection: &[AdversarySelection],
     ) -> Self {
         info!("Initialising simulation...");
-        let mut rng = crate::RNG.lock().unwrap();
-        *rng = SeedableRng::seed_from_u64(run);
         let event_queue = EventQueue::new();
         let outstanding_invoices: BTreeMap<String, HashMap<usize, Invoice>> = BTreeMap::new();
         let successful_payments = Vec::new();
+        let initial_balances = graph.get_edge_balances();
         Self {
             graph,
             amount,
+            rng: SeedableRng::seed_from_u64(run),
             run,
             routing_metric,
             payment_parts,
@@ -77,12 +143,121 @@ impl Simulation {
             number_of_adversaries,
             adversaries: vec![],
             node_hits: HashMap::default(),
+            channel_usage: HashMap::default(),
             path_distances: PathDistances(vec![]),
             adversary_selection: adversary_selection.to_owned(),
             path_diversity: PathDiversity(vec![]),
+            diversity_lambdas: crate::DIVERSITY_LAMBDAS.to_vec(),
+            failure_stats: FailureStats::default(),
+            balance_delta_log: false,
+            payment_delay_secs: crate::SIM_DELAY_IN_SECS,
+            hop_delay_secs: crate::HOP_DELAY_IN_SECS,
+            max_retries: usize::MAX,
+            max_fee_msat: None,
+            max_total_cltv: crate::MAX_TOTAL_CLTV,
+            candidate_pool: 1,
+            risk_factor: crate::RISK_FACTOR,
+            random_failure_prob: 0.0,
+            enforce_invoice_amount: false,
+            check_receive_capacity: true,
+            learn_channel_priors: false,
+            channel_priors: HashMap::new(),
+            check_invariants: true,
+            dry_run: false,
+            in_flight_htlcs: Vec::new(),
+            concurrency: 1,
+            unsettled_htlcs: VecDeque::new(),
+            initial_balances,
         }
     }
 
+    /// Sets the probability with which a hop fails a HTLC regardless of its liquidity.
+    pub fn set_random_failure_prob(&mut self, random_failure_prob: f32) {
+        self.random_failure_prob = random_failure_prob;
+    }
+
+    /// Caps the number of alternate routes that are tried per payment (or MPP shard).
+    pub fn set_max_retries(&mut self, max_retries: usize) {
+        self.max_retries = max_retries;
+    }
+
+    /// Caps the fees each payment is willing to pay.
+    pub fn set_max_fee_msat(&mut self, max_fee_msat: usize) {
+        self.max_fee_msat = Some(max_fee_msat);
+    }
+
+    /// Caps the total timelock of the routes payments and adversaries consider.
+    pub fn set_max_total_cltv(&mut self, max_total_cltv: usize) {
+        self.max_total_cltv = max_total_cltv;
+    }
+
+    /// Weighs the timelock penalty of the fee metric by `risk_factor`, see
+    /// [`PathFinder::set_risk_factor`](crate::PathFinder::set_risk_factor).
+    pub fn set_risk_factor(&mut self, risk_factor: usize) {
+        self.risk_factor = risk_factor;
+    }
+
+    /// Disables the receiver-side capacity check, e.g. to model receivers that rebalance.
+    pub fn set_check_receive_capacity(&mut self, check_receive_capacity: bool) {
+        self.check_receive_capacity = check_receive_capacity;
+    }
+
+    /// Remembers which channels failed to forward HTLCs and lowers their success probability in
+    /// later payments, similar to LND's mission control. The learned probabilities recover with
+    /// every attempt.
+    pub fn set_learn_channel_priors(&mut self, learn_channel_priors: bool) {
+        self.learn_channel_priors = learn_channel_priors;
+    }
+
+    /// Disables the consistency checks at the end of a run, e.g. when fuzzing.
+    pub fn set_check_invariants(&mut self, check_invariants: bool) {
+        self.check_invariants = check_invariants;
+    }
+
+    /// Lets single-path payments pick the first of the `candidate_pool` shortest paths that has
+    /// enough liquidity instead of always attempting the shortest one.
+    pub fn set_candidate_pool(&mut self, candidate_pool: usize) {
+        self.candidate_pool = candidate_pool;
+    }
+
+    /// Makes destinations reject payments that deliver less than the invoice amount.
+    pub fn set_enforce_invoice_amount(&mut self, enforce_invoice_amount: bool) {
+        self.enforce_invoice_amount = enforce_invoice_amount;
+    }
+
+    /// Only studies routability: payments succeed as soon as a route is found and no balance is
+    /// ever changed.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Lets up to `concurrency` payments be in flight at once, i.e. a payment's HTLCs lock
+    /// liquidity until it settles and later payments have to route around it.
+    /// Only has an effect if payments are scheduled closer together than they take to settle.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    /// Sets the simulated time between two consecutive payments.
+    pub fn set_payment_delay_secs(&mut self, payment_delay_secs: f32) {
+        self.payment_delay_secs = payment_delay_secs;
+    }
+
+    /// Sets the simulated time a single hop adds to the settlement of a payment.
+    pub fn set_hop_delay_secs(&mut self, hop_delay_secs: f32) {
+        self.hop_delay_secs = hop_delay_secs;
+    }
+
+    /// Sets the lambdas the effective path diversity is computed for.
+    pub fn set_diversity_lambdas(&mut self, diversity_lambdas: &[f32]) {
+        self.diversity_lambdas = diversity_lambdas.to_vec();
+    }
+
+    /// Enables recording of the per-channel balance changes caused by each payment.
+    pub fn set_balance_delta_log(&mut self, balance_delta_log: bool) {
+        self.balance_delta_log = balance_delta_log;
+    }
+
     pub fn new_batch_simulator(
         run: u64,
         graph: Graph,
@@ -91,12 +266,7 @@ impl Simulation {
         number_of_adversaries: Option<Vec<usize>>,
         adversary_selection: &[AdversarySelection],
     ) -> Self {
-        let (routing_metric, payment_parts) = match weight_parts {
-            WeightPartsCombi::MinFeeSingle => (RoutingMetric::MinFee, PaymentParts::Single),
-            WeightPartsCombi::MinFeeMulti => (RoutingMetric::MinFee, PaymentParts::Split),
-            WeightPartsCombi::MaxProbSingle => (RoutingMetric::MaxProb, PaymentParts::Single),
-            WeightPartsCombi::MaxProbMulti => (RoutingMetric::MaxProb, PaymentParts::Split),
-        };
+        let (routing_metric, payment_parts) = Self::metric_and_parts(weight_parts);
         Self::new(
             run,
             graph,
@@ -108,29 +278,224 @@ impl Simulation {
         )
     }
 
+    fn metric_and_parts(weight_parts: WeightPartsCombi) -> (RoutingMetric, PaymentParts) {
+        match weight_parts {
+            WeightPartsCombi::MinFeeSingle => (RoutingMetric::MinFee, PaymentParts::Single),
+            WeightPartsCombi::MinFeeMulti => (RoutingMetric::MinFee, PaymentParts::Split),
+            WeightPartsCombi::MaxProbSingle => (RoutingMetric::MaxProb, PaymentParts::Single),
+            WeightPartsCombi::MaxProbMulti => (RoutingMetric::MaxProb, PaymentParts::Split),
+            WeightPartsCombi::MinFeeRedundant => (
+                RoutingMetric::MinFee,
+                PaymentParts::Redundant {
+                    overhead: crate::REDUNDANCY_OVERHEAD,
+                },
+            ),
+            WeightPartsCombi::MaxProbRedundant => (
+                RoutingMetric::MaxProb,
+                PaymentParts::Redundant {
+                    overhead: crate::REDUNDANCY_OVERHEAD,
+                },
+            ),
+            WeightPartsCombi::MinFeeSingleThenSplit => {
+                (RoutingMetric::MinFee, PaymentParts::SingleThenSplit)
+            }
+            WeightPartsCombi::MaxProbSingleThenSplit => {
+                (RoutingMetric::MaxProb, PaymentParts::SingleThenSplit)
+            }
+        }
+    }
+
+    /// Builds a simulation from in-memory data only, i.e. without touching the filesystem, so that
+    /// it can run in the browser. Adversaries are drawn at random and from the betweenness and
+    /// degree rankings, which list node IDs in descending order of their scores.
+    pub fn new_from_parts(
+        run: u64,
+        graph: Graph,
+        amount: usize,
+        weight_parts: WeightPartsCombi,
+        number_of_adversaries: Option<Vec<usize>>,
+        betweenness_ranking: Vec<ID>,
+        degree_ranking: Vec<ID>,
+    ) -> Self {
+        let adversary_selection = [
+            AdversarySelection::Random,
+            AdversarySelection::HighBetweennessWeb(betweenness_ranking),
+            AdversarySelection::HighDegreeWeb(degree_ranking),
+        ];
+        Self::new_batch_simulator(
+            run,
+            graph,
+            amount,
+            weight_parts,
+            number_of_adversaries,
+            &adversary_selection,
+        )
+    }
+
+    /// Prepares the simulation for another run with the given seed, i.e. restores the balances it
+    /// was created with and discards the payments and statistics of earlier runs.
+    /// The configuration is kept.
+    pub fn reset(&mut self, new_seed: u64) {
+        info!("Resetting simulation with seed {}.", new_seed);
+        self.rng = SeedableRng::seed_from_u64(new_seed);
+        self.run = new_seed;
+        self.graph.restore_edge_balances(&self.initial_balances);
+        self.event_queue = EventQueue::new();
+        self.current_payment_id = 0;
+        self.outstanding_invoices.clear();
+        self.total_num_payments = 0;
+        self.num_successful = 0;
+        self.successful_payments.clear();
+        self.num_failed = 0;
+        self.failed_payments.clear();
+        self.adversaries.clear();
+        self.node_hits.clear();
+        self.channel_usage.clear();
+        self.path_distances = PathDistances(vec![]);
+        self.path_diversity = PathDiversity(vec![]);
+        self.failure_stats = FailureStats::default();
+        self.in_flight_htlcs.clear();
+        self.unsettled_htlcs.clear();
+        self.channel_priors.clear();
+    }
+
     pub fn run(
         &mut self,
         payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
         min_shard_amt: Option<usize>,
         run_all_adversary_scenarios: bool,
+    ) -> SimResult {
+        self.run_with_progress(
+            payment_pairs,
+            min_shard_amt,
+            run_all_adversary_scenarios,
+            None,
+        )
+    }
+
+    /// Same as [`Simulation::run`] but returns an error instead of only logging it if the
+    /// simulation ends up in an inconsistent state.
+    pub fn try_run(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        min_shard_amt: Option<usize>,
+        run_all_adversary_scenarios: bool,
+    ) -> Result<SimResult, Box<dyn Error>> {
+        let result = self.run(payment_pairs, min_shard_amt, run_all_adversary_scenarios);
+        self.verify_invariants()?;
+        Ok(result)
+    }
+
+    /// Checks that every scheduled payment either succeeded or failed. Always passes if the
+    /// checks are disabled.
+    fn verify_invariants(&self) -> Result<(), Box<dyn Error>> {
+        if self.check_invariants && self.num_successful + self.num_failed != self.total_num_payments
+        {
+            return Err(format!(
+                "Expected {} payments to complete but {} succeeded and {} failed.",
+                self.total_num_payments, self.num_successful, self.num_failed
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Same as [`Simulation::run`] but reports the number of completed payments and the total
+    /// number of payments to `progress` every few payments and once all have completed.
+    pub fn run_with_progress(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        min_shard_amt: Option<usize>,
+        run_all_adversary_scenarios: bool,
+        progress: Option<&dyn Fn(usize, usize)>,
+    ) -> SimResult {
+        let amount = self.amount;
+        self.run_payments(
+            payment_pairs.map(|(src, dest)| (src, dest, amount)),
+            min_shard_amt,
+            run_all_adversary_scenarios,
+            progress,
+            None,
+            0,
+        )
+    }
+
+    /// Same as [`Simulation::run`] but each payment's amount is drawn from `amounts` using the
+    /// simulation's seeded RNG instead of using the fixed simulation amount. The result's amount
+    /// is the mean of the drawn amounts.
+    /// Fails if the amounts are read from a file that cannot be read or contains invalid amounts.
+    pub fn run_with_amounts(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        amounts: &AmountDistribution,
+    ) -> Result<SimResult, std::io::Error> {
+        let sampler = AmountSampler::new(amounts)?;
+        let payments: Vec<(ID, ID, usize)> = payment_pairs
+            .map(|(src, dest)| {
+                let amount = sampler.sample(&mut self.rng);
+                (src, dest, amount)
+            })
+            .collect();
+        let mean_amount = match payments.len() {
+            0 => self.amount,
+            num_payments => {
+                payments.iter().map(|(_, _, amount)| amount).sum::<usize>() / num_payments
+            }
+        };
+        let mut result = self.run_payments(payments.into_iter(), None, false, None, None, 0);
+        result.amount = mean_amount;
+        Ok(result)
+    }
+
+    /// Same as [`Simulation::run`] but passes each payment to `sink` as soon as it has
+    /// completed, i.e. in the order the payments finish.
+    pub fn run_streaming(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        mut sink: impl FnMut(PaymentInfo),
+    ) -> SimResult {
+        let amount = self.amount;
+        self.run_payments(
+            payment_pairs.map(|(src, dest)| (src, dest, amount)),
+            None,
+            false,
+            None,
+            Some(&mut sink),
+            0,
+        )
+    }
+
+    /// Schedules and runs one payment per `(src, dest, amount)` triple. `completed_payments` are
+    /// the payments a resumed simulation has already run.
+    pub(super) fn run_payments(
+        &mut self,
+        payments: impl Iterator<Item = (ID, ID, usize)>,
+        min_shard_amt: Option<usize>,
+        run_all_adversary_scenarios: bool,
+        progress: Option<&dyn Fn(usize, usize)>,
+        mut sink: Option<&mut dyn FnMut(PaymentInfo)>,
+        completed_payments: usize,
     ) -> SimResult {
         info!(
             "# Payment pairs = {}, Pathfinding weight = {:?}, Single/MMP payments: {:?}",
-            payment_pairs.size_hint().0,
+            payments.size_hint().0,
             self.routing_metric,
             self.payment_parts
         );
         let mut now = Time::from_secs(0.0); // start simulation at (0)
-        for (src, dest) in payment_pairs {
+        let mut num_payments = 0;
+        for (src, dest, amount) in payments {
             let payment_id = self.next_payment_id();
-            let invoice = Invoice::new(payment_id, self.amount, &src, &dest);
+            let invoice = Invoice::new(payment_id, amount, &src, &dest);
             self.add_invoice(invoice);
-            let payment = Payment::new(payment_id, src, dest, self.amount, min_shard_amt);
+            let payment = Payment::new(payment_id, src, dest, amount, min_shard_amt);
             let event = PaymentEvent::Scheduled { payment };
             self.event_queue.schedule(now, event);
-            now += Time::from_secs(crate::SIM_DELAY_IN_SECS);
+            now += Time::from_secs(self.payment_delay_secs);
+            num_payments += 1;
         }
-        self.total_num_payments = self.event_queue.queue_length();
+        // several payments may share a tick so we cannot rely on the queue's length
+        self.total_num_payments = completed_payments + num_payments;
         debug!(
             "Queued {} events for simulation.",
             self.event_queue.queue_length()
@@ -146,29 +511,33 @@ impl Simulation {
                         payment.payment_id,
                         self.event_queue.now()
                     );
-                    let _ = match self.payment_parts {
-                        PaymentParts::Single => self.send_single_payment(&mut payment),
-                        PaymentParts::Split => self.send_mpp_payment(&mut payment),
-                    };
+                    self.send_payment(&mut payment);
                 }
                 PaymentEvent::UpdateFailed { payment } => {
                     self.num_failed += 1;
+                    if let Some(sink) = sink.as_mut() {
+                        sink(PaymentInfo::from_payment(&payment));
+                    }
                     self.failed_payments.push(payment.to_owned());
+                    self.report_progress(progress);
                 }
                 PaymentEvent::UpdateSuccesful { payment } => {
+                    self.settle_htlcs(payment.payment_id);
                     self.num_successful += 1;
+                    if let Some(sink) = sink.as_mut() {
+                        sink(PaymentInfo::from_payment(&payment));
+                    }
                     self.successful_payments.push(payment.to_owned());
+                    self.report_progress(progress);
                 }
             }
         }
-        assert_eq!(
-            self.num_successful + self.num_failed,
-            self.total_num_payments,
-            "Something went wrong. Expected a different number simulation events."
-        );
+        if let Err(e) = self.verify_invariants() {
+            error!("Something went wrong. {}", e);
+        }
         info!(
             "Completed simulation after {} simulation secs.",
-            now.as_secs(),
+            self.event_queue.now().as_secs(),
         );
         info!(
             "# Total payments = {}, # successful {}, # failed = {}.",
@@ -176,6 +545,7 @@ impl Simulation {
         );
         self.eval_adversaries(run_all_adversary_scenarios);
         self.eval_path_similarity();
+        self.eval_failures();
         SimResult {
             run: self.run,
             amount: self.amount,
@@ -187,27 +557,351 @@ impl Simulation {
             adversaries: self.adversaries.to_owned(),
             path_distances: self.path_distances.to_owned(),
             path_diversity: self.path_diversity.to_owned(),
+            failure_stats: self.failure_stats.to_owned(),
+            success_rate: self.success_rate(),
+            median_fee_msat: self.median_fee_msat(),
+            mean_path_length: self.mean_path_length(),
+            node_hits: self.sorted_node_hits(),
+            channel_usage: self.top_channel_usage(crate::TOP_CHANNELS),
+            initial_balances: self.initial_balances.clone(),
+            final_balances: self.balance_snapshot(),
         }
     }
 
+    /// Simulates the pairs once per scenario of [`WeightPartsCombi`].
+    /// Every combination starts from the balances the simulation was created with. The routing
+    /// metric and payment parts are restored afterwards.
+    pub fn run_all_combis(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+    ) -> Vec<(WeightPartsCombi, SimResult)> {
+        let (routing_metric, payment_parts) = (self.routing_metric, self.payment_parts);
+        let results = WeightPartsCombi::variants()
+            .iter()
+            .map(|&combi| {
+                info!("Simulating {:?}.", combi);
+                self.reset(self.run);
+                (self.routing_metric, self.payment_parts) = Self::metric_and_parts(combi);
+                (combi, self.run(payment_pairs.clone(), None, false))
+            })
+            .collect();
+        self.routing_metric = routing_metric;
+        self.payment_parts = payment_parts;
+        results
+    }
+
+    /// Simulates the same pairs for each of the amounts (in msat) and returns one result per
+    /// amount. Every run is reset with the simulation's seed first, i.e. starts with the balances
+    /// the simulation was created with.
+    /// Targeted attacks are not simulated.
+    pub fn run_amount_sweep(
+        &mut self,
+        payment_pairs: impl Iterator<Item = (ID, ID)> + Clone,
+        amounts: &[usize],
+    ) -> Vec<SimResult> {
+        let amount = self.amount;
+        let results = amounts
+            .iter()
+            .map(|&sweep_amount| {
+                info!("Starting sweep run with amount {} msat.", sweep_amount);
+                self.reset(self.run);
+                self.amount = sweep_amount;
+                self.run(payment_pairs.clone(), None, false)
+            })
+            .collect();
+        self.amount = amount;
+        results
+    }
+
+    /// Sends the payment as single-path or MPP payment depending on the simulation's parts mode.
+    /// Triggers an event either way
+    pub(crate) fn send_payment(&mut self, payment: &mut Payment) -> bool {
+        match self.payment_parts {
+            PaymentParts::Single | PaymentParts::SingleThenSplit => {
+                self.send_single_payment(payment)
+            }
+            PaymentParts::Split | PaymentParts::Redundant { .. } => {
+                self.send_mpp_payment(payment, self.payment_parts)
+            }
+        }
+    }
+
+    fn report_progress(&self, progress: Option<&dyn Fn(usize, usize)>) {
+        if let Some(progress) = progress {
+            let processed = self.num_successful + self.num_failed;
+            if processed.is_multiple_of(crate::PROGRESS_INTERVAL)
+                || processed == self.total_num_payments
+            {
+                progress(processed, self.total_num_payments);
+            }
+        }
+    }
+
+    /// Node hits in descending order of the number of hits; ties are ordered by ID
+    fn sorted_node_hits(&self) -> Vec<(ID, usize)> {
+        let mut node_hits: Vec<(ID, usize)> = self
+            .node_hits
+            .iter()
+            .map(|(id, hits)| (id.clone(), *hits))
+            .collect();
+        node_hits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        node_hits
+    }
+
+    /// The `n` channels HTLCs were forwarded through most often, most used first
+    fn top_channel_usage(&self, n: usize) -> Vec<(String, usize)> {
+        let mut channel_usage: Vec<(String, usize)> = self
+            .channel_usage
+            .iter()
+            .map(|(channel_id, usage)| (channel_id.clone(), *usage))
+            .collect();
+        channel_usage.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        channel_usage.truncate(n);
+        channel_usage
+    }
+
+    fn success_rate(&self) -> f32 {
+        if self.total_num_payments == 0 {
+            0.0
+        } else {
+            self.num_successful as f32 / self.total_num_payments as f32
+        }
+    }
+
+    /// Median of the fees (summed over all shards) paid by successful payments.
+    fn median_fee_msat(&self) -> usize {
+        let mut fees: Vec<usize> = self
+            .successful_payments
+            .iter()
+            .map(|p| p.used_paths.iter().map(|c| c.path_fees()).sum())
+            .collect();
+        if fees.is_empty() {
+            return 0;
+        }
+        fees.sort_unstable();
+        let mid = fees.len() / 2;
+        if fees.len().is_multiple_of(2) {
+            (fees[mid - 1] + fees[mid]) / 2
+        } else {
+            fees[mid]
+        }
+    }
+
+    /// Mean path length over all paths (i.e. shards) of successful payments.
+    fn mean_path_length(&self) -> f32 {
+        let lengths: Vec<usize> = self
+            .successful_payments
+            .iter()
+            .flat_map(|p| p.used_paths.iter())
+            .filter(|c| !c.path.hops.is_empty())
+            .map(|c| c.path.path_length())
+            .collect();
+        if lengths.is_empty() {
+            0.0
+        } else {
+            lengths.iter().sum::<usize>() as f32 / lengths.len() as f32
+        }
+    }
+
+    /// Delay until a payment's outcome is known. Successful payments are settled once the
+    /// longest of their paths has been traversed.
+    pub(crate) fn settlement_delay(&self, payment: &Payment) -> Time {
+        let num_hops = if payment.succeeded {
+            payment
+                .used_paths
+                .iter()
+                .filter(|c| !c.path.hops.is_empty())
+                .map(|c| c.path.path_length())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        Time::from_secs(self.hop_delay_secs * num_hops as f32)
+    }
+
+    /// Balance of every edge as (node, channel_id, balance) sorted by node.
+    pub fn balance_snapshot(&self) -> Vec<(ID, String, usize)> {
+        self.graph.get_edge_balances()
+    }
+
+    /// Snapshot of the edge balances if balance deltas are being logged.
+    pub(crate) fn balance_delta_snapshot(&self) -> Option<Vec<(ID, String, usize)>> {
+        if self.balance_delta_log {
+            Some(self.graph.get_edge_balances())
+        } else {
+            None
+        }
+    }
+
+    /// Net change of each edge's balance compared to the snapshot as (node, channel_id, delta).
+    /// Edges whose balance did not change are omitted.
+    pub(crate) fn balance_deltas_since(
+        &self,
+        snapshot: &[(ID, String, usize)],
+    ) -> Vec<(ID, String, i64)> {
+        let before: HashMap<(&ID, &String), usize> = snapshot
+            .iter()
+            .map(|(node, channel_id, balance)| ((node, channel_id), *balance))
+            .collect();
+        self.graph
+            .get_edge_balances()
+            .into_iter()
+            .filter_map(|(node, channel_id, balance)| {
+                let before = before.get(&(&node, &channel_id)).copied().unwrap_or(0);
+                let delta = balance as i64 - before as i64;
+                (delta != 0).then_some((node, channel_id, delta))
+            })
+            .collect()
+    }
+
     pub fn draw_n_pairs_for_simulation(
         graph: &Graph,
         n: usize,
-    ) -> (impl Iterator<Item = (ID, ID)> + Clone) {
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = (ID, ID)> + Clone {
         info!("Drawing {} sender-receiver pairs for simulation.", n,);
         let g = graph.clone();
-        g.get_random_pairs_of_nodes(n)
+        g.get_random_pairs_of_nodes(n, false, rng)
+    }
+
+    /// Same as [`Simulation::draw_n_pairs_for_simulation`] but no pair is drawn twice. Fewer than
+    /// `n` pairs are returned if the graph does not have as many.
+    pub fn draw_n_unique_pairs_for_simulation(
+        graph: &Graph,
+        n: usize,
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = (ID, ID)> + Clone {
+        info!("Drawing {} unique sender-receiver pairs for simulation.", n);
+        graph.get_random_pairs_of_nodes(n, true, rng)
+    }
+
+    /// Same as [`Simulation::draw_n_pairs_for_simulation`] but destinations are biased towards
+    /// nodes with high inbound capacity.
+    pub fn draw_n_pairs_with_sink_bias(
+        graph: &Graph,
+        n: usize,
+        sink_bias: f32,
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = (ID, ID)> + Clone {
+        info!(
+            "Drawing {} sender-receiver pairs for simulation with sink bias {}.",
+            n, sink_bias
+        );
+        graph.get_pairs_with_sink_bias(n, sink_bias, rng)
+    }
+
+    /// Same as [`Simulation::draw_n_pairs_for_simulation`] but each pair comes with its own
+    /// amount, i.e. `fraction` (clamped to [0, 1]) of the most the source can send via a single
+    /// channel.
+    pub fn draw_pairs_with_relative_amount(
+        graph: &Graph,
+        n: usize,
+        fraction: f32,
+        rng: &mut impl Rng,
+    ) -> Vec<(ID, ID, usize)> {
+        info!(
+            "Drawing {} sender-receiver pairs for simulation with amounts of {} of the senders' balance.",
+            n, fraction
+        );
+        let fraction = fraction.clamp(0.0, 1.0);
+        graph
+            .get_random_pairs_of_nodes(n, false, rng)
+            .map(|(src, dest)| {
+                let amount = (graph.get_max_node_balance(&src) as f32 * fraction) as usize;
+                (src, dest, amount)
+            })
+            .collect()
     }
 
-    pub fn draw_adversaries(nodes: &[ID], num_adv: usize) -> (impl Iterator<Item = ID> + Clone) {
-        let mut rng = crate::RNG.lock().unwrap();
+    /// Reads fixed sender-receiver pairs from a file with one whitespace- or comma-separated
+    /// `src dest` pair per line. Pairs containing nodes that are not in the graph are skipped.
+    pub fn read_pairs_from_file(
+        graph: &Graph,
+        path: &Path,
+    ) -> Result<Vec<(ID, ID)>, std::io::Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let nodes: HashSet<ID> = graph.get_node_ids().into_iter().collect();
+        let mut pairs = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let ids: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|id| !id.is_empty())
+                .collect();
+            if ids.is_empty() {
+                continue;
+            }
+            if let [src, dest] = ids[..] {
+                let (src, dest) = (src.to_owned(), dest.to_owned());
+                if nodes.contains(&src) && nodes.contains(&dest) {
+                    pairs.push((src, dest));
+                    continue;
+                }
+            }
+            warn!("Skipping invalid pair {}.", line);
+        }
+        info!(
+            "Read {} sender-receiver pairs from {}.",
+            pairs.len(),
+            path.display()
+        );
+        Ok(pairs)
+    }
+
+    pub fn draw_adversaries(
+        nodes: &[ID],
+        num_adv: usize,
+        rng: &mut impl Rng,
+    ) -> impl Iterator<Item = ID> + Clone {
         nodes
             .iter()
             .cloned()
-            .choose_multiple(&mut *rng, num_adv)
+            .choose_multiple(rng, num_adv)
             .into_iter()
     }
 
+    /// Same as [`Simulation::draw_adversaries`] but with a dedicated RNG seeded with `seed` so
+    /// that the adversaries only depend on the seed and the nodes, not on what else has drawn
+    /// random numbers before.
+    pub fn draw_adversaries_seeded(nodes: &[ID], num_adv: usize, seed: u64) -> Vec<ID> {
+        // sort for reproducability
+        let mut nodes = nodes.to_vec();
+        nodes.sort();
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self::draw_adversaries(&nodes, num_adv, &mut rng).collect()
+    }
+
+    /// Draws `num_adv` distinct nodes where each node's chance of being drawn is proportional to
+    /// its total channel capacity. Nodes without capacity are only drawn if there are not enough
+    /// other nodes.
+    pub fn draw_capacity_weighted_adversaries(
+        graph: &Graph,
+        num_adv: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<ID> {
+        // sort for reproducability
+        let mut nodes: Vec<(ID, usize)> = graph
+            .get_node_ids()
+            .into_iter()
+            .map(|n| {
+                let capacity = graph.get_total_node_capacity(&n);
+                (n, capacity)
+            })
+            .collect();
+        nodes.sort();
+        match nodes.choose_multiple_weighted(rng, num_adv, |(_, capacity)| *capacity as f64) {
+            Ok(adversaries) => adversaries.map(|(id, _)| id.clone()).collect(),
+            Err(e) => {
+                warn!("Falling back to uniform adversaries: {}.", e);
+                nodes
+                    .choose_multiple(rng, num_adv)
+                    .map(|(id, _)| id.clone())
+                    .collect()
+            }
+        }
+    }
+
     pub(crate) fn add_invoice(&mut self, invoice: Invoice) {
         // Has this node already issued invoices?
         match self.outstanding_invoices.get_mut(&invoice.destination) {
@@ -231,7 +925,13 @@ impl Simulation {
         }
     }
 
-    #[allow(unused)]
+    /// Amount of the invoice `node` issued for the payment
+    pub(crate) fn get_invoice_amount(&self, node: &ID, payment_id: usize) -> Option<usize> {
+        self.get_invoices_for_node(node)
+            .and_then(|invoices| invoices.get(&payment_id))
+            .map(|invoice| invoice.amount)
+    }
+
     pub(crate) fn remove_invoice(&mut self, invoice: &Invoice) {
         let id = invoice.id;
         match self.outstanding_invoices.get_mut(&invoice.destination) {
@@ -243,6 +943,17 @@ impl Simulation {
         };
     }
 
+    /// Removes the invoice a delivered payment has fulfilled
+    pub(crate) fn fulfil_invoice(&mut self, payment: &Payment) {
+        let invoice = self
+            .get_invoices_for_node(&payment.dest)
+            .and_then(|invoices| invoices.get(&payment.payment_id))
+            .cloned();
+        if let Some(invoice) = invoice {
+            self.remove_invoice(&invoice);
+        }
+    }
+
     pub(crate) fn next_payment_id(&mut self) -> usize {
         let current_id = self.current_payment_id;
         self.current_payment_id += 1;
@@ -250,11 +961,152 @@ impl Simulation {
     }
 }
 
+/// Draws payment amounts from an [`AmountDistribution`], reading file-based amounts only once.
+enum AmountSampler {
+    Fixed(usize),
+    Uniform { min: usize, max: usize },
+    LogNormal { mu: f64, sigma: f64 },
+    Empirical(Vec<usize>),
+}
+
+impl AmountSampler {
+    fn new(distribution: &AmountDistribution) -> Result<Self, std::io::Error> {
+        let sampler = match distribution {
+            AmountDistribution::Fixed(amount) => Self::Fixed(*amount),
+            AmountDistribution::Uniform { min, max } => Self::Uniform {
+                min: *min.min(max),
+                max: *min.max(max),
+            },
+            AmountDistribution::LogNormal { mu, sigma } => Self::LogNormal {
+                mu: *mu,
+                sigma: *sigma,
+            },
+            AmountDistribution::FromFile(path) => {
+                let amounts = Self::read_amounts_from_file(path)?;
+                if amounts.is_empty() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("No amounts in {}.", path.display()),
+                    ));
+                }
+                Self::Empirical(amounts)
+            }
+        };
+        Ok(sampler)
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> usize {
+        match self {
+            Self::Fixed(amount) => *amount,
+            Self::Uniform { min, max } => rng.gen_range(*min..=*max),
+            Self::LogNormal { mu, sigma } => {
+                // Box-Muller transform; 1 - u keeps the logarithm's argument in (0, 1]
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                (mu + sigma * z).exp().round() as usize
+            }
+            Self::Empirical(amounts) => *amounts.choose(rng).expect("Amounts are not empty."),
+        }
+    }
+
+    /// Reads one amount per line, skipping empty lines. Fails at the first line that is not an
+    /// amount.
+    fn read_amounts_from_file(path: &Path) -> Result<Vec<usize>, std::io::Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut amounts = vec![];
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let amount = line.parse().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid amount {} in line {} of {}.",
+                        line,
+                        idx + 1,
+                        path.display()
+                    ),
+                )
+            })?;
+            amounts.push(amount);
+        }
+        info!("Read {} amounts from {}.", amounts.len(), path.display());
+        Ok(amounts)
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use super::*;
-    use std::path::Path;
+    use crate::payment::FailureReason;
+    use std::io::Write;
+
+    /// The result without the payments' pathfinding times as wall-clock time differs between runs
+    pub(crate) fn without_timing(mut result: SimResult) -> SimResult {
+        for payment in result
+            .successful_payments
+            .iter_mut()
+            .chain(result.failed_payments.iter_mut())
+        {
+            payment.pathfinding_ms = 0;
+        }
+        result
+    }
+
+    #[test]
+    fn read_pairs() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "alice bob").unwrap();
+        writeln!(file, "chan,dina").unwrap();
+        writeln!(file, "alice eve").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "bob").unwrap();
+        writeln!(file, "dina ,  alice").unwrap();
+        let actual = Simulation::read_pairs_from_file(&graph, file.path()).unwrap();
+        let expected = vec![
+            ("alice".to_owned(), "bob".to_owned()),
+            ("chan".to_owned(), "dina".to_owned()),
+            ("dina".to_owned(), "alice".to_owned()),
+        ];
+        assert_eq!(actual, expected);
+        let missing = Path::new("../test_data/no_such_pairs.txt");
+        assert!(Simulation::read_pairs_from_file(&graph, missing).is_err());
+    }
+
+    #[test]
+    fn read_pairs_fails_on_unreadable_line() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "alice bob").unwrap();
+        // not valid UTF-8 so reading the line fails
+        file.write_all(&[0xff, 0xfe, b'\n']).unwrap();
+        writeln!(file, "chan dina").unwrap();
+        // the pairs are not truncated at the failing line
+        let error = Simulation::read_pairs_from_file(&graph, file.path()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
 
     #[test]
     fn init_simulator() {
@@ -263,7 +1115,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -290,14 +1142,15 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
         let n = 2;
-        let actual = Simulation::draw_n_pairs_for_simulation(&graph, n);
+        let actual =
+            Simulation::draw_n_pairs_for_simulation(&graph, n, &mut StdRng::seed_from_u64(0));
         assert_eq!(actual.size_hint(), (n, Some(n)));
     }
 
@@ -306,7 +1159,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial_connected.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -314,10 +1167,11 @@ mod tests {
         );
         let nodes = &graph.get_node_ids();
         let amount = nodes.len();
-        let actual = Simulation::draw_adversaries(nodes, amount);
+        let rng = &mut StdRng::seed_from_u64(0);
+        let actual = Simulation::draw_adversaries(nodes, amount, rng);
         assert_eq!(actual.size_hint(), (nodes.len(), Some(nodes.len())));
         let amount = 2;
-        let actual = Simulation::draw_adversaries(nodes, amount);
+        let actual = Simulation::draw_adversaries(nodes, amount, rng);
         assert_eq!(actual.size_hint(), (2, Some(2)));
     }
 
@@ -328,7 +1182,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -361,11 +1215,7 @@ mod tests {
         );
         simulator.add_invoice(invoice2.clone());
         assert_eq!(simulator.outstanding_invoices.len(), 1);
-        let actual = simulator
-            .outstanding_invoices
-            .get(&"dina".to_owned())
-            .unwrap()
-            .clone();
+        let actual = simulator.outstanding_invoices.get("dina").unwrap().clone();
         let expected = HashMap::from([(invoice.id, invoice), (invoice2.id, invoice2)]);
         assert_eq!(actual, expected);
     }
@@ -377,7 +1227,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -423,7 +1273,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/trivial.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -458,7 +1308,7 @@ mod tests {
         let path_to_file = Path::new("../test_data/lnbook_example.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &path_to_file,
+                path_to_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -485,8 +1335,12 @@ mod tests {
             Some(number_of_adversaries),
             &adversary_selection,
         );
-        simulator.run(pairs.clone().into_iter(), None, true);
+        let result = simulator.run(pairs.clone().into_iter(), None, true);
         assert_eq!(simulator.num_successful + simulator.num_failed, pairs.len());
+        assert_eq!(
+            result.success_rate,
+            result.num_succesful as f32 / result.total_num as f32
+        );
         let mut expected_hits: HashMap<String, usize> = HashMap::with_capacity(3);
         for payment in simulator.successful_payments {
             for paths in payment.used_paths {
@@ -499,5 +1353,503 @@ mod tests {
             }
         }
         assert_eq!(expected_hits, simulator.node_hits);
+        let (hub, hits) = &result.node_hits[0];
+        assert_eq!(simulator.node_hits.values().max(), Some(hits));
+        assert_eq!(simulator.node_hits.get(hub), Some(hits));
+        assert_eq!(result.node_hits.len(), simulator.node_hits.len());
+        assert!(result.node_hits.windows(2).all(|w| w[0].1 >= w[1].1));
+        // chan -> bob is part of every route to bob
+        assert!(simulator
+            .channel_usage
+            .get("chan1")
+            .is_some_and(|usage| *usage > 0));
+        assert!(result.channel_usage.len() <= crate::TOP_CHANNELS);
+        assert!(result.channel_usage.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(result
+            .channel_usage
+            .iter()
+            .all(|(channel_id, usage)| simulator.channel_usage[channel_id] == *usage));
+    }
+
+    #[test]
+    fn amount_sweep() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let pairs = vec![
+            ("alice".to_owned(), "dina".to_owned()),
+            ("chan".to_owned(), "bob".to_owned()),
+        ];
+        let simulation = |amount| {
+            Simulation::new(
+                3,
+                graph.clone(),
+                amount,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+                None,
+                &[],
+            )
+        };
+        let amounts = [1000, 5000];
+        let mut simulator = simulation(amounts[0]);
+        let results = simulator.run_amount_sweep(pairs.clone().into_iter(), &amounts);
+        assert_eq!(results.len(), amounts.len());
+        assert_eq!(simulator.amount, amounts[0]);
+        for (result, amount) in results.into_iter().zip(amounts) {
+            assert_eq!(result.amount, amount);
+            assert_eq!(result.total_num, pairs.len());
+            // each run starts with the original balances, i.e. as if it ran on its own
+            let expected = simulation(amount).run(pairs.clone().into_iter(), None, false);
+            assert_eq!(without_timing(result), without_timing(expected));
+        }
+    }
+
+    #[test]
+    fn fixed_amounts_match_plain_run() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let pairs = vec![
+            ("alice".to_owned(), "dina".to_owned()),
+            ("chan".to_owned(), "bob".to_owned()),
+        ];
+        let amount = 5000;
+        let simulation = || {
+            Simulation::new(
+                3,
+                graph.clone(),
+                amount,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+                None,
+                &[],
+            )
+        };
+        let actual = simulation()
+            .run_with_amounts(
+                pairs.clone().into_iter(),
+                &AmountDistribution::Fixed(amount),
+            )
+            .unwrap();
+        let expected = simulation().run(pairs.into_iter(), None, false);
+        assert_eq!(without_timing(actual), without_timing(expected));
+    }
+
+    #[test]
+    fn uniform_amounts_stay_within_bounds() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let pairs: Vec<(ID, ID)> = (0..20)
+            .map(|_| ("alice".to_owned(), "dina".to_owned()))
+            .collect();
+        let (min, max) = (1000, 3000);
+        let mut simulator = Simulation::new(
+            3,
+            graph,
+            min,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+            None,
+            &[],
+        );
+        let result = simulator
+            .run_with_amounts(pairs.into_iter(), &AmountDistribution::Uniform { min, max })
+            .unwrap();
+        let amounts: Vec<usize> = result
+            .successful_payments
+            .iter()
+            .chain(result.failed_payments.iter())
+            .map(|payment| payment.amount_msat)
+            .collect();
+        assert_eq!(amounts.len(), 20);
+        assert!(amounts.iter().all(|amount| (min..=max).contains(amount)));
+        assert_eq!(result.amount, amounts.iter().sum::<usize>() / amounts.len());
+        // the amounts are actually drawn rather than fixed
+        assert!(amounts.iter().any(|amount| *amount != amounts[0]));
+    }
+
+    #[test]
+    fn amounts_from_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "1000").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, " 2000 ").unwrap();
+        let distribution = AmountDistribution::FromFile(file.path().to_path_buf());
+        let pairs: Vec<(ID, ID)> = (0..10)
+            .map(|_| ("alice".to_owned(), "dina".to_owned()))
+            .collect();
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let result = simulator
+            .run_with_amounts(pairs.clone().into_iter(), &distribution)
+            .unwrap();
+        assert!(result
+            .successful_payments
+            .iter()
+            .chain(result.failed_payments.iter())
+            .all(|payment| [1000, 2000].contains(&payment.amount_msat)));
+        // the invalid line is reported instead of skipped
+        writeln!(file, "3000 msat").unwrap();
+        let actual = simulator.run_with_amounts(pairs.clone().into_iter(), &distribution);
+        let error = actual.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 4"));
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        let distribution = AmountDistribution::FromFile(empty.path().to_path_buf());
+        assert!(simulator
+            .run_with_amounts(pairs.into_iter(), &distribution)
+            .is_err());
+    }
+
+    #[test]
+    fn simulation_from_in_memory_parts() {
+        let json = include_str!("../../../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_str(json, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let mut simulator = Simulation::new_from_parts(
+            1,
+            graph,
+            1000,
+            WeightPartsCombi::MinFeeSingle,
+            Some(vec![1]),
+            vec!["bob".to_owned(), "chan".to_owned()],
+            vec!["dina".to_owned()],
+        );
+        let pairs = vec![
+            ("alice".to_owned(), "chan".to_owned()),
+            ("alice".to_owned(), "dina".to_owned()),
+        ];
+        let result = simulator.run(pairs.into_iter(), None, false);
+        assert_eq!(result.num_succesful, 2);
+        assert_eq!(result.adversaries.len(), 3);
+        let hits = |name: &str| {
+            result
+                .adversaries
+                .iter()
+                .find(|adv| adv.selection_strategy.to_string() == name)
+                .unwrap()
+                .statistics[0]
+                .hits
+        };
+        // bob forwards both payments while dina only receives one
+        assert_eq!(hits("High Betweenness"), 2);
+        assert_eq!(hits("High Degree"), 0);
+    }
+
+    #[test]
+    fn progress_is_reported() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let pairs = Simulation::draw_n_pairs_for_simulation(
+            &simulator.graph,
+            2 * crate::PROGRESS_INTERVAL + 1,
+            &mut simulator.rng,
+        );
+        let reports = std::cell::RefCell::new(vec![]);
+        let progress = |processed, total| reports.borrow_mut().push((processed, total));
+        let result = simulator.run_with_progress(pairs, None, false, Some(&progress));
+        let reports = reports.into_inner();
+        let total = result.total_num;
+        assert_eq!(total, simulator.total_num_payments);
+        assert_eq!(
+            reports,
+            vec![
+                (crate::PROGRESS_INTERVAL, total),
+                (2 * crate::PROGRESS_INTERVAL, total),
+                (total, total)
+            ]
+        );
+    }
+
+    #[test]
+    fn inconsistent_state_is_returned_as_error() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let pairs =
+            Simulation::draw_n_pairs_for_simulation(&simulator.graph, 4, &mut simulator.rng);
+        simulator.set_check_invariants(true);
+        // payments that completed without ever being scheduled
+        simulator.num_failed = 3;
+        assert!(simulator
+            .clone()
+            .try_run(pairs.clone(), None, false)
+            .is_err());
+        let result = simulator.clone().run(pairs.clone(), None, false);
+        assert_eq!(
+            result.total_num + 3,
+            result.num_succesful + result.num_failed
+        );
+        simulator.set_check_invariants(false);
+        assert!(simulator.try_run(pairs, None, false).is_ok());
+    }
+
+    #[test]
+    fn payments_are_streamed_as_they_complete() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let pairs =
+            Simulation::draw_n_pairs_for_simulation(&simulator.graph, 10, &mut simulator.rng);
+        let mut events = vec![];
+        let result = simulator.run_streaming(pairs, |info| events.push(info));
+        assert_eq!(events.len(), result.total_num);
+        assert_eq!(
+            events.iter().filter(|info| info.succeeded).count(),
+            result.num_succesful
+        );
+        // payments are dispatched one after the other and complete in the order they were scheduled
+        let ids: Vec<usize> = events.iter().map(|info| info.id).collect();
+        assert_eq!(ids, (0..result.total_num).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn reset_simulation_matches_fresh_one() {
+        let graph = crate::attempt::tests::init_sim(None, None).graph;
+        let simulation = || {
+            Simulation::new(
+                0,
+                graph.clone(),
+                1000,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+                None,
+                &[],
+            )
+        };
+        let pairs: Vec<(ID, ID)> = [("alice", "dina"), ("dina", "bob"), ("bob", "chan")]
+            .iter()
+            .map(|(src, dest)| (src.to_string(), dest.to_string()))
+            .collect();
+        let expected = simulation().run(pairs.clone().into_iter(), None, false);
+        let mut simulator = simulation();
+        let first = simulator.run(pairs.clone().into_iter().rev(), None, false);
+        assert_ne!(
+            simulator.graph.get_edge_balances(),
+            graph.get_edge_balances()
+        );
+        simulator.reset(0);
+        assert_eq!(simulator.current_payment_id, 0);
+        assert!(simulator.node_hits.is_empty());
+        assert_eq!(
+            simulator.graph.get_edge_balances(),
+            graph.get_edge_balances()
+        );
+        let actual = simulator.run(pairs.into_iter(), None, false);
+        assert_eq!(without_timing(actual), without_timing(expected));
+        assert_eq!(first.total_num, 3);
+        // the RNG starts over, too
+        simulator.reset(7);
+        let drawn: Vec<(ID, ID)> =
+            Simulation::draw_n_pairs_for_simulation(&graph, 10, &mut simulator.rng).collect();
+        let expected_drawn: Vec<(ID, ID)> =
+            Simulation::draw_n_pairs_for_simulation(&graph, 10, &mut StdRng::seed_from_u64(7))
+                .collect();
+        assert_eq!(drawn, expected_drawn);
+    }
+
+    #[test]
+    fn relative_amounts_are_within_capacity() {
+        let mut graph = crate::attempt::tests::init_sim(None, None).graph;
+        graph.update_channel_balance(&"chan".to_string(), &"chan2".to_string(), 10000);
+        let rng = &mut StdRng::seed_from_u64(0);
+        let pairs = Simulation::draw_pairs_with_relative_amount(&graph, 20, 0.5, rng);
+        assert_eq!(pairs.len(), 20);
+        for (src, _, amount) in pairs {
+            let max_balance = graph.get_max_node_balance(&src);
+            assert!(amount <= max_balance);
+            assert_eq!(amount, max_balance / 2);
+        }
+        let pairs = Simulation::draw_pairs_with_relative_amount(&graph, 20, 1.5, rng);
+        assert!(pairs
+            .iter()
+            .all(|(src, _, amount)| *amount == graph.get_max_node_balance(src)));
+    }
+
+    #[test]
+    fn concurrent_payments_contend_for_liquidity() {
+        let pairs = vec![("alice".to_owned(), "chan".to_owned()); 2];
+        let run = |concurrency| {
+            let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+            // bob can forward one payment at a time
+            simulator
+                .graph
+                .update_channel_balance(&"bob".to_string(), &"bob2".to_string(), 1500);
+            simulator.set_concurrency(concurrency);
+            // the second payment is sent before the first one settles
+            simulator.set_payment_delay_secs(0.0);
+            simulator.set_hop_delay_secs(10.0);
+            let result = simulator.run(pairs.clone().into_iter(), None, false);
+            assert!(simulator.unsettled_htlcs.is_empty());
+            result
+        };
+        let sequential = run(1);
+        assert_eq!(sequential.num_succesful, 2);
+        let concurrent = run(2);
+        assert_eq!(concurrent.num_succesful, 1);
+        assert_eq!(concurrent.num_failed, 1);
+        assert_eq!(
+            concurrent.failed_payments[0].failure_reason,
+            Some(FailureReason::NoRoute)
+        );
+    }
+
+    #[test]
+    fn longer_paths_advance_clock_further() {
+        let path_to_file = Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                path_to_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let elapsed_secs = |pairs: Vec<(ID, ID)>| {
+            let mut simulator = Simulation::new(
+                1,
+                graph.clone(),
+                1000,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+                Some(vec![0]),
+                &[AdversarySelection::Random],
+            );
+            simulator.set_payment_delay_secs(0.0);
+            simulator.set_hop_delay_secs(10.0);
+            let result = simulator.run(pairs.into_iter(), None, false);
+            assert_eq!(result.num_succesful, 1);
+            simulator.event_queue.now().as_secs()
+        };
+        // one hop vs. alice -> bob -> chan -> dina
+        let short = elapsed_secs(vec![("alice".to_owned(), "bob".to_owned())]);
+        let long = elapsed_secs(vec![("alice".to_owned(), "dina".to_owned())]);
+        assert!(long > short);
+    }
+
+    #[test]
+    fn balance_snapshots_before_and_after_run() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let initial = simulator.balance_snapshot();
+        let pairs = vec![("alice".to_string(), "chan".to_string())];
+        let result = simulator.run(pairs.into_iter(), None, false);
+        assert_eq!(result.num_succesful, 1);
+        assert_eq!(result.initial_balances, initial);
+        assert_eq!(result.final_balances, simulator.balance_snapshot());
+        let changed: Vec<(&str, &str)> = result
+            .initial_balances
+            .iter()
+            .zip(result.final_balances.iter())
+            .filter(|(before, after)| before != after)
+            .map(|((node, channel_id, _), _)| (node.as_str(), channel_id.as_str()))
+            .collect();
+        // alice -> bob -> chan
+        assert_eq!(
+            changed,
+            vec![("alice", "alice1"), ("bob", "bob2"), ("chan", "chan1")]
+        );
+    }
+
+    #[test]
+    fn balance_snapshot_of_lnd_channels() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph =
+            crate::attempt::tests::graph_of_channels(&[("alice", "bob", 0), ("bob", "carol", 0)]);
+        simulator
+            .graph
+            .update_channel_balance(&"bob".to_string(), &"alice-bob".to_string(), 0);
+        let expected = vec![
+            ("alice".to_string(), "alice-bob".to_string(), 10000),
+            ("bob".to_string(), "alice-bob".to_string(), 0),
+            ("bob".to_string(), "bob-carol".to_string(), 10000),
+            ("carol".to_string(), "bob-carol".to_string(), 10000),
+        ];
+        assert_eq!(simulator.balance_snapshot(), expected);
+    }
+
+    #[test]
+    fn run_all_combinations() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        let initial = simulator.balance_snapshot();
+        let pairs = vec![
+            ("alice".to_string(), "dina".to_string()),
+            ("dina".to_string(), "bob".to_string()),
+        ];
+        let results = simulator.run_all_combis(pairs.into_iter());
+        let scenarios: Vec<WeightPartsCombi> = results.iter().map(|(combi, _)| *combi).collect();
+        assert_eq!(
+            scenarios,
+            vec![
+                WeightPartsCombi::MinFeeSingle,
+                WeightPartsCombi::MaxProbSingle,
+                WeightPartsCombi::MinFeeMulti,
+                WeightPartsCombi::MaxProbMulti,
+                WeightPartsCombi::MinFeeRedundant,
+                WeightPartsCombi::MaxProbRedundant,
+                WeightPartsCombi::MinFeeSingleThenSplit,
+                WeightPartsCombi::MaxProbSingleThenSplit,
+            ]
+        );
+        for (_, result) in results.iter() {
+            assert_eq!(result.total_num, 2);
+            assert_eq!(result.initial_balances, initial);
+        }
+        assert_eq!(simulator.routing_metric, RoutingMetric::MinFee);
+        assert_eq!(simulator.payment_parts, PaymentParts::Single);
+    }
+
+    #[test]
+    fn parallel_simulations_are_reproducible() {
+        use rayon::prelude::*;
+        let graph = crate::attempt::tests::init_sim(None, None).graph;
+        let pairs: Vec<(ID, ID)> = [
+            ("alice", "dina"),
+            ("dina", "bob"),
+            ("bob", "chan"),
+            ("chan", "alice"),
+            ("alice", "chan"),
+        ]
+        .iter()
+        .map(|(src, dest)| (src.to_string(), dest.to_string()))
+        .collect();
+        let simulate = |seed: u64| {
+            let mut simulator = Simulation::new(
+                seed,
+                graph.clone(),
+                1000,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+                None,
+                &[],
+            );
+            // HTLCs failing at random depend on the simulation's RNG
+            simulator.set_random_failure_prob(0.3);
+            without_timing(simulator.run(pairs.clone().into_iter(), None, false))
+        };
+        let seeds: Vec<u64> = (0..8).collect();
+        let sequential: Vec<SimResult> = seeds.iter().map(|seed| simulate(*seed)).collect();
+        let parallel: Vec<SimResult> = seeds.par_iter().map(|seed| simulate(*seed)).collect();
+        // the seeds lead to different outcomes
+        assert!(sequential
+            .windows(2)
+            .any(|w| w[0].num_succesful != w[1].num_succesful));
+        assert_eq!(sequential, parallel);
     }
 }
@@ -1,19 +1,37 @@
 use crate::{
     payment::Payment,
     stats::{Adversaries, Statistics, TargetedAttack},
+    traversal::pathfinding::Path,
     AdversarySelection, Simulation, ID,
 };
 
 #[cfg(not(test))]
 use log::{info, warn};
+use ordered_float::OrderedFloat;
 use rayon::prelude::*;
 use std::{
     collections::HashMap,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 #[cfg(test)]
 use std::{println as info, println as warn};
 
+/// The smallest numbers of adversaries for which a payment is observed in a certain way;
+/// `usize::MAX` if it never is
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AdversaryExposure {
+    succeeded: bool,
+    /// At least one path contains an adversary
+    hit: usize,
+    /// At least two paths contain an adversary
+    correlated: usize,
+    /// At least one used path is prone
+    prone: usize,
+    /// (contains an adversary, is prone) for each used path
+    used_paths: Vec<(usize, usize)>,
+}
+
 impl Simulation {
     pub(crate) fn eval_adversaries(&mut self, run_all: bool) {
         info!("Starting adversary evaluation scenarios..");
@@ -35,6 +53,15 @@ impl Simulation {
         let adversaries = Arc::new(Mutex::new(vec![]));
         self.adversary_selection.par_iter().for_each(|strategy| {
             let mut statistics: Vec<Statistics> = vec![];
+            // the adversary sets are prefixes of the same ranking so one pass over the payments
+            // suffices for all numbers of adversaries
+            let exposure = Self::adversary_exposure(
+                &all_payments,
+                selected_adversaries
+                    .get(strategy)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+            );
             for num_adv in number_of_adversaries.iter() {
                 let adv = match selected_adversaries.get(strategy) {
                     None => vec![],
@@ -44,12 +71,11 @@ impl Simulation {
                     "Starting adversary scenario: {} sat: {:?} with {} nodes.",
                     self.amount, strategy, num_adv,
                 );
-                let (hits, _parts_hits, _payment_attacks) =
-                    Self::adversary_hits(&all_payments, &adv);
+                let hits = Self::adversary_hits(&exposure, *num_adv);
                 let (correlated, correlated_successful) =
-                    Self::colluding_adversaries(&all_payments, &adv);
+                    Self::colluding_adversaries(&exposure, *num_adv);
                 let (prone_paths, prone_payments) =
-                    Self::prone_paths_and_payments(&all_payments, &adv);
+                    Self::prone_paths_and_payments(&exposure, *num_adv);
                 info!("Completed counting adversary occurences in payments.");
                 /*let anonymity_sets = if let Some(adversary) = adv.last() {
                         let set = self.deanonymise_tx_pairs(adversary);
@@ -97,71 +123,89 @@ impl Simulation {
         }
     }
 
-    /// Count how many adversaries are included in a payment's path
-    /// MPP payment parts are considered jointly
-    #[allow(clippy::type_complexity)]
-    fn adversary_hits(
-        payments: &[Payment],
-        adv: &[ID],
-    ) -> (
-        (usize, usize),
-        (usize, usize),
-        (HashMap<usize, usize>, HashMap<usize, usize>),
-    ) {
-        let mut hits = 0;
-        let mut hits_successful = 0;
-        let mut part_hits = 0;
-        let mut part_hits_successful = 0;
-        let mut adv_count: HashMap<usize, usize> = HashMap::default();
-        let mut adv_count_successful: HashMap<usize, usize> = HashMap::default();
-        let mut contains_an_adversary = |payment: &Payment| {
-            let mut all_paths = payment.used_paths.to_owned();
-            all_paths.extend(payment.failed_paths.to_owned());
-            for path in all_paths.iter() {
-                if !path.path.path_contains_adversary(adv).is_empty() {
-                    hits += 1;
-                    if payment.succeeded {
-                        hits_successful += 1;
-                    }
-                    break;
-                }
-            }
-        };
-        for payment in payments {
-            contains_an_adversary(payment);
-            let mut used_paths = payment.used_paths.to_owned();
-            used_paths.extend(payment.failed_paths.to_owned());
-            let mut num_attacks = 0;
-            for path in used_paths.iter() {
-                let num_adv = path.path.path_contains_adversary(adv);
-                if !num_adv.is_empty() {
-                    part_hits += 1;
-                    if payment.succeeded {
-                        part_hits_successful += 1;
-                    }
-                }
-                num_attacks += num_adv.len();
-                adv_count
-                    .entry(num_attacks)
-                    .and_modify(|occ| *occ += 1)
-                    .or_insert(1);
-                if payment.succeeded {
-                    adv_count_successful
-                        .entry(num_attacks)
-                        .and_modify(|occ| *occ += 1)
-                        .or_insert(1);
+    /// Determines for each payment the smallest number of adversaries, i.e. length of a prefix of
+    /// `ranking`, at which the payment is hit, correlated or prone. Every path is only scanned once.
+    fn adversary_exposure(payments: &[Payment], ranking: &[ID]) -> Vec<AdversaryExposure> {
+        let mut ranks: HashMap<&ID, usize> = HashMap::with_capacity(ranking.len());
+        for (rank, node) in ranking.iter().enumerate() {
+            ranks.entry(node).or_insert(rank);
+        }
+        payments
+            .iter()
+            .map(|payment| {
+                let used_paths: Vec<(usize, usize)> = payment
+                    .used_paths
+                    .iter()
+                    .map(|path| Self::path_exposure(&path.path, &ranks))
+                    .collect();
+                let mut contained: Vec<usize> = used_paths
+                    .iter()
+                    .map(|(contained, _)| *contained)
+                    .chain(
+                        payment
+                            .failed_paths
+                            .iter()
+                            .map(|path| Self::path_exposure(&path.path, &ranks).0),
+                    )
+                    .collect();
+                contained.sort_unstable();
+                AdversaryExposure {
+                    succeeded: payment.succeeded,
+                    hit: contained.first().copied().unwrap_or(usize::MAX),
+                    // a payment can be correlated once two of its paths contain adversaries
+                    correlated: contained.get(1).copied().unwrap_or(usize::MAX),
+                    prone: used_paths
+                        .iter()
+                        .map(|(_, prone)| *prone)
+                        .min()
+                        .unwrap_or(usize::MAX),
+                    used_paths,
                 }
-            }
+            })
+            .collect()
+    }
+
+    /// Number of adversaries needed for the path to contain an adversary and to be prone, i.e.
+    /// for adversaries to sit on both the first and the last hop.
+    /// Source and destination are not considered to be adversaries.
+    fn path_exposure(path: &Path, ranks: &HashMap<&ID, usize>) -> (usize, usize) {
+        let involved_nodes = path.get_involved_nodes();
+        let len = involved_nodes.len();
+        if len < 2 {
+            return (usize::MAX, usize::MAX);
         }
-        (
-            (hits, hits_successful),
-            (part_hits, part_hits_successful),
-            (adv_count, adv_count_successful),
-        )
+        // a node is part of the first n adversaries if its rank is < n
+        let threshold = |node: &ID| ranks.get(node).map(|r| r + 1).unwrap_or(usize::MAX);
+        let contained = involved_nodes[1..len - 1]
+            .iter()
+            .map(threshold)
+            .min()
+            .unwrap_or(usize::MAX);
+        let first_hop = if path.is_first_hop(&involved_nodes[1]) {
+            threshold(&involved_nodes[1])
+        } else {
+            usize::MAX
+        };
+        let last_hop = if path.is_last_hop(&involved_nodes[len - 2]) {
+            threshold(&involved_nodes[len - 2])
+        } else {
+            usize::MAX
+        };
+        (contained, contained.max(first_hop).max(last_hop))
+    }
+
+    /// Count how many payments include at least one of the first `num_adv` adversaries in one of
+    /// their paths. MPP payment parts are considered jointly
+    fn adversary_hits(exposure: &[AdversaryExposure], num_adv: usize) -> (usize, usize) {
+        let hit: Vec<&AdversaryExposure> = exposure.iter().filter(|e| e.hit <= num_adv).collect();
+        (hit.len(), hit.iter().filter(|e| e.succeeded).count())
     }
 
     /// Returns the number of paths and payments that were observed the first and last hops
-    fn prone_paths_and_payments(payments: &[Payment], adv: &[ID]) -> ((f32, f32), (f32, f32)) {
+    fn prone_paths_and_payments(
+        exposure: &[AdversaryExposure],
+        num_adv: usize,
+    ) -> ((f32, f32), (f32, f32)) {
         let mut prone_paths = 0;
         let mut prone_paths_successful = 0;
         let mut safe_paths = 0;
@@ -170,36 +214,26 @@ impl Simulation {
         let mut prone_payments_successful = 0;
         let mut safe_payments = 0;
         let mut safe_payments_successful = 0;
-        for payment in payments {
-            let mut payment_is_prone = false;
-            for path in payment.used_paths.iter() {
-                // no need to exclude the src and dest and the called function takes that into account
-                if !path.path.path_contains_adversary(adv).is_empty() {
-                    let mut first_hop = false;
-                    let mut final_hop = false;
-                    for a in adv {
-                        if path.path.is_first_hop(a) {
-                            first_hop = true;
-                        }
-                        if path.path.is_last_hop(a) {
-                            final_hop = true;
-                        }
+        for payment in exposure {
+            // only paths that contain adversaries are considered
+            for (_, prone) in payment
+                .used_paths
+                .iter()
+                .filter(|(contained, _)| *contained <= num_adv)
+            {
+                if *prone <= num_adv {
+                    prone_paths += 1;
+                    if payment.succeeded {
+                        prone_paths_successful += 1;
                     }
-                    if first_hop && final_hop {
-                        payment_is_prone = true;
-                        prone_paths += 1;
-                        if payment.succeeded {
-                            prone_paths_successful += 1;
-                        }
-                    } else {
-                        safe_paths += 1;
-                        if payment.succeeded {
-                            safe_paths_successful += 1;
-                        }
+                } else {
+                    safe_paths += 1;
+                    if payment.succeeded {
+                        safe_paths_successful += 1;
                     }
                 }
             }
-            if payment_is_prone {
+            if payment.prone <= num_adv {
                 prone_payments += 1;
                 if payment.succeeded {
                     prone_payments_successful += 1;
@@ -226,41 +260,60 @@ impl Simulation {
     /// Counts the number of paths per payment that could be correlated by colluding adversaries.
     /// Includes all payment attempts
     /// Returns the number of payments that were observed on multiple occasions
-    fn colluding_adversaries(payments: &[Payment], adv: &[ID]) -> (usize, usize) {
+    fn colluding_adversaries(exposure: &[AdversaryExposure], num_adv: usize) -> (usize, usize) {
         info!("Counting colluding adversaries.");
-        let mut correlated = 0;
-        let mut correlated_successful = 0;
-        for payment in payments {
-            let mut all_paths = payment.used_paths.to_owned();
-            all_paths.extend(payment.failed_paths.to_owned());
-            let mut paths_containing_adversaries = 0;
-            for path in all_paths.iter() {
-                // no need to exclude the src and dest and the called function takes that into account
-                if !path.path.path_contains_adversary(adv).is_empty() {
-                    paths_containing_adversaries += 1;
-                }
-            }
-            // because the same payment was seen more than once
-            if paths_containing_adversaries >= 2 {
-                correlated += 1;
-                if payment.succeeded {
-                    correlated_successful += 1;
-                }
+        let correlated: Vec<&AdversaryExposure> = exposure
+            .iter()
+            .filter(|e| e.correlated <= num_adv)
+            .collect();
+        (
+            correlated.len(),
+            correlated.iter().filter(|e| e.succeeded).count(),
+        )
+    }
+
+    /// Ranks the nodes by the weighted sum of their normalised scores in each of the rankings.
+    /// Nodes missing from a ranking score 0 in it.
+    /// Rankings in `.csv` files are read as `id,score` rows, all others as one node ID per line.
+    fn composite_ranking(
+        nodes: &[ID],
+        rankings: &[(PathBuf, OrderedFloat<f32>)],
+    ) -> Result<Vec<ID>, std::io::Error> {
+        let mut scores: HashMap<ID, f32> = HashMap::new();
+        for (path, weight) in rankings {
+            let ranking = if path.extension().is_some_and(|ext| ext == "csv") {
+                network_parser::read_normalised_node_rankings_with_scores(nodes, path)?
+            } else {
+                network_parser::read_normalised_node_rankings(nodes, path)?
+            };
+            for (id, score) in ranking {
+                *scores.entry(id).or_default() += weight.into_inner() * score;
             }
         }
-        (correlated, correlated_successful)
+        let mut ranking: Vec<(ID, f32)> = scores.into_iter().collect();
+        // ties are broken by ID to be deterministic
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(ranking.into_iter().map(|(id, _)| id).collect())
     }
 
     fn get_adversaries(
-        &self,
+        &mut self,
         number_of_adversaries: usize,
     ) -> HashMap<AdversarySelection, Vec<ID>> {
         let nodes = self.graph.get_node_ids();
         let mut all_adversaries: HashMap<AdversarySelection, Vec<ID>> = HashMap::new();
         for strategy in self.adversary_selection.iter() {
             let adv: Vec<ID> = match strategy {
+                // independent of other draws so that strategies can be compared on the same run
                 AdversarySelection::Random => {
-                    Simulation::draw_adversaries(&nodes, number_of_adversaries).collect()
+                    Simulation::draw_adversaries_seeded(&nodes, number_of_adversaries, self.run)
+                }
+                AdversarySelection::CapacityWeightedRandom => {
+                    Simulation::draw_capacity_weighted_adversaries(
+                        &self.graph,
+                        number_of_adversaries,
+                        &mut self.rng,
+                    )
                 }
                 AdversarySelection::HighBetweenness(path)
                 | AdversarySelection::HighDegree(path) => {
@@ -272,9 +325,47 @@ impl Simulation {
                         }
                     }
                 }
-                AdversarySelection::HighBetweennessWeb(ranking) => {
-                    ranking[0..number_of_adversaries].to_owned()
+                AdversarySelection::HighBetweennessWeb(ranking)
+                | AdversarySelection::HighDegreeWeb(ranking) => ranking
+                    .iter()
+                    .take(number_of_adversaries)
+                    .cloned()
+                    .collect(),
+                AdversarySelection::HighBetweennessComputed => self
+                    .graph
+                    .betweenness_centrality()
+                    .into_iter()
+                    .take(number_of_adversaries)
+                    .map(|(id, _)| id)
+                    .collect(),
+                AdversarySelection::HighDegreeComputed => self
+                    .graph
+                    .degree_centrality()
+                    .into_iter()
+                    .take(number_of_adversaries)
+                    .map(|(id, _)| id)
+                    .collect(),
+                AdversarySelection::CompositeRanking(rankings) => {
+                    match Self::composite_ranking(&nodes, rankings) {
+                        Ok(ranking) => ranking.into_iter().take(number_of_adversaries).collect(),
+                        Err(e) => {
+                            warn!("No scores available {}. Proceeding with 0 adversaries.", e);
+                            vec![]
+                        }
+                    }
                 }
+                AdversarySelection::Custom(adversaries) => adversaries
+                    .iter()
+                    .filter(|id| {
+                        let in_graph = nodes.contains(id);
+                        if !in_graph {
+                            warn!("Ignoring adversary {} that is not in the graph.", id);
+                        }
+                        in_graph
+                    })
+                    .take(number_of_adversaries)
+                    .cloned()
+                    .collect(),
             };
             all_adversaries.insert(strategy.clone(), adv);
         }
@@ -292,7 +383,7 @@ mod tests {
         AdversarySelection,
     };
     use approx::*;
-    use std::collections::VecDeque;
+    use std::collections::{HashSet, VecDeque};
 
     #[test]
     fn adversary_hits() {
@@ -345,9 +436,10 @@ mod tests {
     #[test]
     fn choose_adversaries() {
         let number_of_adversaries = 4;
-        let simulator = crate::attempt::tests::init_sim(None, Some(vec![number_of_adversaries]));
+        let mut simulator =
+            crate::attempt::tests::init_sim(None, Some(vec![number_of_adversaries]));
         let adversaries = simulator.get_adversaries(number_of_adversaries);
-        assert!(adversaries.get(&AdversarySelection::Random).is_some());
+        assert!(adversaries.contains_key(&AdversarySelection::Random));
         let actual = adversaries.get(&AdversarySelection::Random).unwrap();
         assert_eq!(actual.len(), number_of_adversaries);
         for node in simulator.graph.get_node_ids() {
@@ -355,6 +447,159 @@ mod tests {
         }
     }
 
+    #[test]
+    fn choose_custom_adversaries() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![2]));
+        let selection = AdversarySelection::Custom(vec![
+            "dina".to_string(),
+            "mallory".to_string(),
+            "bob".to_string(),
+            "alice".to_string(),
+        ]);
+        simulator.adversary_selection = vec![selection.clone()];
+        let adversaries = simulator.get_adversaries(2);
+        assert_eq!(adversaries.len(), 1);
+        // unknown nodes are skipped
+        assert_eq!(
+            adversaries.get(&selection).unwrap(),
+            &vec!["dina".to_string(), "bob".to_string()]
+        );
+        assert_eq!(selection.to_string(), "Custom");
+        // the list itself is not serialised
+        assert_eq!(serde_json::to_string(&selection).unwrap(), r#""Custom""#);
+    }
+
+    #[test]
+    fn choose_adversaries_by_composite_ranking() {
+        use std::io::Write;
+        let mut degree = tempfile::NamedTempFile::new().unwrap();
+        for id in ["alice", "bob", "mallory", "chan", "dina"] {
+            writeln!(degree, "{}", id).unwrap();
+        }
+        let mut betweenness = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+        writeln!(betweenness, "id,score").unwrap();
+        for (id, score) in [("chan", 10), ("bob", 6), ("dina", 4), ("alice", 0)] {
+            writeln!(betweenness, "{},{}", id, score).unwrap();
+        }
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![3]));
+        let composite = |degree_weight: f32, betweenness_weight: f32| {
+            AdversarySelection::CompositeRanking(vec![
+                (degree.path().to_path_buf(), OrderedFloat(degree_weight)),
+                (
+                    betweenness.path().to_path_buf(),
+                    OrderedFloat(betweenness_weight),
+                ),
+            ])
+        };
+        // mallory is not in the graph: alice 1, bob 2/3, chan 1/3, dina 0 by degree and chan 1,
+        // bob 0.6, dina 0.4, alice 0 by betweenness
+        let betweenness_heavy = composite(0.3, 0.7);
+        let degree_heavy = composite(0.7, 0.3);
+        simulator.adversary_selection = vec![betweenness_heavy.clone(), degree_heavy.clone()];
+        let adversaries = simulator.get_adversaries(3);
+        assert_eq!(
+            adversaries[&betweenness_heavy],
+            vec!["chan", "bob", "alice"]
+        );
+        assert_eq!(adversaries[&degree_heavy], vec!["alice", "bob", "chan"]);
+        let name = |file: &tempfile::NamedTempFile| {
+            file.path()
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        };
+        assert_eq!(
+            betweenness_heavy.to_string(),
+            format!(
+                "Composite Ranking ({}: 0.3, {}: 0.7)",
+                name(&degree),
+                name(&betweenness)
+            )
+        );
+        // the rankings are serialised so that the selection can be restored
+        let serialised = serde_json::to_string(&betweenness_heavy).unwrap();
+        let deserialised: AdversarySelection = serde_json::from_str(&serialised).unwrap();
+        assert_eq!(deserialised, betweenness_heavy);
+    }
+
+    #[test]
+    fn seeded_adversaries_ignore_prior_draws() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![2]));
+        simulator.adversary_selection = vec![
+            AdversarySelection::Random,
+            AdversarySelection::CapacityWeightedRandom,
+        ];
+        let nodes = simulator.graph.get_node_ids();
+        let expected = Simulation::draw_adversaries_seeded(&nodes, 2, 7);
+        assert_eq!(expected.len(), 2);
+        // other consumers draw from the simulation's RNG in between
+        let _ = Simulation::draw_n_pairs_for_simulation(&simulator.graph, 10, &mut simulator.rng)
+            .count();
+        let _ = simulator.get_adversaries(2);
+        let _: u64 = rand::Rng::gen(&mut simulator.rng);
+        assert_eq!(Simulation::draw_adversaries_seeded(&nodes, 2, 7), expected);
+        // the order of the nodes does not matter either
+        let reversed: Vec<ID> = nodes.iter().rev().cloned().collect();
+        assert_eq!(
+            Simulation::draw_adversaries_seeded(&reversed, 2, 7),
+            expected
+        );
+        // the random strategy is drawn from the run's seed
+        let random = simulator.get_adversaries(2)[&AdversarySelection::Random].clone();
+        let _: u64 = rand::Rng::gen(&mut simulator.rng);
+        assert_eq!(
+            simulator.get_adversaries(2)[&AdversarySelection::Random],
+            random
+        );
+    }
+
+    #[test]
+    fn capacity_weighted_adversaries() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![1]));
+        simulator.graph = crate::attempt::tests::graph_of_channels(&[
+            ("hub", "a", 0),
+            ("hub", "b", 0),
+            ("hub", "tiny", 0),
+            ("a", "b", 0),
+        ]);
+        for edges in simulator.graph.edges.values_mut() {
+            for edge in edges {
+                edge.capacity = match edge.source.as_str() {
+                    "hub" => 10000000,
+                    "tiny" => 1,
+                    _ => edge.capacity,
+                };
+            }
+        }
+        simulator.adversary_selection = vec![AdversarySelection::CapacityWeightedRandom];
+        let mut hits: HashMap<ID, usize> = HashMap::new();
+        for seed in 0..200 {
+            simulator.rng = rand::SeedableRng::seed_from_u64(seed);
+            let adversaries = simulator.get_adversaries(1);
+            let adversaries = &adversaries[&AdversarySelection::CapacityWeightedRandom];
+            assert_eq!(adversaries.len(), 1);
+            *hits.entry(adversaries[0].clone()).or_default() += 1;
+        }
+        let hub = hits.get("hub").copied().unwrap_or_default();
+        let tiny = hits.get("tiny").copied().unwrap_or_default();
+        assert!(hub > 150);
+        assert!(hub > 10 * tiny.max(1));
+        // all nodes are drawn if we ask for as many adversaries as there are nodes
+        let adversaries = simulator.get_adversaries(4);
+        assert_eq!(
+            adversaries[&AdversarySelection::CapacityWeightedRandom]
+                .iter()
+                .collect::<HashSet<_>>()
+                .len(),
+            4
+        );
+        assert_eq!(
+            AdversarySelection::CapacityWeightedRandom.to_string(),
+            "Capacity Weighted Random"
+        );
+    }
+
     #[test]
     fn count_correlations() {
         let number_of_adversaries = 4; // all four nodes are adversaries
@@ -380,7 +625,8 @@ mod tests {
     #[test]
     fn correlate_payments() {
         let number_of_adversaries = 4;
-        let simulator = crate::attempt::tests::init_sim(None, Some(vec![number_of_adversaries]));
+        let mut simulator =
+            crate::attempt::tests::init_sim(None, Some(vec![number_of_adversaries]));
         let adversaries = simulator.get_adversaries(number_of_adversaries);
         let adversaries = adversaries.get(&AdversarySelection::Random).unwrap();
         let source = "alice".to_string();
@@ -409,9 +655,9 @@ mod tests {
                     weight: 100.0,
                     amount: 1100,
                     time: 40,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 }],
-                failed_amounts: Vec::default(),
-                successful_shards: Vec::default(),
                 failed_paths: vec![CandidatePath {
                     path: Path {
                         src: "alice".to_string(),
@@ -425,14 +671,16 @@ mod tests {
                     weight: 100.0,
                     amount: 1100,
                     time: 40,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 }],
+                ..Default::default()
             },
             Payment {
                 payment_id: 2,
                 source: source.clone(),
                 dest: "eric".to_string(),
                 amount_msat: 1000,
-                succeeded: false,
                 min_shard_amt: crate::MIN_SHARD_AMOUNT,
                 num_parts: 1,
                 htlc_attempts: 2,
@@ -451,9 +699,9 @@ mod tests {
                     weight: 100.0,
                     amount: 1100,
                     time: 40,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 }],
-                failed_amounts: Vec::default(),
-                successful_shards: Vec::default(),
                 failed_paths: vec![CandidatePath {
                     path: Path {
                         src: "alice".to_string(),
@@ -467,15 +715,19 @@ mod tests {
                     weight: 100.0,
                     amount: 1100,
                     time: 40,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 }],
+                ..Default::default()
             },
         ];
+        let exposure = Simulation::adversary_exposure(&payments, adversaries);
         let (correlation_count, correlation_count_successful) =
-            Simulation::colluding_adversaries(&payments, &adversaries);
+            Simulation::colluding_adversaries(&exposure, adversaries.len());
         assert_eq!(correlation_count, 2); // bob sees the payment twice
         assert_eq!(correlation_count_successful, 1);
         let (prone_paths, prone_payments) =
-            Simulation::prone_paths_and_payments(&payments, &adversaries);
+            Simulation::prone_paths_and_payments(&exposure, adversaries.len());
         // all paths are susceptible
         assert_abs_diff_eq!(prone_paths.0, 1.0, epsilon = 0.001f32);
         // all successful payemnts' paths are susceptible
@@ -485,4 +737,86 @@ mod tests {
         // the only successful payment is prone
         assert_abs_diff_eq!(prone_payments.1, 1.0, epsilon = 0.001f32);
     }
+
+    /// Straightforward per-set computation of (hits, correlated, prone paths, prone payments)
+    #[allow(clippy::type_complexity)]
+    fn reference_statistics(
+        payments: &[Payment],
+        adv: &[ID],
+    ) -> ((usize, usize), (usize, usize), (u32, u32), (u32, u32)) {
+        let contains = |path: &CandidatePath| !path.path.path_contains_adversary(adv).is_empty();
+        let (mut hits, mut correlated) = ((0, 0), (0, 0));
+        let (mut paths, mut payments_prone) = ([0; 4], [0; 4]);
+        for payment in payments {
+            let all_paths = payment.used_paths.iter().chain(payment.failed_paths.iter());
+            let observed = all_paths.filter(|p| contains(p)).count();
+            if observed >= 1 {
+                hits.0 += 1;
+                hits.1 += payment.succeeded as usize;
+            }
+            if observed >= 2 {
+                correlated.0 += 1;
+                correlated.1 += payment.succeeded as usize;
+            }
+            let mut payment_is_prone = false;
+            for path in payment.used_paths.iter().filter(|p| contains(p)) {
+                let first = adv.iter().any(|a| path.path.is_first_hop(a));
+                let last = adv.iter().any(|a| path.path.is_last_hop(a));
+                let idx = if first && last { 0 } else { 2 };
+                payment_is_prone |= first && last;
+                paths[idx] += 1;
+                paths[idx + 1] += payment.succeeded as usize;
+            }
+            let idx = if payment_is_prone { 0 } else { 2 };
+            payments_prone[idx] += 1;
+            payments_prone[idx + 1] += payment.succeeded as usize;
+        }
+        let prob = |prone: usize, safe: usize| (prone as f32 / (prone + safe) as f32).to_bits();
+        (
+            hits,
+            correlated,
+            (prob(paths[0], paths[2]), prob(paths[1], paths[3])),
+            (
+                prob(payments_prone[0], payments_prone[2]),
+                prob(payments_prone[1], payments_prone[3]),
+            ),
+        )
+    }
+
+    #[test]
+    fn incremental_statistics_match_per_set_statistics() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        simulator.run(
+            vec![
+                ("alice".to_string(), "chan".to_string()),
+                ("alice".to_string(), "dina".to_string()),
+                ("dina".to_string(), "alice".to_string()),
+                ("bob".to_string(), "dina".to_string()),
+            ]
+            .into_iter(),
+            None,
+            false,
+        );
+        let mut payments = simulator.successful_payments.clone();
+        payments.extend(simulator.failed_payments.clone());
+        let ranking: Vec<ID> = ["chan", "bob", "dina", "alice"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        // computed once for all numbers of adversaries
+        let exposure = Simulation::adversary_exposure(&payments, &ranking);
+        assert_eq!(exposure.len(), payments.len());
+        for num_adv in 0..=ranking.len() {
+            let adv = &ranking[0..num_adv];
+            let (prone_paths, prone_payments) =
+                Simulation::prone_paths_and_payments(&exposure, num_adv);
+            let actual = (
+                Simulation::adversary_hits(&exposure, num_adv),
+                Simulation::colluding_adversaries(&exposure, num_adv),
+                (prone_paths.0.to_bits(), prone_paths.1.to_bits()),
+                (prone_payments.0.to_bits(), prone_payments.1.to_bits()),
+            );
+            assert_eq!(actual, reference_statistics(&payments, adv));
+        }
+    }
 }
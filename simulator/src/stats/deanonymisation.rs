@@ -17,20 +17,19 @@ use std::{println as info, println as debug, println as trace, println as warn};
 impl Simulation {
     /// Returns a set of potential recipients as well as a set of all potential recipients
     #[allow(dead_code)]
-    pub(crate) fn deanonymise_tx_pairs(&self, adversary: &ID) -> Vec<AnonymitySet> {
+    pub(crate) fn deanonymise_tx_pairs(&mut self, adversary: &ID) -> Vec<AnonymitySet> {
         info!(
             "Computing anonymity sets for {:?}, {:?} of {} sat.",
             self.routing_metric, self.payment_parts, self.amount,
         );
         let all_anonymits_sets = Arc::new(Mutex::new(vec![]));
         let graph = self.graph.clone();
-        let mut rng = crate::RNG.lock().unwrap();
         // randomly pick 20% of the payments
         let payments = self
             .successful_payments
             .iter()
             .cloned()
-            .choose_multiple(&mut *rng, self.successful_payments.len() * 20 / 100);
+            .choose_multiple(&mut self.rng, self.successful_payments.len() * 20 / 100);
         info!(
             "Evaluating {} successful payments for anonymity sets.",
             payments.len()
@@ -42,7 +41,11 @@ impl Simulation {
                 let mut sd_anon_set = HashSet::new();
                 let mut rx_anon_set = HashSet::new();
                 // will only be one at most
-                if let Some(adv) = p.path.path_contains_adversary(&[adversary.clone()]).first() {
+                if let Some(adv) = p
+                    .path
+                    .path_contains_adversary(std::slice::from_ref(adversary))
+                    .first()
+                {
                     let adversary_id = adv.0.clone();
                     let (pred, succ, amount_to_succ, ttl_to_rx) =
                         Self::extract_tx_info(p, &adversary_id);
@@ -80,8 +83,8 @@ impl Simulation {
                         ) {
                             // TODO: Source can stay the same
                             let mut p_i_prime = p_i.clone();
-                            if !p_i_prime.path.get_involved_nodes().contains(&adversary_id)
-                                && !p_i_prime.path.get_involved_nodes().contains(&pred)
+                            if !p_i_prime.contains_node(&adversary_id)
+                                && !p_i_prime.contains_node(&pred)
                             {
                                 p_i_prime.path.hops.push_front((
                                     adversary_id.clone(),
@@ -138,12 +141,13 @@ impl Simulation {
                     }
                     let correct_recipient = rx_anon_set.contains(&payment.dest);
                     let correct_source = sd_anon_set.contains(&payment.source);
-                    all_anonymits_sets.lock().unwrap().push(AnonymitySet {
-                        sender: sd_anon_set.len(),
-                        recipient: rx_anon_set.len(),
+                    all_anonymits_sets.lock().unwrap().push(AnonymitySet::new(
+                        sd_anon_set.len(),
+                        rx_anon_set.len(),
                         correct_recipient,
                         correct_source,
-                    });
+                        payment.amount_msat,
+                    ));
                 }
             });
         });
@@ -207,10 +211,10 @@ impl Simulation {
             if p_i.path.is_subpath(&p_n.path) {
                 sources.extend(
                     self.graph
-                        .get_outedges(&p_n.path.src) // outedges = inedges since the graph is symmetric
+                        .get_inedges(&p_n.path.src)
                         .iter()
-                        .filter(|e| !p_n.path.get_involved_nodes().contains(&e.destination))
-                        .map(|e| e.destination.clone())
+                        .filter(|e| !p_n.contains_node(&e.source))
+                        .map(|e| e.source.clone())
                         .collect::<HashSet<ID>>(),
                 );
             }
@@ -259,6 +263,8 @@ impl Simulation {
                     self.routing_metric,
                     self.payment_parts,
                 );
+                path_finder.set_max_total_cltv(self.max_total_cltv);
+                path_finder.set_risk_factor(self.risk_factor);
                 if let Some(shortest_path) = path_finder.shortest_path_from(src) {
                     // determine cost for path - treat src as an intermediary
                     trace!(
@@ -274,6 +280,9 @@ impl Simulation {
                         .collect();
                     let mut candidate_path = CandidatePath::new_with_path(path);
                     path_finder.get_aggregated_path_cost(&mut candidate_path, true);
+                    if candidate_path.is_empty() {
+                        return None;
+                    }
                     // path we have computed = candidate_path
                     // store computed paths in hashmap <src, path>
                     all_shortest_paths.insert((src.clone(), rec.clone()), candidate_path.clone());
@@ -437,13 +446,15 @@ mod tests {
             weight: 0.0,
             amount: 0,
             time: 0,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         }];
         let actual = Simulation::get_all_reachable_paths(&graph, &next, amount, ttl);
         assert!(actual.is_some());
         assert_eq!(actual.unwrap(), expected);
         let next = "bob".to_string();
         let ttl = 40;
-        let expected = vec![
+        let expected = [
             CandidatePath {
                 path: Path {
                     src: "bob".to_owned(),
@@ -456,6 +467,8 @@ mod tests {
                 weight: 0.0,
                 amount: 0,
                 time: 0,
+                hold_time_secs: 0.0,
+                hop_probabilities: vec![],
             },
             CandidatePath {
                 path: Path {
@@ -469,6 +482,8 @@ mod tests {
                 weight: 0.0,
                 amount: 0,
                 time: 0,
+                hold_time_secs: 0.0,
+                hop_probabilities: vec![],
             },
         ];
         let actual = Simulation::get_all_reachable_paths(&graph, &next, amount, ttl);
@@ -491,6 +506,8 @@ mod tests {
             weight: 0.0,
             amount: 0,
             time: 0,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         }];
         let actual = Simulation::get_all_reachable_paths(&graph, &next, amount, ttl);
         assert!(actual.is_some());
@@ -531,6 +548,8 @@ mod tests {
             weight: 0.0,
             amount: 0,
             time: 0,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         // alice's neighbours
         let mut shortest_paths = HashMap::from([
@@ -541,6 +560,8 @@ mod tests {
                     weight: 0.0,
                     amount: 0,
                     time: 0,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 },
             ),
             (
@@ -558,6 +579,8 @@ mod tests {
                     weight: 0.0,
                     amount: 0,
                     time: 0,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 },
             ),
         ]);
@@ -588,6 +611,8 @@ mod tests {
             weight: 0.0,
             amount: 0,
             time: 0,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         let path_from_pre = CandidatePath {
             path: Path {
@@ -603,6 +628,8 @@ mod tests {
             weight: 0.0,
             amount: 0,
             time: 0,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         let mut shortest_paths = HashMap::from([((pre.to_owned(), next), path_from_pre)]);
         assert!(simulator.is_pred_definitive_sender(&p_i_prime, &pre, amount, &mut shortest_paths));
@@ -627,6 +654,8 @@ mod tests {
             weight: 5175.0,
             amount: 5175,
             time: 90,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         let path_from_adv = CandidatePath {
             path: Path {
@@ -640,6 +669,8 @@ mod tests {
             weight: 5175.0,
             amount: 5175,
             time: 90,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         assert!(Simulation::is_potential_destination(
             &p_i_prime,
@@ -661,6 +692,8 @@ mod tests {
             weight: 5175.0,
             amount: 5175,
             time: 90,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         assert!(!Simulation::is_potential_destination(
             &p_i_prime,
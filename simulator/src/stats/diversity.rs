@@ -1,8 +1,19 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
-use super::Diversity;
-use crate::{Simulation, ID};
+use super::{Diversity, GraphDiversity};
+use crate::{
+    graph::Graph, traversal::pathfinding::PathFinder, CandidatePath, Path, PaymentParts,
+    RoutingMetric, Simulation, ID,
+};
 use itertools::Itertools;
+#[cfg(not(test))]
+use log::{debug, info};
+use rayon::prelude::*;
+#[cfg(test)]
+use std::{println as info, println as debug};
 
 type NodeLinkID = (ID, String);
 
@@ -11,15 +22,12 @@ impl Simulation {
     /// al.
     pub(crate) fn eval_path_similarity(&mut self) {
         let mut levenshtein_distances = vec![];
-        let mut path_diversity = vec![];
-        let lambdas = vec![0.2, 0.5, 0.7, 1.0];
-        for (i, lambda) in lambdas.into_iter().enumerate() {
-            let mut epds = vec![];
-            for payment in &self.successful_payments {
-                if payment.num_parts <= 1 {
-                    continue;
-                }
-                let paths: Vec<Vec<NodeLinkID>> = payment
+        let mpp_paths: Vec<Vec<Vec<NodeLinkID>>> = self
+            .successful_payments
+            .iter()
+            .filter(|payment| payment.num_parts > 1)
+            .map(|payment| {
+                payment
                     .used_paths
                     .iter()
                     .map(|p| {
@@ -29,18 +37,23 @@ impl Simulation {
                             .map(|h| (h.0.clone(), h.3.clone()))
                             .collect()
                     })
-                    .collect();
-                epds.push(Self::calculate_effective_path_diversity(&paths, lambda));
-                if i == 0 {
-                    // we only need to calculate this once: hacky
-                    levenshtein_distances.extend(Self::calculate_levenshtein_distance(&paths));
-                }
-            }
-            path_diversity.push(Diversity {
-                lambda,
-                diversity: epds,
-            });
+                    .collect()
+            })
+            .collect();
+        for paths in &mpp_paths {
+            levenshtein_distances.extend(Self::calculate_levenshtein_distance(paths));
         }
+        let path_diversity = self
+            .diversity_lambdas
+            .iter()
+            .map(|&lambda| Diversity {
+                lambda,
+                diversity: mpp_paths
+                    .iter()
+                    .map(|paths| Self::calculate_effective_path_diversity(paths, lambda))
+                    .collect(),
+            })
+            .collect();
         self.path_distances.0 = levenshtein_distances;
         self.path_diversity.0 = path_diversity;
     }
@@ -52,12 +65,10 @@ impl Simulation {
         for idx in 0..paths.len() {
             let mut div_min_path_i = f32::MAX;
             let mut alternate_paths = paths.to_vec();
-            // the base path should be the only item returned by drain
-            if let Some(base_path) = alternate_paths.drain(idx..idx + 1).last() {
-                for path in alternate_paths {
-                    let div = Self::calculate_path_diversity(&base_path, &path);
-                    div_min_path_i = f32::min(div_min_path_i, div);
-                }
+            let base_path = alternate_paths.remove(idx);
+            for path in alternate_paths {
+                let div = Self::calculate_path_diversity(&base_path, &path);
+                div_min_path_i = f32::min(div_min_path_i, div);
             }
             aggregated_div += div_min_path_i;
         }
@@ -72,6 +83,14 @@ impl Simulation {
     ) -> f32 {
         let base_path = Self::get_intermediate_node_and_edges(base_path);
         let alternate_path = Self::get_intermediate_node_and_edges(alternate_path);
+        // nothing to compare against, e.g. a path without any channels
+        if base_path.is_empty() {
+            return if base_path == alternate_path {
+                0.0
+            } else {
+                1.0
+            };
+        }
         1.0 - (base_path.intersection(&alternate_path).count() as f32 / base_path.len() as f32)
     }
 
@@ -158,14 +177,158 @@ impl Simulation {
     }
 
     pub fn get_intermediate_node_and_edges(hops: &[NodeLinkID]) -> HashSet<String> {
-        let nodes: Vec<ID> = (1..hops.len() - 1).map(|h| hops[h].0.clone()).collect();
-        let links: Vec<ID> = (0..hops.len() - 1).map(|h| hops[h].1.clone()).collect();
+        let last = hops.len().saturating_sub(1);
+        let nodes: Vec<ID> = (1..last).map(|h| hops[h].0.clone()).collect();
+        let links: Vec<ID> = (0..last).map(|h| hops[h].1.clone()).collect();
         let mut path = HashSet::from_iter(nodes);
         path.extend(links);
         path
     }
 }
 
+/// The TGD [0, 1] is the average of the EPD values of all node pairs within that graph.
+/// a star topology will always have a TGD of 0, while a ring topology will have a TGD of 0.6 given
+/// a lambda of 1.
+/// an EPD of 1 would indicate an infinite diversity) and also reflect the decreasing marginal
+/// utility provided by additional paths in real networks.
+/// We only calculate the EPDs for the payment pairs we used because of the runtime
+pub fn total_graph_diversity(
+    graph: &Graph,
+    k: usize,
+    routing_metric: RoutingMetric,
+    lambdas: &[f32],
+    amount: usize,
+) -> Vec<GraphDiversity> {
+    let mut graph_diversity = vec![];
+    let ids = graph.get_node_ids();
+    let pairs: Vec<(String, String)> = ids.into_iter().tuple_combinations().collect();
+    let count = pairs.len() as f32;
+    info!("Computing graph diversity using {} pairs.", count);
+    let outstanding = Arc::new(Mutex::new(pairs.len()));
+    let div_scores = Arc::new(Mutex::new(Vec::new()));
+    pairs.par_iter().for_each(|comb| {
+        info!("{} computations to go.", outstanding.lock().unwrap());
+        let (src, dest) = (comb.0.clone(), comb.1.clone());
+        let diversities =
+            effective_path_diversity(&src, &dest, graph, k, routing_metric, lambdas, amount);
+        div_scores.lock().unwrap().push(diversities);
+        *outstanding.lock().unwrap() -= 1;
+    });
+    let mut scores: HashMap<(usize, usize), f32> = HashMap::new();
+    if let Ok(arc) = Arc::try_unwrap(div_scores) {
+        if let Ok(mutex) = arc.into_inner() {
+            for d in mutex {
+                for (k, v) in d {
+                    if let Some(x) = scores.get_mut(&k) {
+                        *x += v;
+                    } else {
+                        scores.insert(k, v);
+                    }
+                }
+            }
+        }
+    }
+    for (k, v) in scores {
+        let diversity = v / count;
+        let gd = GraphDiversity {
+            lambda: lambdas[k.1],
+            diversity,
+            k: k.0,
+        };
+        graph_diversity.push(gd);
+    }
+    info!("Completed graph diversity using {} pairs.", count);
+    graph_diversity
+}
+
+/// Effective path diversity (EPD) is an aggregation of path diversities for a selected set of
+/// paths between a given node-pair
+fn effective_path_diversity(
+    source: &ID,
+    dest: &ID,
+    graph: &Graph,
+    max_num_paths: usize,
+    routing_metric: RoutingMetric,
+    lambdas: &[f32],
+    amount: usize,
+) -> HashMap<(usize, usize), f32> {
+    // returns a map of <(k, lambda[pos]), f32>
+    let mut k_div = HashMap::with_capacity(max_num_paths);
+    debug!("Calculating diversity between {source} and {dest}.");
+    let k = if max_num_paths == 20 {
+        vec![4, 5, 6, 10]
+    } else {
+        vec![max_num_paths]
+    };
+    let k_shortest_paths =
+        get_shortest_paths(source, dest, graph, max_num_paths, routing_metric, amount);
+    let mut aggregated_div_src_dest = 0.0;
+    for num in k {
+        if num > k_shortest_paths.len() {
+            continue;
+        }
+        for idx in 0..num {
+            let mut div_min_path_i = f32::MAX;
+            let mut alternate_paths = k_shortest_paths[0..num].to_vec();
+            let base_path = alternate_paths.remove(idx);
+            for path in alternate_paths {
+                let div = Simulation::calculate_path_diversity(&base_path, &path);
+                div_min_path_i = f32::min(div_min_path_i, div);
+            }
+            aggregated_div_src_dest += div_min_path_i;
+        }
+        debug!("Completed diversity calculation between {source} and {dest}.");
+        for (id, lambda) in lambdas.iter().enumerate() {
+            let div = 1.0 - std::f32::consts::E.powf(-lambda * aggregated_div_src_dest);
+            k_div.insert((num, id), div);
+        }
+    }
+    k_div
+}
+
+fn get_shortest_paths(
+    source: &ID,
+    dest: &ID,
+    graph: &Graph,
+    k: usize,
+    routing_metric: RoutingMetric,
+    amount: usize,
+) -> Vec<Vec<(ID, String)>> {
+    let mut shortest_paths = vec![];
+    let mut graph_copy = graph.clone();
+    let mut path_finder = PathFinder::new(
+        source.clone(),
+        dest.clone(),
+        0,
+        &graph_copy,
+        routing_metric,
+        PaymentParts::Single,
+    );
+    graph_copy.set_edges(PathFinder::remove_inadequate_edges(&graph_copy, amount));
+    let k_shortest_paths = path_finder.k_shortest_paths_from(source, k);
+    for p in k_shortest_paths {
+        let mut path = Path::new(source.clone(), dest.clone());
+        path.hops =
+            p.0.into_iter()
+                .map(|h| (h, usize::default(), usize::default(), String::default()))
+                .collect();
+        let mut candidate_path = CandidatePath::new_with_path(path);
+        path_finder.get_aggregated_path_cost(&mut candidate_path, false);
+        if candidate_path.is_empty() {
+            continue;
+        }
+        // add the edges
+        let links: Vec<(ID, String)> = candidate_path
+            .path
+            .hops
+            .iter()
+            .map(|h| (h.0.clone(), h.3.clone()))
+            .collect();
+        shortest_paths.push(links);
+    }
+    shortest_paths
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -229,13 +392,10 @@ mod tests {
         let source = String::from("a");
         let dest = String::from("d");
         let successful_payments = vec![Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat: amount,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 2,
             used_paths: vec![
                 CandidatePath {
@@ -252,6 +412,8 @@ mod tests {
                     weight: 175.0, // fees (b->c, c->d)
                     amount: 5175,  // amount + fees
                     time: 55,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 },
                 CandidatePath {
                     path: Path {
@@ -265,11 +427,11 @@ mod tests {
                     weight: 15.0,
                     amount: 55,
                     time: 5,
+                    hold_time_secs: 0.0,
+                    hop_probabilities: vec![],
                 },
             ],
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         }];
         simulator.successful_payments = successful_payments;
         simulator.eval_path_similarity();
@@ -278,6 +440,64 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn direct_path_diversity() {
+        let direct = vec![
+            ("a".to_string(), "ab".to_string()),
+            ("b".to_string(), "".to_string()),
+        ];
+        let parallel = vec![
+            ("a".to_string(), "ab2".to_string()),
+            ("b".to_string(), "".to_string()),
+        ];
+        assert_eq!(Simulation::calculate_path_diversity(&direct, &direct), 0.0);
+        assert_eq!(
+            Simulation::calculate_path_diversity(&direct, &parallel),
+            1.0
+        );
+        // paths without channels
+        let empty = vec![("a".to_string(), "".to_string())];
+        assert_eq!(Simulation::calculate_path_diversity(&empty, &empty), 0.0);
+        assert_eq!(Simulation::calculate_path_diversity(&empty, &direct), 1.0);
+        assert_eq!(Simulation::calculate_path_diversity(&[], &direct), 1.0);
+        let epd = Simulation::calculate_effective_path_diversity(&[direct, parallel, empty], 0.5);
+        assert!(epd.is_finite());
+    }
+
+    #[test]
+    fn custom_diversity_lambdas() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.set_diversity_lambdas(&[0.3]);
+        let path = |hops: Vec<(&str, &str)>| {
+            CandidatePath::new_with_path(Path {
+                src: String::from("alice"),
+                dest: String::from("dina"),
+                hops: hops
+                    .into_iter()
+                    .map(|(node, channel)| (node.to_string(), 0, 0, channel.to_string()))
+                    .collect(),
+            })
+        };
+        simulator.successful_payments = vec![Payment {
+            num_parts: 2,
+            used_paths: vec![
+                path(vec![
+                    ("alice", "alice1"),
+                    ("bob", "bob2"),
+                    ("chan", "chan2"),
+                    ("dina", "dina1"),
+                ]),
+                path(vec![("alice", "alice1"), ("dina", "dina1")]),
+            ],
+            ..Payment::new(0, String::from("alice"), String::from("dina"), 100, None)
+        }];
+        simulator.eval_path_similarity();
+        assert_eq!(simulator.path_diversity.0.len(), 1);
+        assert_eq!(simulator.path_diversity.0[0].lambda, 0.3);
+        assert_eq!(simulator.path_diversity.0[0].diversity.len(), 1);
+        assert_eq!(simulator.path_distances.0, vec![2]);
+    }
+
     #[test]
     fn split_base_from_other_paths() {
         let paths = vec![
@@ -384,4 +604,126 @@ mod tests {
         let actual = Simulation::calculate_effective_path_diversity(&paths, lambda);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn shortest_paths() {
+        let k = 3;
+        let routing_metric = crate::RoutingMetric::MinFee;
+        let path = std::path::Path::new("../test_data/trivial_connected.json");
+        let graph = crate::graph::Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let amount = 10;
+        let pairs = vec![
+            ("034".to_owned(), "025".to_owned()),
+            ("025".to_owned(), "036".to_owned()),
+            ("036".to_owned(), "034".to_owned()),
+        ];
+        for pair in pairs {
+            // only two loopless paths each available
+            let k_shortest_paths =
+                get_shortest_paths(&pair.0, &pair.1, &graph, k, routing_metric, amount);
+            assert_eq!(k_shortest_paths.len(), 2);
+        }
+    }
+
+    #[test]
+    fn calculate_effective_diversity() {
+        let lambdas = [0.5];
+        let path = std::path::Path::new("../test_data/trivial_connected.json");
+        let graph = crate::graph::Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let k = 2;
+        let routing_metric = crate::RoutingMetric::MinFee;
+        let amount = 10;
+        let source = String::from("034");
+        let dest = String::from("036");
+        let k_shortest_paths =
+            get_shortest_paths(&source, &dest, &graph, k, routing_metric, amount);
+        // since we only have two paths, each diversity is the min and the sum is equal to k_sd
+        let mut agg_diversity =
+            Simulation::calculate_path_diversity(&k_shortest_paths[0], &k_shortest_paths[1]);
+        agg_diversity +=
+            Simulation::calculate_path_diversity(&k_shortest_paths[1], &k_shortest_paths[0]);
+        let expected = 1.0 - std::f32::consts::E.powf(-lambdas[0] * agg_diversity);
+        let epd = super::effective_path_diversity(
+            &source,
+            &dest,
+            &graph,
+            k,
+            routing_metric,
+            &lambdas,
+            amount,
+        );
+        let actual = epd.get(&(k, 0)).unwrap();
+        assert_eq!(*actual, expected);
+    }
+
+    #[test]
+    fn calculate_graph_diversity() {
+        let path = std::path::Path::new("../test_data/trivial_connected.json");
+        let graph = crate::graph::Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let k = 2;
+        let lambdas = [0.5];
+        let amount = 10;
+        let routing_metric = crate::RoutingMetric::MinFee;
+        let ids = graph.get_node_ids();
+        let mut diversity = 0.0;
+        let pairs = vec![
+            ("034".to_owned(), "025".to_owned()),
+            ("025".to_owned(), "036".to_owned()),
+            ("036".to_owned(), "034".to_owned()),
+        ];
+        for pair in &pairs {
+            let div = super::effective_path_diversity(
+                &pair.0,
+                &pair.1,
+                &graph,
+                k,
+                routing_metric,
+                &lambdas,
+                amount,
+            );
+            diversity += div.get(&(k, 0)).unwrap();
+        }
+        diversity /= ids.len() as f32;
+        let expected = vec![GraphDiversity {
+            lambda: lambdas[0],
+            diversity,
+            k,
+        }];
+        let actual = total_graph_diversity(&graph, k, routing_metric, &lambdas, amount);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn lnbook_graph_diversity() {
+        let path = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = crate::graph::Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let lambdas = [0.5, 1.0];
+        let amount = 1000;
+        let routing_metric = crate::RoutingMetric::MinFee;
+        // the nodes form a line so there are no alternative paths between any pair
+        assert!(total_graph_diversity(&graph, 2, routing_metric, &lambdas, amount).is_empty());
+        let actual = total_graph_diversity(&graph, 1, routing_metric, &lambdas, amount);
+        assert_eq!(actual.len(), lambdas.len());
+        for diversity in actual {
+            assert_eq!(diversity.k, 1);
+            assert!(lambdas.contains(&diversity.lambda));
+            assert!((0.0..=1.0).contains(&diversity.diversity));
+        }
+    }
 }
@@ -1,16 +1,38 @@
 use crate::{
-    event::*, io::PaymentInfo, payment::Payment, stats::TargetedAttack, time::Time, Invoice,
-    PaymentParts, Simulation, ID,
+    event::*,
+    io::PaymentInfo,
+    payment::Payment,
+    stats::{FailureStats, TargetedAttack},
+    time::Time,
+    Invoice, Simulation, ID,
 };
 
 use itertools::EitherOrBoth::{Both, Left, Right};
 use itertools::Itertools;
 #[cfg(not(test))]
 use log::{debug, info, trace};
+use std::collections::HashSet;
 #[cfg(test)]
 use std::{println as info, println as debug, println as trace};
 
+impl FailureStats {
+    /// Tallies the failing hops of the payments' failed attempts
+    pub(crate) fn from_payments(payments: &[Payment]) -> Self {
+        let mut stats = Self::default();
+        for (node, channel) in payments.iter().flat_map(|p| p.failing_channels.iter()) {
+            *stats.node_failures.entry(node.clone()).or_default() += 1;
+            *stats.channel_failures.entry(channel.clone()).or_default() += 1;
+        }
+        stats
+    }
+}
+
 impl Simulation {
+    /// Tallies where the failed payments failed
+    pub fn eval_failures(&mut self) {
+        self.failure_stats = FailureStats::from_payments(&self.failed_payments);
+    }
+
     pub(crate) fn rerun_simulation(&self, targets: &[ID]) -> TargetedAttack {
         info!(
             "Simulating targeted node attacks for {:?}, {:?} of {} sats.",
@@ -41,17 +63,20 @@ impl Simulation {
             self.routing_metric,
             self.payment_parts
         );
-        let mut now = self.event_queue.now();
+        // schedule relative to the current simulation time
+        let mut delay = Time::from_secs(0.0);
+        let mut num_payments = 0;
         for (src, dest) in payment_pairs {
             let payment_id = self.next_payment_id();
             let invoice = Invoice::new(payment_id, self.amount, &src, &dest);
             self.add_invoice(invoice);
             let payment = Payment::new(payment_id, src, dest, self.amount, min_shard_amt);
             let event = PaymentEvent::Scheduled { payment };
-            self.event_queue.schedule(now, event);
-            now += Time::from_secs(crate::SIM_DELAY_IN_SECS);
+            self.event_queue.schedule(delay, event);
+            delay += Time::from_secs(self.payment_delay_secs);
+            num_payments += 1;
         }
-        self.total_num_payments = self.event_queue.queue_length();
+        self.total_num_payments = num_payments;
         debug!(
             "Queued {} events for simulation.",
             self.event_queue.queue_length()
@@ -66,16 +91,14 @@ impl Simulation {
                         payment.payment_id,
                         self.event_queue.now()
                     );
-                    match self.payment_parts {
-                        PaymentParts::Single => self.send_single_payment(&mut payment),
-                        PaymentParts::Split => self.send_mpp_payment(&mut payment),
-                    };
+                    self.send_payment(&mut payment);
                 }
                 PaymentEvent::UpdateFailed { payment } => {
                     self.num_failed += 1;
                     self.failed_payments.push(payment.to_owned());
                 }
                 PaymentEvent::UpdateSuccesful { payment } => {
+                    self.settle_htlcs(payment.payment_id);
                     self.num_successful += 1;
                     self.successful_payments.push(payment.to_owned());
                 }
@@ -100,14 +123,11 @@ impl Simulation {
 
     fn delete_targets(&mut self, targets: &[ID]) {
         trace!("Removed {} nodes from the graph.", targets.len());
-        for node in targets {
-            self.graph.remove_node(node);
-        }
+        self.graph
+            .remove_nodes(&targets.iter().cloned().collect::<HashSet<ID>>());
     }
 
-    fn reconstruct_payment_pairs(
-        &self,
-    ) -> ((impl Iterator<Item = (ID, ID)> + Clone), Option<usize>) {
+    fn reconstruct_payment_pairs(&self) -> (impl Iterator<Item = (ID, ID)> + Clone, Option<usize>) {
         let mut payment_pairs = vec![];
         let mut min_shard_amt = None;
         for payments_iter in self
@@ -145,6 +165,32 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn bottleneck_dominates_failures() {
+        let mut simulator = crate::attempt::tests::init_sim(None, Some(vec![0]));
+        // chan's channel towards dina is the only way to reach dina and can't forward the amount
+        // including fees
+        simulator
+            .graph
+            .update_channel_balance(&"chan".to_string(), &"chan2".to_string(), 1000);
+        let pairs = vec![
+            ("alice".to_string(), "dina".to_string()),
+            ("bob".to_string(), "dina".to_string()),
+            ("alice".to_string(), "chan".to_string()),
+        ];
+        let result = simulator.run(pairs.into_iter(), None, false);
+        let stats = result.failure_stats;
+        assert_eq!(result.num_failed, 2);
+        let (bottleneck, failures) = stats
+            .channel_failures
+            .iter()
+            .max_by_key(|(_, failures)| **failures)
+            .unwrap();
+        assert_eq!(bottleneck, "chan2");
+        assert_eq!(*failures, stats.channel_failures.values().sum::<usize>());
+        assert_eq!(stats.node_failures.get("chan"), Some(&2));
+    }
+
     #[test]
     fn delete_targets() {
         let mut simulator = crate::attempt::tests::init_sim(None, None);
@@ -218,7 +264,7 @@ mod tests {
             total_num: 2,
             num_successful: 2,
             num_failed: 0,
-            payments: vec![
+            payments: [
                 Payment {
                     payment_id: 0,
                     source: "alice".to_string(),
@@ -2,18 +2,21 @@ mod adversaries;
 mod deanonymisation;
 pub mod diversity;
 mod failures;
+mod probing;
+mod sender_liquidity;
 
-use crate::io::PaymentInfo;
-use serde::Serialize;
+use crate::{io::PaymentInfo, ID};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Adversaries {
     pub selection_strategy: crate::AdversarySelection,
     pub statistics: Vec<Statistics>,
 }
 
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Statistics {
     /// how many adversaries
@@ -40,26 +43,108 @@ pub struct Statistics {
 }
 
 /// All the distances in the simulated payments' paths
-#[derive(Debug, Default, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct PathDistances(pub Vec<usize>);
 
+impl PathDistances {
+    pub fn min(&self) -> Option<usize> {
+        self.0.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.0.iter().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.0.iter().sum::<usize>() as f32 / self.0.len() as f32)
+        }
+    }
+
+    pub fn median(&self) -> Option<f32> {
+        self.percentile(50.0)
+    }
+
+    /// The p-th percentile (0 <= p <= 100), linearly interpolated between the closest ranks
+    pub fn percentile(&self, p: f32) -> Option<f32> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut distances = self.0.clone();
+        distances.sort_unstable();
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (distances.len() - 1) as f32;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        let (lower_dist, upper_dist) = (distances[lower] as f32, distances[upper] as f32);
+        Some(lower_dist + (upper_dist - lower_dist) * (rank - lower as f32))
+    }
+}
+
 /// All the diversity scorres in the simulated payments' paths
-#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PathDiversity(pub Vec<Diversity>);
 
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct AnonymitySet {
     /// Possible senders
-    sender: usize,
+    pub(crate) sender: usize,
     /// Possible recipients
-    recipient: usize,
+    pub(crate) recipient: usize,
     /// True if the recipient is included in the recipient anonymity set
-    correct_recipient: bool,
-    correct_source: bool,
+    pub(crate) correct_recipient: bool,
+    pub(crate) correct_source: bool,
+    /// Value of the payment the sets were computed for
+    pub(crate) amount_msat: usize,
+    /// Entropy (bits) of the sender set assuming every possible sender is equally likely
+    #[serde(default)]
+    pub(crate) sender_entropy: f32,
+    /// Entropy (bits) of the recipient set assuming every possible recipient is equally likely
+    #[serde(default)]
+    pub(crate) recipient_entropy: f32,
+}
+
+impl AnonymitySet {
+    pub(crate) fn new(
+        sender: usize,
+        recipient: usize,
+        correct_recipient: bool,
+        correct_source: bool,
+        amount_msat: usize,
+    ) -> Self {
+        Self {
+            sender,
+            recipient,
+            correct_recipient,
+            correct_source,
+            amount_msat,
+            sender_entropy: Self::entropy(sender),
+            recipient_entropy: Self::entropy(recipient),
+        }
+    }
+
+    /// log2 of the set size; an empty set carries no uncertainty either
+    fn entropy(set_size: usize) -> f32 {
+        if set_size == 0 {
+            0.0
+        } else {
+            (set_size as f32).log2()
+        }
+    }
+}
+
+/// How often each node and channel was the failing hop of the failed payments' attempts
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureStats {
+    /// Number of failed attempts per node they failed at
+    pub node_failures: BTreeMap<ID, usize>,
+    /// Number of failed attempts per channel they failed at
+    pub channel_failures: BTreeMap<String, usize>,
 }
 
-#[derive(Debug, Serialize, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct TargetedAttack {
     pub total_num: usize,
@@ -69,10 +154,56 @@ pub struct TargetedAttack {
     pub path_distances: PathDistances,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+/// Total graph diversity for a number of shortest paths and a lambda
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphDiversity {
+    pub lambda: f32,
+    /// The number of paths we searched for
+    pub k: usize,
+    pub diversity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Diversity {
     pub lambda: f32,
     /// one value for each set of paths
     pub diversity: Vec<f32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymity_set_entropy() {
+        let anonymity_set = AnonymitySet::new(4, 1, true, false, 1000);
+        assert_eq!(anonymity_set.sender_entropy, 2.0);
+        assert_eq!(anonymity_set.recipient_entropy, 0.0);
+        let anonymity_set = AnonymitySet::new(0, 32, false, false, 1000);
+        assert_eq!(anonymity_set.sender_entropy, 0.0);
+        assert_eq!(anonymity_set.recipient_entropy, 5.0);
+    }
+
+    #[test]
+    fn path_distance_summaries() {
+        let distances = PathDistances(vec![4, 1, 3, 2, 7, 5]);
+        assert_eq!(distances.min(), Some(1));
+        assert_eq!(distances.max(), Some(7));
+        assert_eq!(distances.mean(), Some(22.0 / 6.0));
+        assert_eq!(distances.median(), Some(3.5));
+        assert_eq!(distances.percentile(50.0), distances.median());
+        assert_eq!(distances.percentile(0.0), Some(1.0));
+        assert_eq!(distances.percentile(100.0), Some(7.0));
+        let odd = PathDistances(vec![2, 9, 4]);
+        assert_eq!(odd.percentile(50.0), Some(4.0));
+        assert_eq!(odd.percentile(50.0), odd.median());
+        let empty = PathDistances::default();
+        assert_eq!(empty.min(), None);
+        assert_eq!(empty.max(), None);
+        assert_eq!(empty.mean(), None);
+        assert_eq!(empty.median(), None);
+        assert_eq!(empty.percentile(90.0), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    payment::Payment,
+    traversal::pathfinding::{CandidatePath, Path, PathFinder},
+    Simulation, ID,
+};
+
+#[cfg(not(test))]
+use log::{debug, info};
+#[cfg(test)]
+use std::{println as info, println as debug};
+
+/// Probes never come with an invoice so that they are guaranteed to fail at the recipient
+const PROBE_PAYMENT_ID: usize = usize::MAX;
+
+impl Simulation {
+    /// Estimates the balance of the channel from src to dest by actively probing it.
+    /// Performs a binary search for the largest amount in [0, upper_bound] that can be forwarded
+    /// through the channel. Probes are never settled so the simulation's balances are unaffected.
+    pub fn estimate_channel_balance(
+        &self,
+        src: &ID,
+        dest: &ID,
+        channel_id: &ID,
+        upper_bound: usize,
+    ) -> usize {
+        info!(
+            "Probing channel {} from {} to {} with upper bound {} msat.",
+            channel_id, src, dest, upper_bound
+        );
+        let mut sim = self.clone();
+        let (mut lower, mut upper) = (0, upper_bound);
+        while lower < upper {
+            // round up so that the search terminates
+            let amount = lower + (upper - lower).div_ceil(2);
+            if sim.probe_channel(src, dest, channel_id, amount) {
+                lower = amount;
+            } else {
+                upper = amount - 1;
+            }
+        }
+        debug!(
+            "Estimated balance of channel {}: {} msat.",
+            channel_id, lower
+        );
+        lower
+    }
+
+    /// Sends an unpayable HTLC through the channel and reverts it.
+    /// Returns true if the HTLC made it past the channel, i.e. failed at the recipient.
+    fn probe_channel(&mut self, src: &ID, dest: &ID, channel_id: &ID, amount: usize) -> bool {
+        let return_channel = self
+            .graph
+            .get_edge(dest, src)
+            .map(|e| e.channel_id)
+            .unwrap_or_default();
+        let mut path = Path::new(src.clone(), dest.clone());
+        path.hops
+            .push_back((src.clone(), amount, 0, channel_id.clone()));
+        path.hops
+            .push_back((dest.clone(), amount, 0, return_channel));
+        let mut candidate_path = CandidatePath {
+            amount,
+            ..CandidatePath::new_with_path(path)
+        };
+        let graph = self.graph.clone();
+        let mut path_finder = PathFinder::new(
+            src.clone(),
+            dest.clone(),
+            amount,
+            &graph,
+            self.routing_metric,
+            self.payment_parts,
+        );
+        let mut probe = Payment::new(PROBE_PAYMENT_ID, src.clone(), dest.clone(), amount, None)
+            .to_shard(amount);
+        let (_, transferred) = self.attempt_payment(
+            &mut probe,
+            &mut candidate_path,
+            &mut path_finder,
+            self.payment_parts,
+        );
+        // funds are only moved if the channel was able to forward the probe
+        let passed = !transferred.is_empty();
+        self.revert_payment(&transferred);
+        debug!("Probe of {} msat passed: {}.", amount, passed);
+        passed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn estimate_known_channel_balance() {
+        let simulator = crate::attempt::tests::init_sim(None, None);
+        let (src, dest, channel_id) =
+            ("alice".to_string(), "bob".to_string(), "alice1".to_string());
+        let balance = simulator.graph.get_channel_balance(&src, &channel_id);
+        let estimate = simulator.estimate_channel_balance(&src, &dest, &channel_id, 10000);
+        // a channel can forward strictly less than its balance
+        assert!(balance - estimate <= 1);
+        // probing leaves balances untouched
+        assert_eq!(
+            simulator.graph.get_channel_balance(&src, &channel_id),
+            balance
+        );
+        let estimate = simulator.estimate_channel_balance(&src, &dest, &channel_id, 1000);
+        assert_eq!(estimate, 1000);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{payment::Payment, Invoice, Simulation, ID};
+
+#[cfg(not(test))]
+use log::{debug, info};
+#[cfg(test)]
+use std::{println as info, println as debug};
+
+impl Simulation {
+    /// Success rate of payments from `src` to each of `dests` when all of the sender's outbound
+    /// channels hold `balance` msat, for each of the `balance_levels`.
+    /// Every level is evaluated on a fresh copy of the simulation so the simulation's state is
+    /// unaffected; payments within a level are sent one after another and share the liquidity.
+    pub fn success_vs_sender_balance(
+        &self,
+        src: &ID,
+        dests: &[ID],
+        balance_levels: &[usize],
+    ) -> Vec<(usize, f32)> {
+        info!(
+            "Measuring success rate of {} payments from {} for {} balance levels.",
+            dests.len(),
+            src,
+            balance_levels.len()
+        );
+        balance_levels
+            .iter()
+            .map(|balance| {
+                let mut sim = self.clone();
+                for edge in sim.graph.get_outedges(src) {
+                    sim.graph
+                        .update_channel_balance(src, &edge.channel_id, *balance);
+                }
+                let num_successful = dests
+                    .iter()
+                    .filter(|dest| sim.send_payment_to(src, dest))
+                    .count();
+                let success_rate = if dests.is_empty() {
+                    0.0
+                } else {
+                    num_successful as f32 / dests.len() as f32
+                };
+                debug!(
+                    "Success rate with sender balance {} msat: {}.",
+                    balance, success_rate
+                );
+                (*balance, success_rate)
+            })
+            .collect()
+    }
+
+    /// Issues an invoice and immediately sends the simulation's amount from src to dest.
+    fn send_payment_to(&mut self, src: &ID, dest: &ID) -> bool {
+        let payment_id = self.next_payment_id();
+        let invoice = Invoice::new(payment_id, self.amount, src, dest);
+        self.add_invoice(invoice);
+        let mut payment = Payment::new(payment_id, src.clone(), dest.clone(), self.amount, None);
+        self.send_payment(&mut payment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn success_rises_with_sender_balance() {
+        let simulator = crate::attempt::tests::init_sim(None, None);
+        let src = String::from("alice");
+        let dests: Vec<String> = ["bob", "chan", "dina", "bob"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        let levels = [500, 2500, 4711];
+        let actual = simulator.success_vs_sender_balance(&src, &dests, &levels);
+        assert_eq!(
+            actual.iter().map(|(b, _)| *b).collect::<Vec<usize>>(),
+            levels
+        );
+        // the sender cannot even afford a single payment
+        assert_eq!(actual[0].1, 0.0);
+        assert!(actual.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!(actual[2].1 > actual[1].1);
+        // the simulation's own balances are untouched
+        assert_eq!(
+            simulator
+                .graph
+                .get_channel_balance(&src, &String::from("alice1")),
+            4711
+        );
+    }
+}
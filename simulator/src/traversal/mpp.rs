@@ -1,9 +1,10 @@
 use crate::{
-    core_types::{event::PaymentEvent, time::Time},
-    payment::Payment,
+    core_types::event::PaymentEvent,
+    payment::{FailureReason, Payment},
     traversal::pathfinding::{CandidatePath, PathFinder},
-    Simulation,
+    PaymentParts, Simulation,
 };
+use std::time::Instant;
 
 #[cfg(not(test))]
 use log::{error, info, trace};
@@ -14,14 +15,22 @@ impl Simulation {
     /// Sends an MPP and fails when payment can no longer be split into smaller parts
     /// Triggers an event either way
     /// Includes pathfinding and ultimate routing
-    pub(crate) fn send_mpp_payment(&mut self, payment: &mut Payment) -> bool {
+    /// `payment_parts` decides whether the payment is split or sent in redundant shards
+    pub(crate) fn send_mpp_payment(
+        &mut self,
+        payment: &mut Payment,
+        payment_parts: PaymentParts,
+    ) -> bool {
         let mut succeeded = false;
         let mut failed = false;
+        self.make_room_for_payment();
         let graph = Box::new(self.graph.clone());
+        let balances_before = self.balance_delta_snapshot();
         // fail immediately if sender's total balance < amount
         let total_out_balance = graph.get_total_node_balance(&payment.source);
         if total_out_balance < payment.amount_msat {
             error!("Payment failing. {} total balance insufficient for payment. Amount {}, max balance {}", payment.source, payment.amount_msat, total_out_balance);
+            payment.failure_reason = Some(FailureReason::InsufficientSenderBalance);
             payment.htlc_attempts += 1;
             failed = true;
         }
@@ -30,6 +39,7 @@ impl Simulation {
             let max_receive_balance = graph.get_max_receive_amount(&payment.dest);
             if max_receive_balance < payment.amount_msat {
                 error!("Payment failing due to insufficient receive capacity. Payment amount {}, max receive {}", payment.amount_msat, max_receive_balance);
+                payment.failure_reason = Some(FailureReason::DestinationCapacity);
                 payment.htlc_attempts += 1;
                 failed = true;
             }
@@ -38,11 +48,23 @@ impl Simulation {
         if !succeeded && !failed {
             payment.used_paths.clear();
             payment.num_parts = 0;
-            succeeded = self.send_mpp_shards(payment);
+            // resolved before splitting so that the shards share the budget rather than each
+            // falling back to all of it
+            payment.max_fee_msat = payment.max_fee_msat.or(self.max_fee_msat);
+            succeeded = match payment_parts {
+                PaymentParts::Redundant { overhead } => {
+                    self.send_redundant_shards(payment, overhead)
+                }
+                _ => self.send_mpp_shards(payment, payment_parts),
+            };
+        }
+        self.hold_in_flight_htlcs(payment.payment_id, succeeded);
+        if let Some(balances_before) = balances_before {
+            payment.balance_deltas = self.balance_deltas_since(&balances_before);
         }
-        let now = self.event_queue.now() + Time::from_secs(crate::SIM_DELAY_IN_SECS);
         let event = if succeeded {
             assert!(payment.succeeded);
+            self.fulfil_invoice(payment);
             info!(
                 "Payment from {} to {} delivered in {} parts.",
                 payment.source, payment.dest, payment.num_parts
@@ -57,12 +79,13 @@ impl Simulation {
                 payment: payment.to_owned(),
             }
         };
-        self.event_queue.schedule(now, event);
+        self.event_queue
+            .schedule(self.settlement_delay(payment), event);
         succeeded
     }
 
     /// Splits a payment into a list of shards belonging to one payment and tries to send them atomically
-    fn send_mpp_shards(&mut self, root: &mut Payment) -> bool {
+    fn send_mpp_shards(&mut self, root: &mut Payment, payment_parts: PaymentParts) -> bool {
         trace!(
             "Attempting MPP payment {} worth {} msat.",
             root.payment_id,
@@ -71,15 +94,34 @@ impl Simulation {
         let mut succeeded = false;
         let mut failed = false;
         let mut stack = vec![];
-        stack.push(root.clone());
+        // the first shard starts without the root's attempts, e.g. those of a failed single path
+        // payment, so that they are not counted twice
+        stack.push(Payment {
+            htlc_attempts: 0,
+            route_attempts: 0,
+            pathfinding_ms: 0,
+            failed_paths: vec![],
+            failure_hops: vec![],
+            failing_channels: vec![],
+            ..root.clone()
+        });
         let mut num_parts = 0;
+        // the value of successful parts tells us if the entire payment succeeded
+        let mut amount_received = 0;
         while let Some(mut current_shard) = stack.pop() {
             if !succeeded && !failed {
                 num_parts += 1;
-                let (success, mut to_reverse) = self.send_one_payment(&mut current_shard);
+                let (success, mut to_reverse) =
+                    self.send_one_payment(&mut current_shard, payment_parts);
                 root.htlc_attempts += current_shard.htlc_attempts;
+                root.route_attempts += current_shard.route_attempts;
+                root.pathfinding_ms += current_shard.pathfinding_ms;
                 root.failed_paths.append(&mut current_shard.failed_paths);
+                root.failure_hops.append(&mut current_shard.failure_hops);
+                root.failing_channels
+                    .append(&mut current_shard.failing_channels);
                 if !success && !failed {
+                    root.failure_reason = current_shard.failure_reason;
                     root.failed_amounts.push(current_shard.amount_msat);
                     trace!(
                         "Splitting payment {} worth {} msat into {} parts.",
@@ -92,6 +134,7 @@ impl Simulation {
                             "Aborting splitting as max parts of {} has been reached.",
                             crate::MAX_PARTS
                         );
+                        root.failure_reason = Some(FailureReason::SplitLimit);
                         failed = true;
                     } else if let Some(shards) = Payment::split_payment(&current_shard) {
                         let (mut shard1, mut shard2) = (shards.0, shards.1);
@@ -101,24 +144,27 @@ impl Simulation {
                         stack.push(shard2);
                     } else {
                         // Splitting failed so we know at least some part wont succeed
+                        root.failure_reason = Some(FailureReason::SplitLimit);
                         failed = true;
                     }
                 } else if success {
+                    amount_received += current_shard.amount_msat;
                     root.num_parts += 1;
                     root.used_paths
                         .append(&mut current_shard.used_paths.clone());
                     root.successful_shards.append(&mut to_reverse);
                 }
             }
-            // the value of successful parts tells us if the entire payment succeeded
-            let mut amount_received = 0;
-            for s in root.successful_shards.iter() {
-                if s.0 == root.dest {
-                    amount_received += s.2;
-                }
-            }
-            if amount_received == root.amount_msat {
+            if amount_received == root.amount_msat && self.invoice_underpaid(root) {
+                error!(
+                    "Payment {} failing. Parts worth {} msat do not cover the invoice.",
+                    root.payment_id, amount_received
+                );
+                root.failure_reason = Some(FailureReason::InvoiceUnderpaid);
+                failed = true;
+            } else if amount_received == root.amount_msat {
                 root.succeeded = true;
+                root.failure_reason = None;
                 succeeded = true;
                 // no longer needed - used to revert payments
                 root.successful_shards.clear();
@@ -133,12 +179,165 @@ impl Simulation {
         }
         succeeded
     }
+
+    /// Sends the halves of the payment and `overhead` times as many extra shards of the same
+    /// amount, each along its own node-disjoint path. For an odd amount, the shard completing the
+    /// payment carries one msat less than the others. Shards are not retried; instead the payment
+    /// succeeds as soon as the delivered shards cover the amount and later shards are cancelled.
+    fn send_redundant_shards(&mut self, root: &mut Payment, overhead: f32) -> bool {
+        let required_parts = 2;
+        let shard_amount = root.amount_msat.div_ceil(required_parts);
+        let num_shards = (required_parts as f32 * (1.0 + overhead.max(0.0))).ceil() as usize;
+        trace!(
+            "Attempting redundant payment {} worth {} msat in {} shards of {} msat.",
+            root.payment_id,
+            root.amount_msat,
+            num_shards,
+            shard_amount
+        );
+        let graph = self.graph.clone();
+        let mut path_finder = PathFinder::new(
+            root.source.clone(),
+            root.dest.clone(),
+            shard_amount,
+            &graph,
+            self.routing_metric,
+            PaymentParts::Redundant { overhead },
+        );
+        path_finder.set_max_total_cltv(self.max_total_cltv);
+        path_finder.set_risk_factor(self.risk_factor);
+        path_finder.set_channel_priors(self.channel_priors.clone());
+        path_finder
+            .graph
+            .set_edges(PathFinder::remove_inadequate_edges(&graph, shard_amount));
+        let start = Instant::now();
+        let paths = path_finder.find_disjoint_paths(num_shards);
+        root.pathfinding_ms += start.elapsed().as_millis();
+        // each shard may spend its share of the budget
+        let shard_budget = root
+            .max_fee_msat
+            .map(|max_fee| max_fee * shard_amount / root.amount_msat);
+        let mut amount_received = 0;
+        for mut candidate_path in paths {
+            // the shard that completes the payment only carries the rest of the amount
+            let remaining = root.amount_msat.saturating_sub(amount_received);
+            let amount = if remaining > 0 {
+                shard_amount.min(remaining)
+            } else {
+                shard_amount
+            };
+            if amount != shard_amount {
+                path_finder.amount = amount;
+                path_finder.get_aggregated_path_cost(&mut candidate_path, false);
+            }
+            if shard_budget.is_some_and(|max_fee| candidate_path.path_fees() > max_fee) {
+                error!(
+                    "Payment {} rejecting shard path with {} msat fees exceeding the budget of {:?} msat.",
+                    root.payment_id, candidate_path.path_fees(), shard_budget
+                );
+                root.failure_reason = Some(FailureReason::FeeBudgetExceeded);
+                root.failed_paths.push(candidate_path);
+                continue;
+            }
+            self.record_node_hits(&candidate_path);
+            // the shard starts without the root's attempts so that they are not counted twice
+            let mut shard = Payment {
+                htlc_attempts: 0,
+                failure_hops: vec![],
+                failing_channels: vec![],
+                ..root.clone()
+            }
+            .to_shard(amount);
+            // the shard is cancelled if the payment is complete before it arrives
+            let surplus = amount_received >= root.amount_msat;
+            let num_in_flight = self.in_flight_htlcs.len();
+            let (success, mut transferred) = if self.dry_run {
+                (true, Vec::new())
+            } else {
+                self.attempt_payment(
+                    &mut shard,
+                    &mut candidate_path,
+                    &mut path_finder,
+                    PaymentParts::Redundant { overhead },
+                )
+            };
+            root.htlc_attempts += shard.htlc_attempts;
+            if surplus {
+                trace!("Cancelling surplus shard of payment {}.", root.payment_id);
+                self.revert_payment(&transferred);
+                // the HTLCs of a delivered shard are in flight and have to be released, too
+                let cancelled = self.in_flight_htlcs.split_off(num_in_flight);
+                self.release_htlcs(&cancelled);
+            } else if !success {
+                self.revert_payment(&transferred);
+                root.failure_hops.append(&mut shard.failure_hops);
+                root.failing_channels.append(&mut shard.failing_channels);
+                root.failure_reason = shard.failure_reason;
+                root.failed_amounts.push(amount);
+                root.failed_paths.push(candidate_path);
+            } else {
+                amount_received += amount;
+                root.num_parts += 1;
+                root.used_paths.push(candidate_path);
+                root.successful_shards.append(&mut transferred);
+            }
+        }
+        let succeeded = amount_received >= root.amount_msat;
+        if succeeded {
+            root.succeeded = true;
+            root.failure_reason = None;
+            root.successful_shards.clear();
+        } else {
+            error!(
+                "Redundant payment {} failing. Shards delivered {} of {} msat.",
+                root.payment_id, amount_received, root.amount_msat
+            );
+            root.failure_reason.get_or_insert(FailureReason::NoRoute);
+            self.revert_payment(&root.successful_shards);
+            root.used_paths.clear();
+        }
+        succeeded
+    }
+
+    /// Whether the invoice amount is enforced and the parts of the payment sum to less
+    fn invoice_underpaid(&self, payment: &Payment) -> bool {
+        self.enforce_invoice_amount
+            && self
+                .get_invoice_amount(&payment.dest, payment.payment_id)
+                .is_some_and(|amount| payment.amount_msat < amount)
+    }
 }
 
 impl PathFinder {
     pub(super) fn find_path_mpp_payment(&mut self) -> Option<CandidatePath> {
         self.find_path_single_payment()
     }
+
+    /// Returns up to num_paths paths which share neither intermediaries nor channels, cheapest
+    /// first.
+    pub(crate) fn find_disjoint_paths(&mut self, num_paths: usize) -> Vec<CandidatePath> {
+        let (excluded_nodes, excluded_channels) =
+            (self.excluded_nodes.clone(), self.excluded_channels.clone());
+        let mut paths = Vec::new();
+        while paths.len() < num_paths {
+            let Some(candidate_path) = self.find_path_single_payment() else {
+                break;
+            };
+            let hops = &candidate_path.path.hops;
+            self.excluded_nodes.extend(
+                hops.iter()
+                    .take(hops.len() - 1)
+                    .skip(1)
+                    .map(|hop| hop.0.clone()),
+            );
+            self.excluded_channels
+                .extend(hops.iter().map(|hop| hop.3.clone()));
+            paths.push(candidate_path);
+        }
+        self.excluded_nodes = excluded_nodes;
+        self.excluded_channels = excluded_channels;
+        paths
+    }
 }
 
 #[cfg(test)]
@@ -156,30 +355,28 @@ mod tests {
         let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
         let amount_msat = 300000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         assert!(!simulator.send_single_payment(payment));
-        simulator
-            .graph
-            .update_channel_balance(&String::from("alice-carol"), 100000);
-        simulator
-            .graph
-            .update_channel_balance(&String::from("alice-dave"), 250000);
+        simulator.graph.update_channel_balance(
+            &String::from("alice"),
+            &String::from("alice-carol"),
+            100000,
+        );
+        simulator.graph.update_channel_balance(
+            &String::from("alice"),
+            &String::from("alice-dave"),
+            250000,
+        );
 
         simulator.payment_parts = PaymentParts::Split;
-        simulator.send_mpp_payment(payment);
+        simulator.send_mpp_payment(payment, simulator.payment_parts);
         assert!(payment.num_parts > 1);
     }
 
@@ -196,30 +393,33 @@ mod tests {
         for edges in simulator.graph.edges.values_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
+        // the shards' HTLCs are all forwarded by carol and locked until the payment completes
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-alice"),
+            2 * balance,
+        );
         let amount_msat = 12000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         simulator.payment_parts = PaymentParts::Single;
         assert!(!simulator.send_single_payment(payment));
         simulator.payment_parts = PaymentParts::Split;
-        assert!(simulator.send_mpp_payment(payment));
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
         assert!(payment.succeeded);
         assert!(payment.num_parts > 1);
+        // the invoice is fulfilled once all parts have been delivered
+        assert!(simulator.get_invoices_for_node(&dest).is_none());
     }
 
     #[test]
@@ -235,41 +435,83 @@ mod tests {
         for edges in simulator.graph.edges.values_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let bob_eve_channel = String::from("bob-eve");
         let bob_carol_channel = String::from("bob-carol");
         let bob_dave_channel = String::from("bob-dave");
         let bob_total_balance = 15000;
-        simulator
-            .graph
-            .update_channel_balance(&bob_eve_channel, bob_total_balance / 3);
-        simulator
-            .graph
-            .update_channel_balance(&bob_carol_channel, bob_total_balance / 3);
-        simulator
-            .graph
-            .update_channel_balance(&bob_dave_channel, bob_total_balance / 3);
+        simulator.graph.update_channel_balance(
+            &String::from("bob"),
+            &bob_eve_channel,
+            bob_total_balance / 3,
+        );
+        simulator.graph.update_channel_balance(
+            &String::from("bob"),
+            &bob_carol_channel,
+            bob_total_balance / 3,
+        );
+        simulator.graph.update_channel_balance(
+            &String::from("bob"),
+            &bob_dave_channel,
+            bob_total_balance / 3,
+        );
         let amount_msat = 12000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         simulator.payment_parts = PaymentParts::Single;
         assert!(!simulator.send_single_payment(payment));
         simulator.payment_parts = PaymentParts::Split;
-        assert!(!simulator.send_mpp_payment(payment));
+        assert!(!simulator.send_mpp_payment(payment, simulator.payment_parts));
+    }
+
+    #[test]
+    fn partially_delivered_mpp_payment_is_reported() {
+        let json_file = "../test_data/trivial_multipath.json";
+        let source = "bob".to_string();
+        let dest = "alice".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        let balance = 10000;
+        for edges in simulator.graph.edges.values_mut() {
+            for e in edges {
+                e.balance = balance;
+                e.liquidity = balance;
+            }
+        }
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-alice"),
+            2 * balance,
+        );
+        // only bob -> carol -> alice can carry a half but not both
+        simulator
+            .graph
+            .update_channel_balance(&source, &String::from("bob-eve"), 1000);
+        simulator
+            .graph
+            .update_channel_balance(&source, &String::from("bob-dave"), 1000);
+        let amount_msat = 12000;
+        // the halves cannot be split any further
+        let mut payment = Payment::new(0, source.clone(), dest.clone(), amount_msat, Some(6000));
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        simulator.payment_parts = PaymentParts::Split;
+        assert!(!simulator.send_mpp_payment(&mut payment, simulator.payment_parts));
+        let info = crate::io::PaymentInfo::from_payment(&payment);
+        assert!(!info.succeeded);
+        assert_eq!(info.delivered_msat, amount_msat / 2);
+        assert_eq!(
+            info.failed_msat,
+            payment.failed_amounts.iter().sum::<usize>()
+        );
+        assert!(info.failed_msat >= amount_msat / 2);
     }
 
     #[test]
@@ -282,27 +524,31 @@ mod tests {
         for edges in simulator.graph.edges.values_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
+        // the shards' HTLCs are all forwarded by carol and locked until the payment completes
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-alice"),
+            2 * balance,
+        );
         let amount_msat = 12000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
-            succeeded: false,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         assert!(!simulator.send_single_payment(payment));
         simulator.payment_parts = PaymentParts::Split;
-        assert!(simulator.send_mpp_payment(payment));
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
+        // estimated success probability of a hop given its capacity
+        let success_prob =
+            |capacity: f32, amount: f32| (capacity + 1.0 - amount) / (capacity + 1.0);
         let expected_used_path = vec![
             CandidatePath {
                 path: Path {
@@ -317,6 +563,8 @@ mod tests {
                 weight: 10.0,
                 amount: 6010,
                 time: 5,
+                hold_time_secs: 2.0,
+                hop_probabilities: vec![success_prob(235000.0, 6000.0)],
             },
             CandidatePath {
                 path: Path {
@@ -332,6 +580,11 @@ mod tests {
                 weight: 30.0,
                 amount: 6030,
                 time: 10,
+                hold_time_secs: 3.0,
+                hop_probabilities: vec![
+                    success_prob(135000.0, 6010.0),
+                    success_prob(235000.0, 6000.0),
+                ],
             },
         ];
         assert_eq!(payment.htlc_attempts, 5);
@@ -341,4 +594,229 @@ mod tests {
         assert!(payment.failed_paths.is_empty()); // since the single payment fails immediately
         assert_eq!(expected_used_path, payment.used_paths);
     }
+
+    #[test]
+    fn dry_run_leaves_balances_untouched() {
+        let json_file = "../test_data/trivial_multipath.json";
+        let mut simulator =
+            crate::attempt::tests::init_sim(Some(json_file.to_string()), Some(vec![0]));
+        simulator.payment_parts = PaymentParts::Split;
+        simulator.set_dry_run(true);
+        // the amount has to be split and only the smaller of the two shards (4501 and 4500 msat)
+        // fits through eve
+        simulator.amount = 9001;
+        simulator.graph.update_channel_balance(
+            &String::from("alice"),
+            &String::from("alice-carol"),
+            10000,
+        );
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-eve"),
+            4500,
+        );
+        let balances = simulator.graph.get_edge_balances();
+        let pairs = vec![("alice".to_string(), "bob".to_string()); 3];
+        let result = simulator.run(pairs.into_iter(), None, false);
+        assert_eq!(result.num_succesful, 3);
+        assert!(result
+            .successful_payments
+            .iter()
+            .all(|payment| payment.num_parts > 1));
+        assert_eq!(simulator.graph.get_edge_balances(), balances);
+        assert!(!result.path_distances.0.is_empty());
+    }
+
+    #[test]
+    fn redundant_shards_cover_a_failing_shard() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "eve", 10),
+            ("alice", "carol", 0),
+            ("carol", "eve", 5),
+            ("alice", "dave", 0),
+            ("dave", "eve", 20),
+            ("alice", "frank", 0),
+            ("frank", "eve", 30),
+        ]);
+        // the cheapest path fails as carol cannot forward the shard to eve
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-eve"),
+            1000,
+        );
+        simulator.payment_parts = PaymentParts::Redundant { overhead: 1.0 };
+        let (source, dest) = ("alice".to_string(), "eve".to_string());
+        let amount_msat = 2000;
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        let payment = &mut Payment::new(0, source, dest.clone(), amount_msat, Some(10));
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
+        assert_eq!(payment.num_parts, 2);
+        assert_eq!(payment.failed_paths.len(), 1);
+        assert_eq!(
+            payment.failed_paths[0].path.get_involved_nodes(),
+            vec!["alice", "carol", "eve"]
+        );
+        let receive_balance = |channel_id: &str| {
+            simulator
+                .graph
+                .get_channel_balance(&dest, &channel_id.to_string())
+        };
+        assert_eq!(receive_balance("bob-eve"), 11000);
+        assert_eq!(receive_balance("dave-eve"), 11000);
+        // the payment was complete before frank's shard arrived
+        assert_eq!(receive_balance("frank-eve"), 10000);
+        assert_eq!(receive_balance("carol-eve"), 10000);
+    }
+
+    #[test]
+    fn cancelled_surplus_shards_release_their_htlcs() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "eve", 10),
+            ("alice", "carol", 0),
+            ("carol", "eve", 5),
+            ("alice", "dave", 0),
+            ("dave", "eve", 20),
+            ("alice", "frank", 0),
+            ("frank", "eve", 30),
+        ]);
+        // the HTLCs of the delivered shards stay in flight until the payment settles
+        simulator.set_concurrency(2);
+        simulator.payment_parts = PaymentParts::Redundant { overhead: 1.0 };
+        let (source, dest) = ("alice".to_string(), "eve".to_string());
+        let amount_msat = 2000;
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        let payment = &mut Payment::new(0, source, dest, amount_msat, Some(10));
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
+        assert_eq!(payment.num_parts, 2);
+        assert!(payment.failed_paths.is_empty());
+        assert!(payment.failed_amounts.is_empty());
+        let liquidity = |node: &str, channel_id: &str| {
+            let (node, channel_id) = (node.to_string(), channel_id.to_string());
+            (
+                simulator.graph.get_channel_balance(&node, &channel_id),
+                simulator.graph.get_channel_liquidity(&node, &channel_id),
+            )
+        };
+        for (node, channel_id) in [("carol", "carol-eve"), ("bob", "bob-eve")] {
+            let (balance, liquidity) = liquidity(node, channel_id);
+            assert_eq!(liquidity, balance - amount_msat / 2);
+        }
+        // the surplus shards were cancelled
+        for (node, channel_id) in [("dave", "dave-eve"), ("frank", "frank-eve")] {
+            let (balance, liquidity) = liquidity(node, channel_id);
+            assert_eq!(liquidity, balance);
+        }
+    }
+
+    #[test]
+    fn redundant_shards_deliver_the_exact_amount() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "eve", 10),
+            ("alice", "carol", 0),
+            ("carol", "eve", 5),
+            ("alice", "dave", 0),
+            ("dave", "eve", 20),
+            ("alice", "frank", 0),
+            ("frank", "eve", 30),
+        ]);
+        simulator.payment_parts = PaymentParts::Redundant { overhead: 1.0 };
+        let (source, dest) = ("alice".to_string(), "eve".to_string());
+        let amount_msat = 2001;
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        let payment = &mut Payment::new(0, source, dest.clone(), amount_msat, Some(10));
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
+        assert_eq!(payment.num_parts, 2);
+        let receive_balance = |channel_id: &str| {
+            simulator
+                .graph
+                .get_channel_balance(&dest, &channel_id.to_string())
+        };
+        // the cheapest path carries the larger half, the second one only the rest
+        assert_eq!(receive_balance("carol-eve"), 11001);
+        assert_eq!(receive_balance("bob-eve"), 11000);
+        assert_eq!(receive_balance("dave-eve"), 10000);
+        assert_eq!(receive_balance("frank-eve"), 10000);
+        let delivered: usize = payment.used_paths.iter().map(|p| p.path_amount()).sum();
+        assert_eq!(delivered, amount_msat);
+    }
+
+    #[test]
+    fn shards_share_the_simulation_fee_budget() {
+        let send = |max_fee_msat| {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            // no channel can carry the whole amount and each path costs 10 msat in fees
+            simulator.graph = crate::attempt::tests::graph_of_channels(&[
+                ("alice", "bob", 0),
+                ("bob", "dave", 10),
+                ("alice", "carol", 0),
+                ("carol", "dave", 10),
+            ]);
+            simulator.payment_parts = PaymentParts::Split;
+            simulator.set_max_fee_msat(max_fee_msat);
+            let (source, dest) = ("alice".to_string(), "dave".to_string());
+            let amount_msat = 15000;
+            simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+            let mut payment = Payment::new(0, source, dest, amount_msat, None);
+            let succeeded = simulator.send_mpp_payment(&mut payment, simulator.payment_parts);
+            (succeeded, payment)
+        };
+        let (succeeded, payment) = send(20);
+        assert!(succeeded);
+        assert_eq!(payment.num_parts, 2);
+        let fees: usize = payment.used_paths.iter().map(|p| p.path_fees()).sum();
+        assert!(fees <= 20);
+        // either shard alone would fit into the whole budget
+        let (succeeded, payment) = send(15);
+        assert!(!succeeded);
+        assert!(payment.used_paths.is_empty());
+    }
+
+    #[test]
+    fn redundant_shards_share_the_fee_budget() {
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "eve", 10),
+            ("alice", "carol", 0),
+            ("carol", "eve", 5),
+            ("alice", "dave", 0),
+            ("dave", "eve", 20),
+            ("alice", "frank", 0),
+            ("frank", "eve", 30),
+        ]);
+        simulator.payment_parts = PaymentParts::Redundant { overhead: 1.0 };
+        // each of the two required shards may spend 15 msat
+        simulator.set_max_fee_msat(30);
+        let (source, dest) = ("alice".to_string(), "eve".to_string());
+        let amount_msat = 2000;
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        let payment = &mut Payment::new(0, source, dest.clone(), amount_msat, None);
+        assert!(simulator.send_mpp_payment(payment, simulator.payment_parts));
+        assert_eq!(payment.num_parts, 2);
+        let mut rejected: Vec<Vec<String>> = payment
+            .failed_paths
+            .iter()
+            .map(|p| p.path.get_involved_nodes())
+            .collect();
+        rejected.sort();
+        assert_eq!(
+            rejected,
+            vec![vec!["alice", "dave", "eve"], vec!["alice", "frank", "eve"]]
+        );
+        // the rejected shards were never sent
+        for channel_id in ["dave-eve", "frank-eve"] {
+            assert_eq!(
+                simulator
+                    .graph
+                    .get_channel_balance(&dest, &channel_id.to_string()),
+                10000
+            );
+        }
+    }
 }
@@ -1,11 +1,15 @@
 use crate::{graph::Graph, Edge, EdgeWeight, PaymentParts, RoutingMetric, ID};
 
+use itertools::Itertools;
 use log::{debug, trace};
-use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
 
 /// Describes a path between two nodes
-#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Path {
     pub(crate) src: ID,
     pub(crate) dest: ID,
@@ -16,6 +20,10 @@ pub struct Path {
     pub hops: VecDeque<(ID, usize, usize, String)>,
 }
 
+/// Nodes of a path found by the search, each with the channel it is reached through. The
+/// channel of the first node is empty
+pub(crate) type Route = Vec<(ID, String)>;
+
 /// Pathfinding object
 #[derive(Debug, Clone)]
 pub struct PathFinder {
@@ -29,10 +37,32 @@ pub struct PathFinder {
     pub(super) amount: usize,
     pub(super) routing_metric: RoutingMetric,
     pub(super) payment_parts: PaymentParts,
+    /// Weights of the graph's edges for `amount` keyed by source and channel ID since both
+    /// directions of a channel may share its ID
+    pub(super) edge_weights: HashMap<ID, HashMap<String, EdgeWeight>>,
+    /// Nodes that are never routed through
+    pub(super) excluded_nodes: HashSet<ID>,
+    /// Channels that are never routed through
+    pub(super) excluded_channels: HashSet<String>,
+    /// Penalties of the channels around earlier failures of the payment keyed by channel ID
+    pub(super) failure_penalties: HashMap<String, f32>,
+    /// Paths whose accumulated timelock exceeds this many blocks are rejected
+    pub(super) max_total_cltv: usize,
+    /// Number of shortest paths single-path payments choose from; they are attempted in order,
+    /// skipping those the sender's own channels cannot fund
+    pub(super) candidate_pool: usize,
+    /// Candidates of the pool that have not been attempted yet, cheapest first. None until the
+    /// pool has been computed
+    pub(super) pending_candidates: Option<VecDeque<CandidatePath>>,
+    /// Weight of the timelock penalty when computing an edge's fee
+    pub(super) risk_factor: usize,
+    /// Success probabilities of channels learned from earlier payments keyed by channel ID.
+    /// Channels without an entry are not known to be unreliable
+    pub(super) channel_priors: HashMap<String, f32>,
 }
 
 /// A path that we may use to route from src to dest
-#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct CandidatePath {
     pub path: Path,
     /// The aggregated path weight (fees or probability) describing how costly the path is
@@ -41,6 +71,13 @@ pub struct CandidatePath {
     pub(crate) amount: usize,
     /// The aggregated timelock
     pub(crate) time: usize,
+    /// Simulated time the HTLCs of the path were held for when it was attempted
+    #[serde(default)]
+    pub(crate) hold_time_secs: f32,
+    /// Estimated success probability of each edge that is forwarded along, from the sender to
+    /// the receiver
+    #[serde(default)]
+    pub(crate) hop_probabilities: Vec<f32>,
 }
 
 impl Path {
@@ -54,6 +91,31 @@ impl Path {
         self.hops.iter().map(|h| h.0.clone()).collect()
     }
 
+    /// Returns the path from `dest` to `src` along the same nodes.
+    /// Each hop's channel is replaced by the channel in the reverse direction if the graph has
+    /// one. Fees and timelocks are not recomputed but stay with the node they belong to.
+    pub fn reverse(&self, graph: &Graph) -> Path {
+        let mut hops: VecDeque<_> = self.hops.iter().rev().cloned().collect();
+        // the last hop already names the channel towards its predecessor
+        for idx in 0..hops.len().saturating_sub(1) {
+            let (from, to) = (&hops[idx].0, &hops[idx + 1].0);
+            let forward = &self.hops[self.hops.len() - 2 - idx].3;
+            let edges = graph.get_all_src_dest_edges(from, to);
+            if let Some(edge) = edges
+                .iter()
+                .find(|e| e.channel_id == *forward)
+                .or(edges.first())
+            {
+                hops[idx].3 = edge.channel_id.clone();
+            }
+        }
+        Path {
+            src: self.dest.clone(),
+            dest: self.src.clone(),
+            hops,
+        }
+    }
+
     // will fail if node is the first hop
     pub(crate) fn get_pred(&self, node: &ID) -> ID {
         let node_pos = self.get_involved_nodes().iter().position(|n| n.eq(node));
@@ -117,40 +179,15 @@ impl Path {
         }
     }
 
-    /// Checks if the other path is a subpath of self
+    /// Checks if self is a subpath of the other path, i.e. if self's nodes appear in the other
+    /// path in the same order and without gaps
     pub(crate) fn is_subpath(&self, other: &Path) -> bool {
-        // find first matching element in
-        let mut is_subpath = false;
-        if self.hops.len() > other.hops.len() {
-            is_subpath = false;
-        } else {
-            let first_elem_in_both = self
+        let nodes = self.get_involved_nodes();
+        !nodes.is_empty()
+            && other
                 .get_involved_nodes()
-                .iter()
-                .position(|n| other.get_involved_nodes().contains(n));
-            match first_elem_in_both {
-                Some(idx) => {
-                    match other
-                        .get_involved_nodes()
-                        .iter()
-                        .position(|n| *n == self.hops[idx].0)
-                    {
-                        Some(i) => {
-                            // start at the next element since we know i == idx
-                            for (s, o) in (idx + 1..self.hops.len()).zip(i + 1..other.hops.len()) {
-                                if self.hops[s].0 == other.hops[o].0 {
-                                    is_subpath = true;
-                                    break;
-                                }
-                            }
-                        }
-                        None => is_subpath = false,
-                    }
-                }
-                None => is_subpath = false,
-            };
-        }
-        is_subpath
+                .windows(nodes.len())
+                .any(|window| window == nodes.as_slice())
     }
 
     pub(crate) fn is_equal(
@@ -192,9 +229,36 @@ impl Path {
         }
     }
 
+    /// The channel the hop forwards the payment through or an empty string if unknown
+    fn get_hop_channel(&self, hop_id: &ID) -> String {
+        self.hops
+            .iter()
+            .find(|h| h.0 == *hop_id)
+            .map(|h| h.3.clone())
+            .unwrap_or_default()
+    }
+
     pub(crate) fn path_length(&self) -> usize {
         self.hops.len() - 1 // hops includes src and dest
     }
+
+    /// The fees charged by the intermediaries. The src's and dest's hops carry amounts instead.
+    pub fn total_fees(&self) -> usize {
+        self.intermediaries().map(|hop| hop.1).sum()
+    }
+
+    /// The timelock deltas of the intermediaries, i.e. the total timelock of the path.
+    pub fn total_timelock(&self) -> usize {
+        self.intermediaries().map(|hop| hop.2).sum()
+    }
+
+    /// All hops but the src and dest
+    fn intermediaries(&self) -> impl Iterator<Item = &(ID, usize, usize, String)> {
+        self.hops
+            .iter()
+            .skip(1)
+            .take(self.hops.len().saturating_sub(2))
+    }
 }
 
 impl CandidatePath {
@@ -204,6 +268,8 @@ impl CandidatePath {
             weight: f32::default(),
             amount: usize::default(),
             time: usize::default(),
+            hold_time_secs: 0.0,
+            hop_probabilities: Vec::default(),
         }
     }
 
@@ -216,6 +282,31 @@ impl CandidatePath {
             0
         }
     }
+    /// True if the path has no hops, e.g. because it was rejected.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.path.hops.is_empty()
+    }
+    /// The estimated probability that the path succeeds, i.e. the product of the success
+    /// probabilities of the edges it forwards along.
+    pub fn success_probability(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        self.hop_probabilities.iter().product()
+    }
+    /// True if the node is the sender, the receiver or an intermediary of the path.
+    pub fn contains_node(&self, id: &ID) -> bool {
+        self.path.hops.iter().any(|hop| hop.0 == *id)
+    }
+    /// True if the path forwards along the channel.
+    pub fn contains_channel(&self, channel_id: &str) -> bool {
+        // the receiver's hop only names the channel's other direction
+        self.path
+            .hops
+            .iter()
+            .take(self.path.hops.len().saturating_sub(1))
+            .any(|hop| hop.3 == channel_id)
+    }
     /// Returns the amount that was trasferred by this path.
     pub(crate) fn path_amount(&self) -> usize {
         if !self.path.hops.is_empty() {
@@ -226,6 +317,27 @@ impl CandidatePath {
     }
 }
 
+/// Renders the path as `src -> n1 (fee) -> ... -> dest` followed by its totals.
+impl fmt::Display for CandidatePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "{} -> {}: no hops", self.path.src, self.path.dest);
+        }
+        write!(f, "{}", self.path.src)?;
+        for (node, fee, _, _) in self.path.intermediaries() {
+            write!(f, " -> {} ({} msat)", node, fee)?;
+        }
+        write!(
+            f,
+            " -> {}: amount {} msat, fees {} msat, timelock {}",
+            self.path.dest,
+            self.path_amount(),
+            self.path_fees(),
+            self.time
+        )
+    }
+}
+
 impl PathFinder {
     /// New PathFinder for payment from src to dest transferring amount of msats
     pub fn new(
@@ -236,34 +348,170 @@ impl PathFinder {
         routing_metric: RoutingMetric,
         payment_parts: PaymentParts,
     ) -> Self {
-        Self {
+        let mut path_finder = Self {
             graph: Box::new(graph.clone()),
             src,
             dest,
             amount,
             routing_metric,
             payment_parts,
+            edge_weights: HashMap::new(),
+            excluded_nodes: HashSet::new(),
+            excluded_channels: HashSet::new(),
+            failure_penalties: HashMap::new(),
+            max_total_cltv: crate::MAX_TOTAL_CLTV,
+            candidate_pool: 1,
+            pending_candidates: None,
+            risk_factor: crate::RISK_FACTOR,
+            channel_priors: HashMap::new(),
+        };
+        path_finder.cache_edge_weights();
+        path_finder
+    }
+
+    /// Never route through the given nodes and channels. The graph itself is left untouched.
+    pub fn with_excluded(
+        mut self,
+        excluded_nodes: HashSet<ID>,
+        excluded_channels: HashSet<String>,
+    ) -> Self {
+        self.excluded_nodes = excluded_nodes;
+        self.excluded_channels = excluded_channels;
+        self
+    }
+
+    /// True if the edge leads to or belongs to an excluded node or channel
+    fn is_excluded(&self, edge: &Edge) -> bool {
+        self.excluded_channels.contains(&edge.channel_id)
+            || self.excluded_nodes.contains(&edge.source)
+            || self.excluded_nodes.contains(&edge.destination)
+    }
+
+    /// True if the edge is not excluded and a HTLC of the amount fits within its
+    /// htlc_maximum_msat
+    fn is_usable(&self, edge: &Edge) -> bool {
+        !self.is_excluded(edge) && edge.htlc_maximum_msat >= self.amount
+    }
+
+    /// Rejects paths whose accumulated timelock exceeds max_total_cltv blocks.
+    pub fn set_max_total_cltv(&mut self, max_total_cltv: usize) {
+        self.max_total_cltv = max_total_cltv;
+    }
+
+    /// Lets single-path payments consider the `candidate_pool` shortest paths (Yen's k) instead
+    /// of only the shortest one.
+    pub fn set_candidate_pool(&mut self, candidate_pool: usize) {
+        self.candidate_pool = candidate_pool.max(1);
+    }
+
+    /// Weighs the timelock penalty of the fee metric by `risk_factor` instead of LND's default.
+    pub fn set_risk_factor(&mut self, risk_factor: usize) {
+        self.risk_factor = risk_factor;
+        self.cache_edge_weights();
+    }
+
+    /// Blends the learned success probabilities of channels into their estimated probability.
+    pub fn set_channel_priors(&mut self, channel_priors: HashMap<String, f32>) {
+        self.channel_priors = channel_priors;
+        self.cache_edge_weights();
+    }
+
+    /// The learned success probability of the channel, 1 if nothing was learned
+    fn channel_prior(&self, channel_id: &str) -> f32 {
+        self.channel_priors.get(channel_id).copied().unwrap_or(1.0)
+    }
+
+    /// Changes the amount to find paths for. The edge weights depend on the amount and are
+    /// therefore recomputed.
+    pub fn set_amount(&mut self, amount: usize) {
+        if amount != self.amount {
+            self.amount = amount;
+            self.cache_edge_weights();
+        }
+    }
+
+    fn cache_edge_weights(&mut self) {
+        self.edge_weights = self
+            .graph
+            .edges
+            .iter()
+            .map(|(src, edges)| {
+                let weights = edges
+                    .iter()
+                    .map(|e| (e.channel_id.clone(), self.get_penalized_edge_weight(e)))
+                    .collect();
+                (src.clone(), weights)
+            })
+            .collect();
+    }
+
+    /// Penalises the channels of the node a payment attempt failed at so that retries of the
+    /// payment avoid the area, similar to LND's mission control.
+    /// Earlier penalties decay with every failure.
+    pub(crate) fn penalize_failure(&mut self, node: &ID) {
+        for penalty in self.failure_penalties.values_mut() {
+            *penalty *= crate::FAILURE_PENALTY_DECAY;
+        }
+        let channels: HashSet<String> = self
+            .graph
+            .get_outedges(node)
+            .into_iter()
+            .chain(self.graph.get_inedges(node))
+            .map(|e| e.channel_id)
+            .collect();
+        trace!("Penalising {} channels of {}.", channels.len(), node);
+        for channel_id in channels {
+            *self.failure_penalties.entry(channel_id).or_insert(0.0) += 1.0;
+        }
+        self.cache_edge_weights();
+    }
+
+    /// The edge's weight including the penalty of earlier failures. A full penalty costs as much
+    /// as the amount for [`RoutingMetric::MinFee`] and certain failure for
+    /// [`RoutingMetric::MaxProb`].
+    fn get_penalized_edge_weight(&self, edge: &Edge) -> EdgeWeight {
+        let weight = Self::get_edge_weight(
+            edge,
+            self.amount,
+            self.routing_metric,
+            self.risk_factor,
+            self.channel_prior(&edge.channel_id),
+        );
+        match self.failure_penalties.get(&edge.channel_id) {
+            Some(penalty) => {
+                let scale = match self.routing_metric {
+                    RoutingMetric::MinFee => self.amount as f32,
+                    RoutingMetric::MaxProb => 1.0,
+                };
+                weight + penalty * scale
+            }
+            None => weight,
         }
     }
 
     pub(crate) fn find_path(&mut self) -> Option<CandidatePath> {
         match self.payment_parts {
-            PaymentParts::Single => self.find_path_single_payment(),
-            PaymentParts::Split => self.find_path_mpp_payment(),
+            PaymentParts::Single | PaymentParts::SingleThenSplit => self.find_path_single_payment(),
+            PaymentParts::Split | PaymentParts::Redundant { .. } => self.find_path_mpp_payment(),
         }
     }
 
-    pub(super) fn get_edge_weight(edge: &Edge, amount: usize, metric: RoutingMetric) -> EdgeWeight {
+    pub(super) fn get_edge_weight(
+        edge: &Edge,
+        amount: usize,
+        metric: RoutingMetric,
+        risk_factor: usize,
+        prior: f32,
+    ) -> EdgeWeight {
         match metric {
-            RoutingMetric::MinFee => Self::get_edge_fee(edge, amount),
-            RoutingMetric::MaxProb => Self::get_edge_failure_probabilty(edge, amount),
+            RoutingMetric::MinFee => Self::get_edge_fee(edge, amount, risk_factor),
+            RoutingMetric::MaxProb => Self::get_edge_failure_probabilty(edge, amount, prior),
         }
     }
 
     /// Computes the weight of an edge as done in [LND](https://github.com/lightningnetwork/lnd/blob/290b78e700021e238f7e6bdce6acc80de8d0a64f/routing/pathfind.go#L263)
     /// Used when searching for the shortest path between two nodes.
-    fn get_edge_fee(edge: &Edge, amount: usize) -> EdgeWeight {
-        let risk_factor = 15;
+    fn get_edge_fee(edge: &Edge, amount: usize, risk_factor: usize) -> EdgeWeight {
         let millionths = 1000000;
         let billionths = 1000000000;
         let base_fee = edge.fee_base_msat;
@@ -276,15 +524,21 @@ impl PathFinder {
     /// weights it accordingly
     /// The higher the returned value, the lower the chances of success
     /// https://github.com/lnbook/lnbook/blob/develop/12_path_finding.asciidoc#liquidity-uncertainty-and-probability
-    fn get_edge_failure_probabilty(edge: &Edge, amount: usize) -> EdgeWeight {
-        let success_prob: f32 =
-            (edge.capacity as f32 + 1.0 - amount as f32) / (edge.capacity as f32 + 1.0);
-        ordered_float::OrderedFloat(1.0 - success_prob)
+    fn get_edge_failure_probabilty(edge: &Edge, amount: usize, prior: f32) -> EdgeWeight {
+        ordered_float::OrderedFloat(1.0 - Self::get_edge_success_probability(edge, amount, prior))
+    }
+
+    /// The estimated probability that the edge can forward the amount, scaled by the channel's
+    /// learned success probability `prior`
+    fn get_edge_success_probability(edge: &Edge, amount: usize, prior: f32) -> f32 {
+        prior * (edge.capacity as f32 + 1.0 - amount as f32) / (edge.capacity as f32 + 1.0)
     }
 
     /// Calculates the total probabilty along a given path starting from dest to src
     /// The first node can optionally be treated as an intermediary and demand fees. Used by
     /// adversary calculations
+    /// Paths no HTLC could use as their total timelock exceeds max_total_cltv are replaced by an
+    /// empty path.
     pub fn get_aggregated_path_cost(
         &mut self,
         candidate_path: &mut CandidatePath,
@@ -304,6 +558,7 @@ impl PathFinder {
             1.0
         };
         let mut accumulated_time = 0; // full timelock delta
+        let mut hop_probabilities = vec![];
         let candidate_path_hops: VecDeque<ID> = candidate_path
             .path
             .get_involved_nodes()
@@ -315,28 +570,40 @@ impl PathFinder {
                 // Edge from src to first hop
                 // safe because src is always last in the list
                 let (src, dest) = (node_id, candidate_path_hops[idx - 1].clone());
-                let cheapest_edge = match self.get_cheapest_edge(src, &dest) {
+                let channel_id = candidate_path.path.get_hop_channel(src);
+                let cheapest_edge = match self.get_path_edge(src, &dest, &channel_id) {
                     None => panic!("Edge in path does not exist! {src} -> {dest}"),
                     Some(e) => e,
                 };
                 if include_src {
                     // src charges a fee
+                    hop_probabilities.push(Self::get_edge_success_probability(
+                        &cheapest_edge,
+                        accumulated_amount,
+                        self.channel_prior(&cheapest_edge.channel_id),
+                    ));
                     match self.routing_metric {
                         RoutingMetric::MaxProb => {
                             accumulated_weight *= 1.0
                                 - Self::get_edge_failure_probabilty(
                                     &cheapest_edge,
                                     accumulated_amount,
+                                    self.channel_prior(&cheapest_edge.channel_id),
                                 )
                                 .into_inner()
                         }
                         RoutingMetric::MinFee => {
-                            accumulated_weight +=
-                                Self::get_edge_fee(&cheapest_edge, accumulated_amount).into_inner()
+                            accumulated_weight += Self::get_edge_fee(
+                                &cheapest_edge,
+                                accumulated_amount,
+                                self.risk_factor,
+                            )
+                            .into_inner()
                         }
                     };
-                    let edge_fee = Self::get_edge_fee(&cheapest_edge, accumulated_amount)
-                        .into_inner() as usize;
+                    let edge_fee =
+                        Self::get_edge_fee(&cheapest_edge, accumulated_amount, self.risk_factor)
+                            .into_inner() as usize;
                     accumulated_amount += edge_fee;
                     let edge_timelock = cheapest_edge.cltv_expiry_delta;
                     accumulated_time += edge_timelock;
@@ -349,7 +616,9 @@ impl PathFinder {
                 );
             } else if node_id.clone() == self.dest {
                 let (dest, src) = (node_id, candidate_path_hops[idx + 1].clone());
-                let cheapest_edge = match self.get_cheapest_edge(dest, &src) {
+                // prefer the reverse direction of the channel the payment arrives through
+                let channel_id = candidate_path.path.get_hop_channel(&src);
+                let cheapest_edge = match self.get_path_edge(dest, &src, &channel_id) {
                     None => panic!("Edge in path does not exist! {src} -> {dest}"),
                     Some(e) => e,
                 };
@@ -363,23 +632,35 @@ impl PathFinder {
                 let (src, dest) = (node_id, candidate_path_hops[idx - 1].clone());
                 // we are interested in the weight from src to dest (the previous node in the list) since that is the direction the
                 // payment will flow in
-                let cheapest_edge = match self.get_cheapest_edge(src, &dest) {
+                let channel_id = candidate_path.path.get_hop_channel(src);
+                let cheapest_edge = match self.get_path_edge(src, &dest, &channel_id) {
                     None => panic!("Edge in path does not exist! {src} -> {dest}"),
                     Some(e) => e,
                 };
+                hop_probabilities.push(Self::get_edge_success_probability(
+                    &cheapest_edge,
+                    accumulated_amount,
+                    self.channel_prior(&cheapest_edge.channel_id),
+                ));
                 match self.routing_metric {
                     RoutingMetric::MaxProb => {
                         accumulated_weight *= 1.0
-                            - Self::get_edge_failure_probabilty(&cheapest_edge, accumulated_amount)
-                                .into_inner()
+                            - Self::get_edge_failure_probabilty(
+                                &cheapest_edge,
+                                accumulated_amount,
+                                self.channel_prior(&cheapest_edge.channel_id),
+                            )
+                            .into_inner()
                     }
                     RoutingMetric::MinFee => {
                         accumulated_weight +=
-                            Self::get_edge_fee(&cheapest_edge, accumulated_amount).into_inner()
+                            Self::get_edge_fee(&cheapest_edge, accumulated_amount, self.risk_factor)
+                                .into_inner()
                     }
                 };
                 let edge_fee =
-                    Self::get_edge_fee(&cheapest_edge, accumulated_amount).into_inner() as usize;
+                    Self::get_edge_fee(&cheapest_edge, accumulated_amount, self.risk_factor)
+                        .into_inner() as usize;
                 accumulated_amount += edge_fee;
                 let edge_timelock = cheapest_edge.cltv_expiry_delta;
                 accumulated_time += edge_timelock;
@@ -391,21 +672,88 @@ impl PathFinder {
                 );
             }
         }
+        if accumulated_time > self.max_total_cltv {
+            trace!(
+                "Rejecting path with a total timelock of {} > {} blocks.",
+                accumulated_time,
+                self.max_total_cltv
+            );
+            *candidate_path = CandidatePath::default();
+            return;
+        }
         candidate_path.weight = accumulated_weight;
         candidate_path.amount = accumulated_amount;
         candidate_path.time = accumulated_time;
+        // the hops were visited from dest to src
+        hop_probabilities.reverse();
+        candidate_path.hop_probabilities = hop_probabilities;
     }
 
     /// Computes the shortest path beween source and dest using Dijkstra's algorithm
     pub fn shortest_path_from(&self, node: &ID) -> Option<(Vec<ID>, EdgeWeight)> {
+        self.shortest_route_from(node)
+            .map(|(route, weight)| (Self::route_nodes(route), weight))
+    }
+
+    /// Same as [`PathFinder::shortest_path_from`] but the path includes the channels the search
+    /// went through
+    pub(crate) fn shortest_route_from(&self, node: &ID) -> Option<(Route, EdgeWeight)> {
         trace!(
             "Looking for shortest paths between src {}, dest {} using {:?} as weight.",
             self.src,
             self.dest,
             self.routing_metric
         );
-        let successors = |node: &ID| -> Vec<(ID, EdgeWeight)> { self.get_successors(node) };
-        pathfinding::prelude::dijkstra(node, successors, |n| *n == self.dest)
+        let successors = |hop: &(ID, String)| -> Vec<((ID, String), EdgeWeight)> {
+            self.get_channel_successors(hop)
+        };
+        pathfinding::prelude::dijkstra(&(node.clone(), String::new()), successors, |(n, _)| {
+            *n == self.dest
+        })
+    }
+
+    /// Computes the shortest path from node to the nearest of `dests` other than node itself
+    /// using Dijkstra's algorithm. The path includes the channels the search went through
+    pub(crate) fn shortest_route_to_any(
+        &self,
+        node: &ID,
+        dests: &[ID],
+    ) -> Option<(Route, EdgeWeight)> {
+        trace!(
+            "Looking for shortest paths between src {} and any of {:?} using {:?} as weight.",
+            node,
+            dests,
+            self.routing_metric
+        );
+        let successors = |hop: &(ID, String)| -> Vec<((ID, String), EdgeWeight)> {
+            self.get_channel_successors(hop)
+        };
+        pathfinding::prelude::dijkstra(&(node.clone(), String::new()), successors, |(n, _)| {
+            n != node && dests.contains(n)
+        })
+    }
+
+    /// Computes the shortest paths from src to every reachable node using a single run of
+    /// Dijkstra's algorithm. Unlike [`PathFinder::shortest_path_from`] the PathFinder's src and
+    /// dest are ignored, i.e. src's channels carry no fees. The src itself is not part of the
+    /// tree.
+    pub fn shortest_path_tree(&self, src: &ID) -> HashMap<ID, (Vec<ID>, EdgeWeight)> {
+        trace!(
+            "Computing shortest path tree from src {} using {:?} as weight.",
+            src,
+            self.routing_metric
+        );
+        let successors = |node: &ID| -> Vec<(ID, EdgeWeight)> { self.get_successors(node, src) };
+        let parents = pathfinding::prelude::dijkstra_all(src, successors);
+        parents
+            .iter()
+            .map(|(node, (_, weight))| {
+                (
+                    node.clone(),
+                    (pathfinding::prelude::build_path(node, &parents), *weight),
+                )
+            })
+            .collect()
     }
 
     /// Computes the k shortest path beween source and dest using Dijkstra's algorithm
@@ -417,30 +765,105 @@ impl PathFinder {
             self.dest,
             self.routing_metric
         );
-        let successors = |node: &ID| -> Vec<(ID, EdgeWeight)> { self.get_successors(node) };
+        let successors =
+            |node: &ID| -> Vec<(ID, EdgeWeight)> { self.get_successors(node, &self.src) };
         pathfinding::prelude::yen(node, successors, |n| *n == self.dest, k)
     }
 
-    fn get_successors(&self, node: &ID) -> Vec<(ID, EdgeWeight)> {
-        let succs = match self.graph.get_edges_for_node(node) {
+    /// Same as [`PathFinder::k_shortest_paths_from`] but paths through parallel channels are told
+    /// apart and include the channels the search went through. Paths visiting a node twice are
+    /// dropped.
+    pub(crate) fn k_shortest_routes_from(&self, node: &ID, k: usize) -> Vec<(Route, EdgeWeight)> {
+        trace!(
+            "Looking for {} shortest routes between src {}, dest {} using {:?} as weight.",
+            k,
+            self.src,
+            self.dest,
+            self.routing_metric
+        );
+        let successors = |hop: &(ID, String)| -> Vec<((ID, String), EdgeWeight)> {
+            self.get_channel_successors(hop)
+        };
+        pathfinding::prelude::yen(
+            &(node.clone(), String::new()),
+            successors,
+            |(n, _)| *n == self.dest,
+            k,
+        )
+        .into_iter()
+        .filter(|(route, _)| route.iter().map(|(n, _)| n).all_unique())
+        .collect()
+    }
+
+    /// The nodes of the route
+    fn route_nodes(route: Route) -> Vec<ID> {
+        route.into_iter().map(|(node, _)| node).collect()
+    }
+
+    /// The successors of node and the weights of the edges to them. The edges of `root`, where
+    /// the search starts, carry no fees.
+    fn get_successors(&self, node: &ID, root: &ID) -> Vec<(ID, EdgeWeight)> {
+        self.weighted_out_edges(node, root)
+            .into_iter()
+            .map(|(e, weight)| (e.destination, weight))
+            .collect()
+    }
+
+    /// Same as [`PathFinder::get_successors`] but each successor carries the channel it is reached
+    /// through so that the search remembers which of several parallel channels it used.
+    /// Going back to the source or through the channel the node was reached by only makes for
+    /// loops, which would be free as the source's channels carry no weight.
+    fn get_channel_successors(
+        &self,
+        (node, channel_id): &(ID, String),
+    ) -> Vec<((ID, String), EdgeWeight)> {
+        self.weighted_out_edges(node, &self.src)
+            .into_iter()
+            .filter(|(e, _)| e.destination != self.src && e.channel_id != *channel_id)
+            .map(|(e, weight)| ((e.destination, e.channel_id), weight))
+            .collect()
+    }
+
+    /// The node's usable out-edges with the weights the search uses for them. The edges of the
+    /// search's `root` are weighted neutrally as the sender pays no fees to itself.
+    fn weighted_out_edges(&self, node: &ID, root: &ID) -> Vec<(Edge, EdgeWeight)> {
+        match self.graph.get_edges_for_node(node) {
             Some(edges) => edges
-                .iter()
+                .into_iter()
+                .filter(|e| self.is_usable(e))
                 .map(|e| {
-                    (
-                        e.destination.clone(),
-                        if e.source != self.src {
-                            Self::get_edge_weight(e, self.amount, self.routing_metric)
-                        } else if self.routing_metric == RoutingMetric::MinFee {
-                            ordered_float::OrderedFloat(0.0)
-                        } else {
-                            ordered_float::OrderedFloat(1.0)
-                        },
-                    )
+                    let weight = if e.source != *root {
+                        self.edge_weights
+                            .get(&e.source)
+                            .and_then(|weights| weights.get(&e.channel_id))
+                            .copied()
+                            .unwrap_or_else(|| self.get_penalized_edge_weight(&e))
+                    } else if self.routing_metric == RoutingMetric::MinFee {
+                        ordered_float::OrderedFloat(0.0)
+                    } else {
+                        ordered_float::OrderedFloat(1.0)
+                    };
+                    (e, weight)
                 })
                 .collect(),
             None => Vec::default(),
-        };
-        succs
+        }
+    }
+
+    /// Returns the edge between from and to belonging to the channel that was chosen during
+    /// pathfinding. Falls back to the cheapest edge if no such channel exists.
+    fn get_path_edge(&mut self, from: &ID, to: &ID, channel_id: &str) -> Option<Edge> {
+        if !channel_id.is_empty() {
+            if let Some(edge) = self
+                .graph
+                .get_all_src_dest_edges(from, to)
+                .into_iter()
+                .find(|e| e.channel_id == channel_id)
+            {
+                return Some(edge);
+            }
+        }
+        self.get_cheapest_edge(from, to)
     }
 
     /// Returns the "cheapest" edge between src and dist bearing the routing me in mind
@@ -451,8 +874,8 @@ impl PathFinder {
         let from_to_outedges = self.graph.get_all_src_dest_edges(from, to);
         let mut cheapest_edge = None;
         let mut min_weight = ordered_float::OrderedFloat(f32::MAX);
-        for edge in from_to_outedges.into_iter() {
-            let edge_weight = Self::get_edge_weight(&edge, self.amount, self.routing_metric);
+        for edge in from_to_outedges.into_iter().filter(|e| self.is_usable(e)) {
+            let edge_weight = self.get_penalized_edge_weight(&edge);
             if edge_weight < min_weight {
                 min_weight = edge_weight;
                 cheapest_edge = Some(edge);
@@ -469,7 +892,7 @@ impl PathFinder {
         for edge in graph.edges.iter() {
             // iter each node's edges
             for e in edge.1 {
-                if e.balance < amount {
+                if e.liquidity < amount || e.htlc_maximum_msat < amount {
                     ctr += 1;
                     copy.remove_edge(&e.source, &e.destination);
                 }
@@ -509,6 +932,50 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn excluded_nodes_and_channels_are_avoided() {
+        let path = std::path::Path::new("../test_data/trivial_multipath.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(path, network_parser::GraphSource::Lnresearch)
+                .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let find_route = |excluded_nodes: &[&str], excluded_channels: &[&str]| {
+            let mut path_finder = PathFinder::new(
+                "alice".to_string(),
+                "bob".to_string(),
+                1000,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            )
+            .with_excluded(
+                excluded_nodes.iter().map(|n| n.to_string()).collect(),
+                excluded_channels.iter().map(|c| c.to_string()).collect(),
+            );
+            let route = path_finder.find_path().map(|c| c.path.get_involved_nodes());
+            // excluding does not mutate the graph
+            assert_eq!(path_finder.graph.edge_count(), graph.edge_count());
+            assert_eq!(path_finder.graph.node_count(), graph.node_count());
+            route
+        };
+        let route = |nodes: &[&str]| Some(nodes.iter().map(|n| n.to_string()).collect());
+        assert_eq!(
+            find_route(&[], &[]),
+            route(&["alice", "carol", "eve", "bob"])
+        );
+        assert_eq!(find_route(&["eve"], &[]), route(&["alice", "carol", "bob"]));
+        assert_eq!(
+            find_route(&["carol"], &[]),
+            route(&["alice", "dave", "bob"])
+        );
+        assert_eq!(
+            find_route(&[], &["carol-eve"]),
+            route(&["alice", "carol", "bob"])
+        );
+        assert_eq!(find_route(&["carol", "dave"], &[]), None);
+    }
+
     #[test]
     fn edge_failure_probabilty() {
         let edge = Edge {
@@ -520,11 +987,11 @@ mod tests {
             ..Default::default()
         };
         let amount = 1;
-        let actual = PathFinder::get_edge_failure_probabilty(&edge, amount);
+        let actual = PathFinder::get_edge_failure_probabilty(&edge, amount, 1.0);
         let expected = 0.0;
         assert_abs_diff_eq!(actual.into_inner(), expected, epsilon = 0.2f32);
         let amount = 600;
-        let actual = PathFinder::get_edge_failure_probabilty(&edge, amount);
+        let actual = PathFinder::get_edge_failure_probabilty(&edge, amount, 1.0);
         let expected = 1.0;
         assert_abs_diff_eq!(actual.into_inner(), expected, epsilon = 0.2f32);
     }
@@ -539,11 +1006,11 @@ mod tests {
             ..Default::default()
         };
         let amount = 1;
-        let actual = PathFinder::get_edge_fee(&edge, amount);
+        let actual = PathFinder::get_edge_fee(&edge, amount, crate::RISK_FACTOR);
         let expected = 100.0;
         assert_eq!(actual, expected);
         let amount = 600;
-        let actual = PathFinder::get_edge_fee(&edge, amount);
+        let actual = PathFinder::get_edge_fee(&edge, amount, crate::RISK_FACTOR);
         let expected = 100.0;
         assert_eq!(actual, expected);
     }
@@ -553,7 +1020,7 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -563,6 +1030,7 @@ mod tests {
         for (_, edges) in graph.edges.iter_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let src = String::from("alice");
@@ -585,21 +1053,269 @@ mod tests {
                 ("dina".to_string(), 5000, 0, "dina1".to_string()),
             ]),
         };
+        // estimated success probability of a hop given its capacity
+        let success_prob =
+            |capacity: f32, amount: f32| (capacity + 1.0 - amount) / (capacity + 1.0);
         let expected: CandidatePath = CandidatePath {
             path: expected_path,
             weight: 175.0, // fees (b->c, c->d)
             amount: 5175,  // amount + fees
             time: 55,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![
+                success_prob(5000000.0, 5075.0),
+                success_prob(270000.0, 5000.0),
+            ],
         };
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn paths_exceeding_max_total_cltv_are_rejected() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let path_finder = |dest: &str| {
+            let mut path_finder = PathFinder::new(
+                String::from("alice"),
+                String::from(dest),
+                5000,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            );
+            path_finder.set_max_total_cltv(50);
+            path_finder
+        };
+        // alice -> bob -> chan -> dina takes 55 blocks
+        assert!(path_finder("dina").find_path().is_none());
+        let actual = path_finder("chan").find_path();
+        assert!(actual.is_some());
+        assert_eq!(actual.unwrap().time, 40);
+        let mut path_finder = path_finder("dina");
+        let mut candidate_path = CandidatePath::new_with_path(Path {
+            src: String::from("alice"),
+            dest: String::from("dina"),
+            hops: VecDeque::from([
+                ("alice".to_string(), 0, 0, "".to_string()),
+                ("bob".to_string(), 0, 0, "".to_string()),
+                ("chan".to_string(), 0, 0, "".to_string()),
+                ("dina".to_string(), 0, 0, "".to_string()),
+            ]),
+        });
+        path_finder.get_aggregated_path_cost(&mut candidate_path, false);
+        assert!(candidate_path.is_empty());
+        path_finder.set_max_total_cltv(crate::MAX_TOTAL_CLTV);
+        let actual = path_finder.find_path();
+        assert_eq!(actual.unwrap().time, 55);
+    }
+
+    #[test]
+    fn shortest_path_tree() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let mut graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let balance = 70000;
+        for (_, edges) in graph.edges.iter_mut() {
+            for e in edges {
+                e.balance = balance;
+                e.liquidity = balance;
+            }
+        }
+        let src = String::from("alice");
+        let amount = 5000;
+        let path_finder = PathFinder::new(
+            src.clone(),
+            String::from("dina"),
+            amount,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+        );
+        let tree = path_finder.shortest_path_tree(&src);
+        assert_eq!(tree.len(), graph.node_count() - 1);
+        for node in graph.get_node_ids() {
+            if node == src {
+                assert!(!tree.contains_key(&node));
+                continue;
+            }
+            // same as a search for this destination only
+            let expected = PathFinder::new(
+                src.clone(),
+                node.clone(),
+                amount,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            )
+            .shortest_path_from(&src)
+            .unwrap();
+            assert_eq!(tree[&node].1, expected.1);
+            assert_eq!(tree[&node].0.first(), Some(&src));
+            assert_eq!(tree[&node].0.last(), Some(&node));
+        }
+        assert_eq!(
+            tree[&String::from("dina")],
+            (
+                vec![
+                    String::from("alice"),
+                    String::from("bob"),
+                    String::from("chan"),
+                    String::from("dina")
+                ],
+                ordered_float::OrderedFloat(175.0)
+            )
+        );
+        // rooted at another node than the path finder's src, the root's channels are free
+        let root = String::from("chan");
+        let tree = path_finder.shortest_path_tree(&root);
+        assert_eq!(tree.len(), graph.node_count() - 1);
+        for node in graph
+            .get_node_ids()
+            .into_iter()
+            .filter(|node| *node != root)
+        {
+            let expected = PathFinder::new(
+                root.clone(),
+                node.clone(),
+                amount,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            )
+            .shortest_path_from(&root)
+            .unwrap();
+            assert_eq!(tree[&node], expected);
+        }
+        assert_eq!(
+            tree[&String::from("dina")].1,
+            ordered_float::OrderedFloat(0.0)
+        );
+    }
+
+    #[test]
+    fn cached_edge_weights() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let (src, dest) = (String::from("alice"), String::from("dina"));
+        for routing_metric in [RoutingMetric::MinFee, RoutingMetric::MaxProb] {
+            let mut cached = PathFinder::new(
+                src.clone(),
+                dest.clone(),
+                5000,
+                &graph,
+                routing_metric,
+                PaymentParts::Single,
+            );
+            assert_eq!(
+                cached
+                    .edge_weights
+                    .values()
+                    .map(HashMap::len)
+                    .sum::<usize>(),
+                graph.edge_count()
+            );
+            // without a cache all weights are computed on the fly
+            let mut uncached = cached.clone();
+            uncached.edge_weights.clear();
+            assert_eq!(
+                cached.shortest_path_from(&src),
+                uncached.shortest_path_from(&src)
+            );
+            assert_eq!(
+                cached.k_shortest_paths_from(&src, 3),
+                uncached.k_shortest_paths_from(&src, 3)
+            );
+            // the cache follows the amount
+            cached.set_amount(50000);
+            uncached.amount = 50000;
+            for edge in graph.edges.values().flatten() {
+                assert_eq!(
+                    cached.edge_weights[&edge.source][&edge.channel_id],
+                    PathFinder::get_edge_weight(
+                        edge,
+                        50000,
+                        routing_metric,
+                        crate::RISK_FACTOR,
+                        1.0
+                    )
+                );
+            }
+            assert_eq!(
+                cached.shortest_path_from(&src),
+                uncached.shortest_path_from(&src)
+            );
+        }
+    }
+
+    #[test]
+    fn cached_weights_of_lnd_directions() {
+        // both directions of a channel share its ID but not its policy
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "dave", 0),
+            ("alice", "carol", 0),
+            ("carol", "dave", 0),
+        ]);
+        for edge in graph.edges.values_mut().flatten() {
+            edge.fee_base_msat = match (edge.source.as_str(), edge.destination.as_str()) {
+                ("bob", "dave") | ("dave", "carol") => 1,
+                ("dave", "bob") => 1000,
+                ("carol", "dave") => 500,
+                _ => 0,
+            };
+        }
+        let path_finder = PathFinder::new(
+            "alice".to_string(),
+            "dave".to_string(),
+            1000,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+        );
+        for edge in graph.edges.values().flatten() {
+            assert_eq!(
+                path_finder.edge_weights[&edge.source][&edge.channel_id],
+                PathFinder::get_edge_weight(
+                    edge,
+                    1000,
+                    RoutingMetric::MinFee,
+                    crate::RISK_FACTOR,
+                    1.0
+                )
+            );
+        }
+        let (path, _) = path_finder
+            .shortest_path_from(&"alice".to_string())
+            .unwrap();
+        assert_eq!(path, vec!["alice", "bob", "dave"]);
+    }
+
     #[test]
     fn find_max_prob_paths() {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -609,6 +1325,7 @@ mod tests {
         for (_, edges) in graph.edges.iter_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let src = String::from("alice");
@@ -641,6 +1358,8 @@ mod tests {
             weight: 1.0,  // prob (b->c, c->d)
             amount: 5175, // amount + fees
             time: 55,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         // a and b equal if |a - b| <= epsilon
         assert_abs_diff_eq!(expected.weight, actual.weight, epsilon = 0.1f32);
@@ -653,20 +1372,20 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
-        let mut path_finder = PathFinder {
-            graph: Box::new(graph),
-            src: "dina".to_string(),
-            dest: "bob".to_string(),
-            amount: 10000,
-            routing_metric: RoutingMetric::MinFee,
-            payment_parts: PaymentParts::Single,
-        };
+        let mut path_finder = PathFinder::new(
+            "dina".to_string(),
+            "bob".to_string(),
+            10000,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+        );
         let path = Path {
             src: path_finder.src.clone(),
             dest: path_finder.dest.clone(),
@@ -676,8 +1395,8 @@ mod tests {
                 ("bob".to_string(), 0, 0, "".to_string()),
             ]),
         };
-        let mut candidate_path = &mut CandidatePath::new_with_path(path);
-        PathFinder::get_aggregated_path_cost(&mut path_finder, &mut candidate_path, false);
+        let candidate_path = &mut CandidatePath::new_with_path(path);
+        PathFinder::get_aggregated_path_cost(&mut path_finder, candidate_path, false);
         let (actual_weight, actual_amount, actual_time) = (
             candidate_path.weight,
             candidate_path.amount,
@@ -694,13 +1413,152 @@ mod tests {
         path_finder.routing_metric = RoutingMetric::MaxProb;
     }
 
+    #[test]
+    fn hops_below_htlc_maximum_are_excluded() {
+        // alice -> bob -> dave is cheaper than alice -> carol -> dave
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "dave", 10),
+            ("alice", "carol", 0),
+            ("carol", "dave", 50),
+        ]);
+        for edge in graph.edges.values_mut().flatten() {
+            if edge.channel_id == "bob-dave" {
+                edge.htlc_maximum_msat = 500;
+            }
+        }
+        let find_path = |amount| {
+            let mut path_finder = PathFinder::new(
+                "alice".to_string(),
+                "dave".to_string(),
+                amount,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            );
+            path_finder.find_path().unwrap().path.get_involved_nodes()
+        };
+        assert_eq!(find_path(500), vec!["alice", "bob", "dave"]);
+        assert_eq!(find_path(1000), vec!["alice", "carol", "dave"]);
+        let edges = PathFinder::remove_inadequate_edges(&graph, 1000);
+        assert!(edges.values().flatten().all(|e| e.channel_id != "bob-dave"));
+    }
+
+    #[test]
+    fn higher_risk_factor_prefers_lower_timelocks() {
+        // bob charges less than carol but demands a much longer timelock
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "dave", 10),
+            ("alice", "carol", 0),
+            ("carol", "dave", 100),
+        ]);
+        for edge in graph.edges.values_mut().flatten() {
+            edge.cltv_expiry_delta = match edge.channel_id.as_str() {
+                "bob-dave" => 1000,
+                _ => 10,
+            };
+        }
+        let find_path = |risk_factor| {
+            let mut path_finder = PathFinder::new(
+                "alice".to_string(),
+                "dave".to_string(),
+                5000,
+                &graph,
+                RoutingMetric::MinFee,
+                PaymentParts::Single,
+            );
+            path_finder.set_risk_factor(risk_factor);
+            path_finder.find_path().unwrap().path.get_involved_nodes()
+        };
+        assert_eq!(find_path(crate::RISK_FACTOR), vec!["alice", "bob", "dave"]);
+        assert_eq!(find_path(1000000), vec!["alice", "carol", "dave"]);
+    }
+
+    #[test]
+    fn aggregated_path_cost_uses_chosen_parallel_channel() {
+        let edge = |channel_id: &str, source: &str, destination: &str, fee_base_msat: usize| Edge {
+            channel_id: channel_id.to_string(),
+            source: source.to_string(),
+            destination: destination.to_string(),
+            fee_base_msat,
+            cltv_expiry_delta: 10,
+            balance: 100000,
+            liquidity: 100000,
+            capacity: 200000,
+            htlc_maximum_msat: 200000,
+            ..Default::default()
+        };
+        let graph = Graph {
+            nodes: ["a", "b", "c"]
+                .iter()
+                .map(|id| crate::Node {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            edges: HashMap::from([
+                ("a".to_string(), vec![edge("ab", "a", "b", 1)]),
+                (
+                    "b".to_string(),
+                    vec![
+                        edge("ab", "b", "a", 1),
+                        edge("cheap", "b", "c", 10),
+                        edge("pricey", "b", "c", 100),
+                    ],
+                ),
+                (
+                    "c".to_string(),
+                    vec![edge("cheap", "c", "b", 10), edge("pricey", "c", "b", 100)],
+                ),
+            ]),
+            ..Default::default()
+        };
+        let amount = 1000;
+        let mut path_finder = PathFinder::new(
+            "a".to_string(),
+            "c".to_string(),
+            amount,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+        );
+        // the search settles on the cheaper of the parallel channels
+        let candidate_path = path_finder.find_path().unwrap();
+        assert_eq!(candidate_path.path.hops[1].3, "cheap");
+        assert_eq!(candidate_path.amount, amount + 10);
+        // a path that was routed via the pricier channel is charged accordingly
+        let path = Path {
+            src: "a".to_string(),
+            dest: "c".to_string(),
+            hops: VecDeque::from([
+                ("a".to_string(), 0, 0, "ab".to_string()),
+                ("b".to_string(), 0, 0, "pricey".to_string()),
+                ("c".to_string(), 0, 0, "".to_string()),
+            ]),
+        };
+        let candidate_path = &mut CandidatePath::new_with_path(path);
+        path_finder.get_aggregated_path_cost(candidate_path, false);
+        let pricey = graph
+            .get_all_src_dest_edges(&"b".to_string(), &"c".to_string())
+            .into_iter()
+            .find(|e| e.channel_id == "pricey")
+            .unwrap();
+        let expected_fee =
+            PathFinder::get_edge_fee(&pricey, amount, crate::RISK_FACTOR).into_inner() as usize;
+        assert_eq!(candidate_path.amount, amount + expected_fee);
+        assert_eq!(candidate_path.path.hops[1].3, "pricey");
+        // the destination's hop refers to the reverse direction of the same channel
+        assert_eq!(candidate_path.path.hops[2].3, "pricey");
+    }
+
     // see above tests for calculations
     #[test]
     fn get_fees() {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let mut graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
@@ -710,6 +1568,7 @@ mod tests {
         for (_, edges) in graph.edges.iter_mut() {
             for e in edges {
                 e.balance = balance;
+                e.liquidity = balance;
             }
         }
         let src = String::from("alice");
@@ -747,6 +1606,8 @@ mod tests {
             weight: 175.0, // fees (b->c, c->d)
             amount: 5175,  // amount + fees
             time: 55,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
         };
         let node = "bob".to_string();
         let pred = path.path.get_pred(&node);
@@ -755,6 +1616,153 @@ mod tests {
         assert_eq!(succ, "chan".to_string());
     }
 
+    #[test]
+    fn path_totals() {
+        let mut path = Path {
+            src: String::from("alice"),
+            dest: String::from("dina"),
+            hops: VecDeque::from([
+                ("alice".to_string(), 5175, 55, "alice1".to_string()),
+                ("bob".to_string(), 100, 40, "bob2".to_string()),
+                ("chan".to_string(), 75, 15, "chan2".to_string()),
+                ("dina".to_string(), 5000, 0, "dina1".to_string()),
+            ]),
+        };
+        let candidate_path = CandidatePath::new_with_path(path.clone());
+        assert_eq!(path.total_fees(), 175);
+        assert_eq!(path.total_fees(), candidate_path.path_fees());
+        assert_eq!(path.total_timelock(), 55);
+        // a direct channel has no intermediaries
+        path.hops.drain(1..3);
+        assert_eq!(path.total_fees(), 0);
+        assert_eq!(path.total_timelock(), 0);
+        assert_eq!(Path::new(path.src, path.dest).total_fees(), 0);
+    }
+
+    #[test]
+    fn display_candidate_path() {
+        let path = Path {
+            src: String::from("alice"),
+            dest: String::from("dina"),
+            hops: VecDeque::from([
+                ("alice".to_string(), 5175, 55, "alice1".to_string()),
+                ("bob".to_string(), 100, 40, "bob2".to_string()),
+                ("chan".to_string(), 75, 15, "chan2".to_string()),
+                ("dina".to_string(), 5000, 0, "dina1".to_string()),
+            ]),
+        };
+        let candidate_path = CandidatePath {
+            path,
+            weight: 175.0,
+            amount: 5175,
+            time: 55,
+            hold_time_secs: 0.0,
+            hop_probabilities: vec![],
+        };
+        let rendered = candidate_path.to_string();
+        assert!(rendered.starts_with("alice -> bob"));
+        assert!(rendered.contains("-> dina"));
+        assert!(rendered.contains("fees 175 msat"));
+        assert_eq!(
+            rendered,
+            "alice -> bob (100 msat) -> chan (75 msat) -> dina: amount 5000 msat, fees 175 msat, timelock 55"
+        );
+        let empty = CandidatePath::new_with_path(Path::new("alice".into(), "dina".into()));
+        assert_eq!(empty.to_string(), "alice -> dina: no hops");
+    }
+
+    #[test]
+    fn success_probability_is_product_of_hops() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let mut path_finder = PathFinder::new(
+            String::from("alice"),
+            String::from("dina"),
+            5000,
+            &graph,
+            RoutingMetric::MaxProb,
+            PaymentParts::Single,
+        );
+        let path = path_finder.find_path().unwrap();
+        // bob forwards 5075 msat via bob2 (capacity 5000000), chan 5000 msat via chan2 (270000)
+        let bob2 = (5000000.0 + 1.0 - 5075.0) / (5000000.0 + 1.0);
+        let chan2 = (270000.0 + 1.0 - 5000.0) / (270000.0 + 1.0);
+        assert_eq!(path.hop_probabilities, vec![bob2, chan2]);
+        assert_eq!(path.success_probability(), bob2 * chan2);
+        assert_eq!(CandidatePath::default().success_probability(), 0.0);
+    }
+
+    #[test]
+    fn path_contains_nodes_and_channels() {
+        let path = Path {
+            src: String::from("alice"),
+            dest: String::from("dina"),
+            hops: VecDeque::from([
+                ("alice".to_string(), 5175, 55, "alice1".to_string()),
+                ("bob".to_string(), 100, 40, "bob2".to_string()),
+                ("chan".to_string(), 75, 15, "chan2".to_string()),
+                ("dina".to_string(), 5000, 0, "dina1".to_string()),
+            ]),
+        };
+        let candidate_path = CandidatePath::new_with_path(path);
+        for node in ["alice", "bob", "chan", "dina"] {
+            assert!(candidate_path.contains_node(&node.to_string()));
+        }
+        assert!(!candidate_path.contains_node(&"eve".to_string()));
+        for channel in ["alice1", "bob2", "chan2"] {
+            assert!(candidate_path.contains_channel(channel));
+        }
+        // the path does not forward along the receiver's channel
+        assert!(!candidate_path.contains_channel("dina1"));
+        assert!(!candidate_path.contains_channel("bob1"));
+        let empty = CandidatePath::new_with_path(Path::new("alice".into(), "dina".into()));
+        assert!(!empty.contains_node(&"alice".to_string()));
+        assert!(!empty.contains_channel("alice1"));
+    }
+
+    #[test]
+    fn reverse_path() {
+        let json_file = std::path::Path::new("../test_data/lnbook_example.json");
+        let graph = Graph::to_sim_graph(
+            &network_parser::Graph::from_json_file(
+                json_file,
+                network_parser::GraphSource::Lnresearch,
+            )
+            .unwrap(),
+            network_parser::GraphSource::Lnresearch,
+        );
+        let path = Path {
+            src: String::from("alice"),
+            dest: String::from("dina"),
+            hops: VecDeque::from([
+                ("alice".to_string(), 5175, 55, "alice1".to_string()),
+                ("bob".to_string(), 100, 40, "bob2".to_string()),
+                ("chan".to_string(), 75, 15, "chan2".to_string()),
+                ("dina".to_string(), 5000, 0, "dina1".to_string()),
+            ]),
+        };
+        let actual = path.reverse(&graph);
+        let expected = Path {
+            src: String::from("dina"),
+            dest: String::from("alice"),
+            hops: VecDeque::from([
+                ("dina".to_string(), 5000, 0, "dina1".to_string()),
+                ("chan".to_string(), 75, 15, "chan1".to_string()),
+                ("bob".to_string(), 100, 40, "bob1".to_string()),
+                ("alice".to_string(), 5175, 55, "alice1".to_string()),
+            ]),
+        };
+        assert_eq!(actual, expected);
+        assert_eq!(actual.reverse(&graph), path);
+    }
+
     #[test]
     fn adversary_in_path() {
         let path = Path {
@@ -780,20 +1788,20 @@ mod tests {
         let json_file = std::path::Path::new("../test_data/lnbook_example.json");
         let graph = Graph::to_sim_graph(
             &network_parser::Graph::from_json_file(
-                &json_file,
+                json_file,
                 network_parser::GraphSource::Lnresearch,
             )
             .unwrap(),
             network_parser::GraphSource::Lnresearch,
         );
-        let mut path_finder = PathFinder {
-            graph: Box::new(graph),
-            src: "dina".to_string(),
-            dest: "bob".to_string(),
-            amount: 10000,
-            routing_metric: RoutingMetric::MinFee,
-            payment_parts: PaymentParts::Single,
-        };
+        let mut path_finder = PathFinder::new(
+            "dina".to_string(),
+            "bob".to_string(),
+            10000,
+            &graph,
+            RoutingMetric::MinFee,
+            PaymentParts::Single,
+        );
         let path = Path {
             src: path_finder.src.clone(),
             dest: path_finder.dest.clone(),
@@ -803,8 +1811,8 @@ mod tests {
                 ("bob".to_string(), 0, 0, "".to_string()),
             ]),
         };
-        let mut candidate_path = &mut CandidatePath::new_with_path(path);
-        path_finder.get_aggregated_path_cost(&mut candidate_path, true);
+        let candidate_path = &mut CandidatePath::new_with_path(path);
+        path_finder.get_aggregated_path_cost(candidate_path, true);
         let (actual_weight, actual_amount, actual_time) = (
             candidate_path.weight,
             candidate_path.amount,
@@ -901,6 +1909,31 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn subpath_with_repeated_and_diverging_nodes() {
+        let path = |nodes: &[&str]| Path {
+            src: nodes[0].to_string(),
+            dest: nodes[nodes.len() - 1].to_string(),
+            hops: nodes
+                .iter()
+                .map(|n| (n.to_string(), 0, 0, String::default()))
+                .collect(),
+        };
+        let other_path = path(&["alice", "bob", "chan", "dina"]);
+        // shares the first hops but diverges
+        assert!(!path(&["alice", "bob", "eve"]).is_subpath(&other_path));
+        assert!(path(&["bob", "chan"]).is_subpath(&other_path));
+        assert!(!path(&["chan", "bob"]).is_subpath(&other_path));
+        assert!(!path(&["alice", "chan"]).is_subpath(&other_path));
+        // the first occurrence of bob is not followed by chan
+        let other_path = path(&["bob", "eve", "bob", "chan"]);
+        assert!(path(&["bob", "chan"]).is_subpath(&other_path));
+        assert!(path(&["eve", "bob", "chan"]).is_subpath(&other_path));
+        let other_path = path(&["chan", "bob", "chan"]);
+        assert!(!path(&["bob", "chan", "bob"]).is_subpath(&other_path));
+        assert!(!Path::new("bob".to_string(), "chan".to_string()).is_subpath(&other_path));
+    }
+
     #[test]
     fn is_first_intermediary() {
         let path = Path {
@@ -1,8 +1,8 @@
 use crate::{
-    core_types::{event::PaymentEvent, time::Time},
-    payment::Payment,
-    traversal::pathfinding::{CandidatePath, Path, PathFinder},
-    Simulation,
+    core_types::event::PaymentEvent,
+    payment::{FailureReason, Payment},
+    traversal::pathfinding::{CandidatePath, Path, PathFinder, Route},
+    PaymentParts, Simulation, ID,
 };
 
 use log::{error, trace};
@@ -14,18 +14,34 @@ impl Simulation {
     pub(crate) fn send_single_payment(&mut self, payment: &mut Payment) -> bool {
         let mut succeeded = false;
         let mut failed = false;
+        self.make_room_for_payment();
+        let balances_before = self.balance_delta_snapshot();
         // fail immediately if sender's balance on each of their edges < amount
         let max_out_balance = self.graph.get_max_node_balance(&payment.source);
         if max_out_balance < payment.amount_msat {
             error!("Payment failing. Sender has no edge with sufficient balance. Amount {}, max balance {}", payment.amount_msat, max_out_balance);
+            payment.failure_reason = Some(FailureReason::InsufficientSenderBalance);
             failed = true;
         }
         // we are not interested in reversing payments here for single path payments
         if !failed {
-            succeeded = self.send_one_payment(payment).0;
+            succeeded = self.send_one_payment(payment, self.payment_parts).0;
+        }
+        if !succeeded && self.payment_parts == PaymentParts::SingleThenSplit {
+            // the failed attempt has not moved any funds; retry by splitting the payment
+            trace!(
+                "Single payment {} failed, falling back to MPP.",
+                payment.payment_id
+            );
+            payment.failure_reason = None;
+            return self.send_mpp_payment(payment, PaymentParts::Split);
+        }
+        self.hold_in_flight_htlcs(payment.payment_id, succeeded);
+        if let Some(balances_before) = balances_before {
+            payment.balance_deltas = self.balance_deltas_since(&balances_before);
         }
-        let now = self.event_queue.now() + Time::from_secs(crate::SIM_DELAY_IN_SECS);
         let event = if succeeded {
+            self.fulfil_invoice(payment);
             PaymentEvent::UpdateSuccesful {
                 payment: payment.to_owned(),
             }
@@ -36,7 +52,8 @@ impl Simulation {
                 payment: payment.to_owned(),
             }
         };
-        self.event_queue.schedule(now, event);
+        self.event_queue
+            .schedule(self.settlement_delay(payment), event);
         succeeded
     }
 }
@@ -44,9 +61,34 @@ impl Simulation {
 impl PathFinder {
     /// Returns a route, the total amount due and lock time and none if no route is found
     /// Search for paths from dest to src
+    /// With a candidate pool, the k shortest paths are computed once and each call returns the
+    /// next one that the sender's own channel can fund and whose channels have not been removed
+    /// after an earlier failure.
     pub(super) fn find_path_single_payment(&mut self) -> Option<CandidatePath> {
+        if self.candidate_pool > 1 {
+            if self.pending_candidates.is_none() {
+                let src = self.src.clone();
+                let candidates: Vec<CandidatePath> = self
+                    .k_shortest_routes_from(&src, self.candidate_pool)
+                    .into_iter()
+                    .filter_map(|(route, _)| self.candidate_path_from_route(route))
+                    .collect();
+                self.pending_candidates = Some(
+                    candidates
+                        .into_iter()
+                        .filter(|candidate_path| self.sender_can_fund(candidate_path))
+                        .collect(),
+                );
+            }
+            while let Some(candidate_path) = self.pending_candidates.as_mut()?.pop_front() {
+                if self.channels_remain(&candidate_path) {
+                    return Some(candidate_path);
+                }
+            }
+            return None;
+        }
         // shortest path from src to dest including src and dest sorted in ascending cost order
-        let shortest_path = self.shortest_path_from(&self.src);
+        let shortest_path = self.shortest_route_from(&self.src);
         match shortest_path {
             None => {
                 trace!("No shortest path between {} and {}.", self.src, self.dest);
@@ -54,32 +96,87 @@ impl PathFinder {
             }
             // - calculate total path cost
             Some(shortest_path) => {
-                if shortest_path.0.len() > crate::MAX_HOPS + 2 {
-                    error!(
-                        "shortest path is too long. len =  {}!",
-                        shortest_path.0.len()
-                    );
-                    None
-                } else {
-                    trace!("Got shortest path between {} and {}.", self.src, self.dest);
-                    trace!(
-                        "Creating candidate path from {:?} shortest path.",
-                        shortest_path
-                    );
-                    let mut path = Path::new(self.src.clone(), self.dest.clone());
-                    // the weights and timelock are set as the total path costs are calculated
-                    path.hops = shortest_path
-                        .0
-                        .into_iter()
-                        .map(|h| (h, usize::default(), usize::default(), String::default()))
-                        .collect();
-                    let mut candidate_path = CandidatePath::new_with_path(path);
-                    self.get_aggregated_path_cost(&mut candidate_path, false);
-                    Some(candidate_path)
-                }
+                trace!("Got shortest path between {} and {}.", self.src, self.dest);
+                self.candidate_path_from_route(shortest_path.0)
             }
         }
     }
+
+    /// Finds the cheapest route from src to the nearest of `dests`, e.g. any of a set of LSPs.
+    /// The path finder's destination is set to the destination that was reached.
+    pub fn find_path_to_any(&mut self, dests: &[ID]) -> Option<CandidatePath> {
+        match self.shortest_route_to_any(&self.src, dests) {
+            None => {
+                trace!(
+                    "No shortest path between {} and any of {:?}.",
+                    self.src,
+                    dests
+                );
+                None
+            }
+            Some((route, _)) => {
+                self.dest = route.last()?.0.clone();
+                trace!("Got shortest path between {} and {}.", self.src, self.dest);
+                self.candidate_path_from_route(route)
+            }
+        }
+    }
+
+    /// Turns a route found by the search into a candidate path including its total costs.
+    /// None if the path is too long or its timelock too large.
+    fn candidate_path_from_route(&mut self, route: Route) -> Option<CandidatePath> {
+        if route.len() > crate::MAX_HOPS + 2 {
+            error!("shortest path is too long. len =  {}!", route.len());
+            return None;
+        }
+        trace!("Creating candidate path from {:?} shortest path.", route);
+        let mut path = Path::new(self.src.clone(), self.dest.clone());
+        // each hop uses the channel the search reached the next hop through so that the costs
+        // are calculated for the same (possibly parallel) channels; the weights and timelock are
+        // set as the total path costs are calculated
+        let next_channels: Vec<String> = route
+            .iter()
+            .skip(1)
+            .map(|(_, channel_id)| channel_id.clone())
+            .chain(std::iter::once(String::default()))
+            .collect();
+        path.hops = route
+            .into_iter()
+            .zip(next_channels)
+            .map(|((h, _), channel_id)| (h, usize::default(), usize::default(), channel_id))
+            .collect();
+        let mut candidate_path = CandidatePath::new_with_path(path);
+        self.get_aggregated_path_cost(&mut candidate_path, false);
+        if candidate_path.is_empty() {
+            None
+        } else {
+            Some(candidate_path)
+        }
+    }
+
+    /// True if the sender's channel of the path can pay the amount including all fees. The
+    /// sender only knows the liquidity of its own channels.
+    fn sender_can_fund(&self, candidate_path: &CandidatePath) -> bool {
+        let (sender, _, _, channel_id) = &candidate_path.path.hops[0];
+        let liquidity = self.graph.get_channel_liquidity(sender, channel_id);
+        if liquidity < candidate_path.amount {
+            trace!(
+                "Skipping candidate path as {} cannot pay {} msat.",
+                sender,
+                candidate_path.amount
+            );
+            return false;
+        }
+        true
+    }
+
+    /// True if none of the path's channels has been removed since the path was found.
+    fn channels_remain(&self, candidate_path: &CandidatePath) -> bool {
+        let hops = &candidate_path.path.hops;
+        hops.iter()
+            .take(hops.len() - 1)
+            .all(|(node, _, _, channel_id)| self.graph.node_channel(node, channel_id).is_some())
+    }
 }
 
 #[cfg(test)]
@@ -97,23 +194,36 @@ mod tests {
         let mut simulator = crate::attempt::tests::init_sim(None, None);
         let amount_msat = 1000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
             succeeded: true,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         assert!(simulator.send_single_payment(payment));
     }
 
+    #[test]
+    fn fulfilled_invoice_is_removed() {
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        let amount_msat = 1000;
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        simulator.add_invoice(Invoice::new(1, amount_msat, &"bob".to_string(), &dest));
+        let payment = &mut Payment::new(0, source.clone(), dest.clone(), amount_msat, None);
+        assert!(simulator.send_single_payment(payment));
+        let invoices = simulator.get_invoices_for_node(&dest).unwrap();
+        assert!(!invoices.contains_key(&0));
+        assert!(invoices.contains_key(&1));
+        // the same payment cannot be delivered twice
+        let payment = &mut Payment::new(0, source, dest, amount_msat, None);
+        assert!(!simulator.send_single_payment(payment));
+    }
+
     #[test]
     fn successful_payment_contains_correct_info() {
         let source = "alice".to_string();
@@ -121,18 +231,13 @@ mod tests {
         let mut simulator = crate::attempt::tests::init_sim(None, None);
         let amount_msat = 1000;
         let payment = &mut Payment {
-            payment_id: 0,
             source: source.clone(),
             dest: dest.clone(),
             amount_msat,
             succeeded: true,
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            used_paths: Vec::default(),
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
-            failed_paths: vec![],
+            ..Default::default()
         };
         simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
         assert!(simulator.send_single_payment(payment));
@@ -149,6 +254,9 @@ mod tests {
             weight: 100.0,
             amount: 1100,
             time: 40,
+            hold_time_secs: 2.0,
+            // bob2 has a capacity of 5000000 msat
+            hop_probabilities: vec![(5000000.0 + 1.0 - 1000.0) / (5000000.0 + 1.0)],
         };
         assert_eq!(payment.htlc_attempts, 2);
         assert!(payment.succeeded);
@@ -158,6 +266,68 @@ mod tests {
         assert!(payment.failed_paths.is_empty()); // since the single payment succeeds immediately
     }
 
+    #[test]
+    fn successful_payment_records_pathfinding_time() {
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        let amount_msat = 1000;
+        let already_spent_ms = 5;
+        let payment = &mut Payment {
+            pathfinding_ms: already_spent_ms,
+            ..Payment::new(0, source.clone(), dest.clone(), amount_msat, Some(10))
+        };
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        assert!(simulator.send_single_payment(payment));
+        // time is accumulated rather than overwritten by the shard conversion
+        assert!(payment.pathfinding_ms >= already_spent_ms);
+        let info = crate::io::PaymentInfo::from_payment(payment);
+        assert_eq!(info.pathfinding_ms, payment.pathfinding_ms);
+    }
+
+    #[test]
+    fn successful_payment_balance_deltas_are_conserved() {
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.set_balance_delta_log(true);
+        let amount_msat = 1000;
+        let payment = &mut Payment::new(0, source.clone(), dest.clone(), amount_msat, Some(10));
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        assert!(simulator.send_single_payment(payment));
+        // alice pays amount plus fees, bob earns the fees and chan receives the amount
+        let expected = vec![
+            ("alice".to_string(), "alice1".to_string(), -1100),
+            ("bob".to_string(), "bob2".to_string(), 100),
+            ("chan".to_string(), "chan1".to_string(), 1000),
+        ];
+        assert_eq!(payment.balance_deltas, expected);
+        assert_eq!(payment.balance_deltas.iter().map(|d| d.2).sum::<i64>(), 0);
+    }
+
+    #[test]
+    fn balance_deltas_of_lnd_channels_are_per_direction() {
+        let source = "alice".to_string();
+        let dest = "carol".to_string();
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        // both directions of a channel share its ID
+        simulator.graph =
+            crate::attempt::tests::graph_of_channels(&[("alice", "bob", 0), ("bob", "carol", 0)]);
+        simulator.set_balance_delta_log(true);
+        let amount_msat = 1000;
+        let payment = &mut Payment::new(0, source.clone(), dest.clone(), amount_msat, None);
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        assert!(simulator.send_single_payment(payment));
+        let deltas: Vec<(&str, &str)> = payment
+            .balance_deltas
+            .iter()
+            .map(|(node, channel_id, _)| (node.as_str(), channel_id.as_str()))
+            .collect();
+        assert!(deltas.contains(&("alice", "alice-bob")));
+        assert!(deltas.contains(&("carol", "bob-carol")));
+        assert_eq!(payment.balance_deltas.iter().map(|d| d.2).sum::<i64>(), 0);
+    }
+
     // checking that payment contains failed path. Failure at the last node due to no invoice
     #[test]
     fn failed_paths_in_failed_single_payment() {
@@ -166,21 +336,445 @@ mod tests {
         let dest = "chan".to_string();
         let mut simulator = crate::attempt::tests::init_sim(None, None);
         let mut payment = Payment {
-            payment_id: 0,
             source,
             dest,
             amount_msat: amount,
-            succeeded: false,
-            used_paths: vec![],
             min_shard_amt: 10,
-            htlc_attempts: 0,
             num_parts: 1,
-            failed_paths: vec![],
-            failed_amounts: Vec::default(),
-            successful_shards: Vec::default(),
+            ..Default::default()
+        };
+        assert!(!simulator.send_single_payment(&mut payment));
+        assert!(!payment.failed_paths.is_empty());
+        assert!(payment.used_paths.is_empty());
+    }
+
+    #[test]
+    fn failure_reasons_are_distinguished() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "chan".to_string();
+        // chan's channel towards bob is already full so it cannot receive anything
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        let capacity = simulator
+            .graph
+            .get_outedges(&dest)
+            .into_iter()
+            .find(|e| e.channel_id == "chan1")
+            .unwrap()
+            .capacity;
+        simulator
+            .graph
+            .update_channel_balance(&dest, &"chan1".to_string(), capacity);
+        simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+        let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, Some(10));
+        assert!(!simulator.send_single_payment(&mut payment));
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::DestinationCapacity)
+        );
+        // without bob's channel to chan there is no route at all
+        let mut simulator = crate::attempt::tests::init_sim(None, None);
+        simulator.graph.remove_channel(&"bob2".to_string());
+        simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+        let mut payment = Payment::new(0, source, dest, amount, Some(10));
+        assert!(!simulator.send_single_payment(&mut payment));
+        assert_eq!(payment.failure_reason, Some(FailureReason::NoRoute));
+        let info = crate::io::PaymentInfo::from_payment(&payment);
+        assert_eq!(info.failure_reason, Some(FailureReason::NoRoute));
+    }
+
+    #[test]
+    fn fee_budget() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "bob".to_string();
+        let json_file = "../test_data/trivial_multipath.json";
+        // the cheapest route via carol and eve costs 13 msat in fees
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        simulator.set_max_fee_msat(20);
+        simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+        let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, Some(10));
+        assert!(simulator.send_single_payment(&mut payment));
+        assert!(payment.used_paths[0].path_fees() <= 20);
+        assert_eq!(payment.failure_reason, None);
+        // every route is more expensive than the payment's own budget
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+        let mut payment = Payment {
+            max_fee_msat: Some(5),
+            ..Payment::new(0, source, dest, amount, Some(10))
         };
         assert!(!simulator.send_single_payment(&mut payment));
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::FeeBudgetExceeded)
+        );
         assert!(!payment.failed_paths.is_empty());
+        // no HTLC is sent, only the two alternate routes are tried; the fees of the last one
+        // exceed even the sender's balance
+        assert_eq!(payment.failed_paths.len(), 3);
+        assert_eq!(payment.htlc_attempts, 0);
+        assert_eq!(payment.route_attempts, 2);
+    }
+
+    #[test]
+    fn retries_are_capped() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "bob".to_string();
+        let json_file = "../test_data/trivial_multipath.json";
+        // without an invoice every route fails at the destination
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, Some(10));
+        assert!(!simulator.send_single_payment(&mut payment));
+        assert!(payment.failed_paths.len() > 2);
+
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        simulator.set_max_retries(1);
+        let mut payment = Payment::new(0, source, dest, amount, Some(10));
+        assert!(!simulator.send_single_payment(&mut payment));
+        // the first route and a single alternate one
+        assert_eq!(payment.failed_paths.len(), 2);
         assert!(payment.used_paths.is_empty());
     }
+
+    #[test]
+    fn retries_are_counted() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "dave".to_string();
+        let send = |max_retries: Option<usize>| {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            simulator.graph = crate::attempt::tests::graph_of_channels(&[
+                ("alice", "bob", 0),
+                ("bob", "dave", 10),
+                ("alice", "carol", 0),
+                ("carol", "dave", 20),
+            ]);
+            // both routes are rejected before any HTLC is sent
+            simulator.set_max_fee_msat(5);
+            if let Some(max_retries) = max_retries {
+                simulator.set_max_retries(max_retries);
+            }
+            simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+            let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, None);
+            assert!(!simulator.send_single_payment(&mut payment));
+            payment
+        };
+        // the second route is tried, then no route is left
+        let payment = send(None);
+        assert_eq!(payment.failed_paths.len(), 2);
+        assert_eq!(payment.route_attempts, 2);
+        assert_eq!(payment.htlc_attempts, 0);
+        let payment = send(Some(1));
+        assert_eq!(payment.failed_paths.len(), 2);
+        assert_eq!(payment.route_attempts, 1);
+        assert_eq!(payment.failure_reason, Some(FailureReason::MaxRetries));
+        let payment = send(Some(0));
+        assert_eq!(payment.failed_paths.len(), 1);
+        assert_eq!(payment.route_attempts, 0);
+    }
+
+    #[test]
+    fn random_htlc_failures() {
+        let amount = 1000;
+        let pairs = [("alice", "chan"), ("alice", "dina"), ("dina", "bob")];
+        for (prob, expected) in [(1.0, false), (0.0, true)] {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            simulator.set_random_failure_prob(prob);
+            for (id, (source, dest)) in pairs.iter().enumerate() {
+                let (source, dest) = (source.to_string(), dest.to_string());
+                simulator.add_invoice(Invoice::new(id, amount, &source, &dest));
+                let mut payment = Payment::new(id, source, dest, amount, None);
+                assert_eq!(simulator.send_single_payment(&mut payment), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn candidate_pool_skips_path_without_liquidity() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "dave".to_string();
+        let send = |candidate_pool| {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            // alice -> bob -> dave is cheaper than alice -> carol -> dave
+            simulator.graph = crate::attempt::tests::graph_of_channels(&[
+                ("alice", "bob", 0),
+                ("bob", "dave", 10),
+                ("alice", "carol", 0),
+                ("carol", "dave", 50),
+            ]);
+            // enough for the amount but not for bob's fee
+            simulator
+                .graph
+                .update_channel_balance(&source, &"alice-bob".to_string(), amount + 5);
+            simulator.set_candidate_pool(candidate_pool);
+            simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+            let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, None);
+            let succeeded = simulator.send_single_payment(&mut payment);
+            (succeeded, payment)
+        };
+        let (succeeded, payment) = send(1);
+        assert!(!succeeded);
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::InsufficientSenderBalance)
+        );
+        let (succeeded, payment) = send(3);
+        assert!(succeeded);
+        assert_eq!(
+            payment.used_paths[0].path.get_involved_nodes(),
+            vec!["alice", "carol", "dave"]
+        );
+    }
+
+    #[test]
+    fn candidate_pool_attempts_paths_in_order() {
+        let amount = 1000;
+        let source = "alice".to_string();
+        let dest = "dave".to_string();
+        let send = |depleted: &[(&str, &str)]| {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            // alice -> bob -> dave is cheaper than alice -> carol -> dave
+            simulator.graph = crate::attempt::tests::graph_of_channels(&[
+                ("alice", "bob", 0),
+                ("bob", "dave", 10),
+                ("alice", "carol", 0),
+                ("carol", "dave", 50),
+            ]);
+            // enough to be considered but too little to forward the amount; the sender cannot see
+            // remote balances
+            for (node, channel_id) in depleted {
+                simulator.graph.update_channel_balance(
+                    &node.to_string(),
+                    &channel_id.to_string(),
+                    amount,
+                );
+            }
+            simulator.set_candidate_pool(2);
+            simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+            let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, None);
+            let succeeded = simulator.send_single_payment(&mut payment);
+            (succeeded, payment)
+        };
+        let (succeeded, direct) = send(&[]);
+        assert!(succeeded);
+        assert!(direct.failed_paths.is_empty());
+        let (succeeded, payment) = send(&[("bob", "bob-dave")]);
+        assert!(succeeded);
+        assert_eq!(payment.failed_paths.len(), 1);
+        assert_eq!(
+            payment.failed_paths[0].path.get_involved_nodes(),
+            vec!["alice", "bob", "dave"]
+        );
+        assert_eq!(
+            payment.used_paths[0].path.get_involved_nodes(),
+            vec!["alice", "carol", "dave"]
+        );
+        // the HTLCs of the failed attempt are counted, too
+        assert!(payment.htlc_attempts > direct.htlc_attempts);
+        let (succeeded, payment) = send(&[("bob", "bob-dave"), ("carol", "carol-dave")]);
+        assert!(!succeeded);
+        assert_eq!(payment.failed_paths.len(), 2);
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::InsufficientLiquidity)
+        );
+    }
+
+    #[test]
+    fn candidate_pool_tells_parallel_channels_apart() {
+        let mut graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "dave", 10),
+            ("bob", "dave", 20),
+        ]);
+        // the pricier of the parallel channels gets its own ID
+        for edges in graph.edges.values_mut() {
+            for edge in edges.iter_mut().filter(|e| e.fee_base_msat == 20) {
+                edge.channel_id = "bob-dave-2".to_string();
+            }
+        }
+        graph.build_channel_index();
+        let mut path_finder = PathFinder::new(
+            "alice".to_string(),
+            "dave".to_string(),
+            1000,
+            &graph,
+            crate::RoutingMetric::MinFee,
+            crate::PaymentParts::Single,
+        );
+        path_finder.set_candidate_pool(2);
+        let candidate_path = path_finder.find_path_single_payment().unwrap();
+        assert_eq!(candidate_path.path.hops[1].3, "bob-dave");
+        assert_eq!(candidate_path.path_fees(), 10);
+        let candidate_path = path_finder.find_path_single_payment().unwrap();
+        assert_eq!(candidate_path.path.hops[1].3, "bob-dave-2");
+        assert_eq!(candidate_path.path_fees(), 20);
+        assert!(path_finder.find_path_single_payment().is_none());
+    }
+
+    #[test]
+    fn single_payment_falls_back_to_split() {
+        let json_file = "../test_data/trivial_multipath.json";
+        let source = "bob".to_string();
+        let dest = "alice".to_string();
+        let amount_msat = 12000;
+        let send = |payment_parts| {
+            let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+            // no single channel can forward the whole amount
+            let balance = 10000;
+            for edges in simulator.graph.edges.values_mut() {
+                for e in edges {
+                    e.balance = balance;
+                    e.liquidity = balance;
+                }
+            }
+            simulator.graph.update_channel_balance(
+                &String::from("carol"),
+                &String::from("carol-alice"),
+                2 * balance,
+            );
+            simulator.payment_parts = payment_parts;
+            simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+            let mut payment = Payment::new(0, source.clone(), dest.clone(), amount_msat, Some(10));
+            let succeeded = simulator.send_single_payment(&mut payment);
+            (succeeded, payment, simulator)
+        };
+        let (succeeded, payment, _) = send(PaymentParts::Single);
+        assert!(!succeeded);
+        assert_eq!(payment.num_parts, 1);
+        assert_eq!(
+            payment.failure_reason,
+            Some(FailureReason::InsufficientSenderBalance)
+        );
+        let (succeeded, payment, simulator) = send(PaymentParts::SingleThenSplit);
+        assert!(succeeded);
+        assert!(payment.succeeded);
+        assert!(payment.num_parts > 1);
+        assert_eq!(payment.used_paths.len(), payment.num_parts);
+        assert_eq!(simulator.payment_parts, PaymentParts::SingleThenSplit);
+        // only the outcome of the fallback is reported
+        assert_eq!(simulator.event_queue.queue_length(), 1);
+    }
+
+    #[test]
+    fn split_fallback_with_enforced_invoice_amount() {
+        let json_file = "../test_data/trivial_multipath.json";
+        let source = "bob".to_string();
+        let dest = "alice".to_string();
+        let amount_msat = 12000;
+        let mut simulator = crate::attempt::tests::init_sim(Some(json_file.to_string()), None);
+        let balance = 10000;
+        for edges in simulator.graph.edges.values_mut() {
+            for e in edges {
+                e.balance = balance;
+                e.liquidity = balance;
+            }
+        }
+        simulator.graph.update_channel_balance(
+            &String::from("carol"),
+            &String::from("carol-alice"),
+            2 * balance,
+        );
+        simulator.payment_parts = PaymentParts::SingleThenSplit;
+        simulator.set_enforce_invoice_amount(true);
+        simulator.add_invoice(Invoice::new(0, amount_msat, &source, &dest));
+        let mut payment = Payment::new(0, source.clone(), dest.clone(), amount_msat, Some(10));
+        // the shards only have to cover their part of the invoice
+        assert!(simulator.send_single_payment(&mut payment));
+        assert!(payment.num_parts > 1);
+        assert_eq!(payment.failure_reason, None);
+    }
+
+    #[test]
+    fn split_fallback_keeps_the_single_attempts() {
+        let amount = 6000;
+        let source = "alice".to_string();
+        let dest = "dave".to_string();
+        let send = |payment_parts| {
+            let mut simulator = crate::attempt::tests::init_sim(None, None);
+            simulator.graph = crate::attempt::tests::graph_of_channels(&[
+                ("alice", "bob", 0),
+                ("bob", "dave", 10),
+                ("alice", "carol", 0),
+                ("carol", "dave", 20),
+            ]);
+            // both routes can forward half of the amount but not all of it
+            for (node, channel_id) in [("bob", "bob-dave"), ("carol", "carol-dave")] {
+                simulator.graph.update_channel_balance(
+                    &node.to_string(),
+                    &channel_id.to_string(),
+                    amount,
+                );
+            }
+            simulator.payment_parts = payment_parts;
+            simulator.add_invoice(Invoice::new(0, amount, &source, &dest));
+            let mut payment = Payment::new(0, source.clone(), dest.clone(), amount, None);
+            let succeeded = match payment_parts {
+                PaymentParts::Split => simulator.send_mpp_payment(&mut payment, payment_parts),
+                _ => simulator.send_single_payment(&mut payment),
+            };
+            (succeeded, payment)
+        };
+        let (succeeded, single) = send(PaymentParts::Single);
+        assert!(!succeeded);
+        assert_eq!(single.failed_paths.len(), 2);
+        let (succeeded, split) = send(PaymentParts::Split);
+        assert!(succeeded);
+        let (succeeded, payment) = send(PaymentParts::SingleThenSplit);
+        assert!(succeeded);
+        assert_eq!(
+            payment.htlc_attempts,
+            single.htlc_attempts + split.htlc_attempts
+        );
+        assert_eq!(
+            payment.failed_paths.len(),
+            single.failed_paths.len() + split.failed_paths.len()
+        );
+        for (actual, expected) in payment.failed_paths.iter().zip(single.failed_paths.iter()) {
+            assert_eq!(
+                actual.path.get_involved_nodes(),
+                expected.path.get_involved_nodes()
+            );
+        }
+        assert_eq!(payment.used_paths.len(), 2);
+    }
+
+    #[test]
+    fn path_to_cheaper_of_two_destinations() {
+        // carol's route to lsp2 is cheaper than bob's to lsp1
+        let graph = crate::attempt::tests::graph_of_channels(&[
+            ("alice", "bob", 0),
+            ("bob", "lsp1", 50),
+            ("alice", "carol", 0),
+            ("carol", "lsp2", 10),
+        ]);
+        let mut path_finder = PathFinder::new(
+            "alice".to_string(),
+            String::default(),
+            1000,
+            &graph,
+            crate::RoutingMetric::MinFee,
+            crate::PaymentParts::Single,
+        );
+        let dests = ["lsp1".to_string(), "lsp2".to_string()];
+        let actual = path_finder.find_path_to_any(&dests).unwrap();
+        assert_eq!(
+            actual.path.get_involved_nodes(),
+            vec!["alice", "carol", "lsp2"]
+        );
+        assert_eq!(actual.path.dest, "lsp2");
+        assert_eq!(path_finder.dest, "lsp2");
+        assert_eq!(actual.path_fees(), 10);
+        // the sender itself is not a destination
+        let dests = ["alice".to_string(), "lsp1".to_string()];
+        let actual = path_finder.find_path_to_any(&dests).unwrap();
+        assert_eq!(
+            actual.path.get_involved_nodes(),
+            vec!["alice", "bob", "lsp1"]
+        );
+        assert!(path_finder.find_path_to_any(&["eve".to_string()]).is_none());
+    }
 }